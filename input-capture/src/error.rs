@@ -95,6 +95,9 @@ pub enum CaptureCreationError {
     #[cfg(target_os = "macos")]
     #[error("error creating macos capture backend")]
     MacOS(#[from] MacosCaptureCreationError),
+    #[cfg(all(target_os = "linux", feature = "evdev"))]
+    #[error("error creating evdev capture backend: `{0}`")]
+    Evdev(#[from] EvdevCaptureCreationError),
 }
 
 impl CaptureCreationError {
@@ -160,6 +163,15 @@ pub enum X11InputCaptureCreationError {
     NotImplemented,
 }
 
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+#[derive(Debug, Error)]
+pub enum EvdevCaptureCreationError {
+    #[error("no keyboard or pointer found in /dev/input (is the user in the input group?)")]
+    NoDevices,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
 #[cfg(target_os = "macos")]
 #[derive(Debug, Error)]
 pub enum MacosCaptureCreationError {
@@ -0,0 +1,712 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    mem,
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::fs::OpenOptionsExt,
+    },
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::mpsc as std_mpsc,
+    task::{ready, Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures_core::Stream;
+use input_event::{scancode, Event, KeyboardEvent, PointerEvent};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::{error::EvdevCaptureCreationError, CaptureOptions};
+
+use super::{Capture, CaptureError, CaptureEvent, CaptureHandle, Position};
+
+const INPUT_DIR: &str = "/dev/input";
+/// how often new devices are looked for
+const SCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+/* linux/input.h */
+const EVIOCGNAME: u32 = 0x06;
+const EVIOCGBIT: u32 = 0x20;
+const EVIOCGRAB: libc::Ioctl = libc::_IOW::<libc::c_int>(b'E' as u32, 0x90);
+
+/* linux/input-event-codes.h */
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const SYN_REPORT: u16 = 0;
+const SYN_DROPPED: u16 = 3;
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+const REL_WHEEL_HI_RES: u16 = 0x0b;
+const REL_HWHEEL_HI_RES: u16 = 0x0c;
+const KEY_A: u16 = 30;
+/// BTN_LEFT ..= BTN_TASK
+const BUTTONS: std::ops::RangeInclusive<u16> = 0x110..=0x117;
+/// key state reported for auto repeat
+const KEY_REPEAT: i32 = 2;
+
+/// xkb modifier masks of the default keymap
+const MODIFIERS: [(scancode::Linux, u32); 8] = [
+    (scancode::Linux::KeyLeftShift, 1 << 0),
+    (scancode::Linux::KeyRightShift, 1 << 0),
+    (scancode::Linux::KeyLeftCtrl, 1 << 2),
+    (scancode::Linux::KeyRightCtrl, 1 << 2),
+    (scancode::Linux::KeyLeftAlt, 1 << 3),
+    (scancode::Linux::KeyLeftMeta, 1 << 6),
+    (scancode::Linux::KeyRightmeta, 1 << 6),
+    (scancode::Linux::KeyRightalt, 1 << 7),
+];
+
+/// input capture reading `/dev/input/event*` directly.
+///
+/// Without screen geometry, clients are entered when a virtual cursor
+/// moved by the relative pointer motion hits the edge of a virtual screen
+/// or when the configured hotkey is pressed.
+/// While capturing, all devices are grabbed (`EVIOCGRAB`).
+pub(crate) struct EvdevInputCapture {
+    clients: Vec<(CaptureHandle, Position)>,
+    events: UnboundedReceiver<Event>,
+    reader: ReaderHandle,
+    cursor: VirtualCursor,
+    hotkey: Vec<scancode::Linux>,
+    /// keys and buttons currently pressed
+    pressed: HashSet<u32>,
+    modifiers: u32,
+    state: State,
+    last_entered: Option<CaptureHandle>,
+    pending: VecDeque<(CaptureHandle, CaptureEvent)>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    Idle,
+    /// waiting for all keys to be released before grabbing,
+    /// so the local session does not miss their release
+    Entering(CaptureHandle),
+    Capturing(CaptureHandle),
+}
+
+impl EvdevInputCapture {
+    pub(crate) fn new(options: &CaptureOptions) -> Result<Self, EvdevCaptureCreationError> {
+        let (tx, events) = mpsc::unbounded_channel();
+        let reader = Reader::spawn(tx)?;
+        let (width, height) = options.evdev_screen_size;
+        Ok(Self {
+            clients: vec![],
+            events,
+            reader,
+            cursor: VirtualCursor::new(width as f64, height as f64),
+            hotkey: options.evdev_hotkey.clone(),
+            pressed: HashSet::new(),
+            modifiers: 0,
+            state: State::Idle,
+            last_entered: None,
+            pending: VecDeque::new(),
+        })
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::Pointer(PointerEvent::Button { button, state, .. }) => {
+                self.update_pressed(button, state != 0)
+            }
+            Event::Keyboard(KeyboardEvent::Key { key, state, .. }) => {
+                self.update_pressed(key, state != 0)
+            }
+            _ => {}
+        }
+
+        match self.state {
+            State::Idle => {
+                let entered = match event {
+                    Event::Pointer(PointerEvent::Motion { dx, dy, .. }) => self
+                        .cursor
+                        .motion(dx, dy)
+                        .and_then(|edge| self.clients.iter().find(|(_, p)| *p == edge))
+                        .map(|&(h, _)| h),
+                    Event::Keyboard(KeyboardEvent::Key { state: 1, .. })
+                        if self.hotkey_pressed() =>
+                    {
+                        self.next_client()
+                    }
+                    _ => None,
+                };
+                if let Some(handle) = entered {
+                    self.state = State::Entering(handle);
+                }
+            }
+            State::Entering(_) => {}
+            State::Capturing(handle) => {
+                self.pending.push_back((handle, CaptureEvent::Input(event)));
+                if let Event::Keyboard(KeyboardEvent::Key { .. }) = event {
+                    let modifiers = self.modifiers();
+                    if modifiers != self.modifiers {
+                        self.modifiers = modifiers;
+                        let event = Event::Keyboard(KeyboardEvent::Modifiers {
+                            depressed: modifiers,
+                            latched: 0,
+                            locked: 0,
+                            group: 0,
+                        });
+                        self.pending.push_back((handle, CaptureEvent::Input(event)));
+                    }
+                }
+            }
+        }
+
+        if let State::Entering(handle) = self.state {
+            if self.pressed.is_empty() {
+                log::info!("capturing input for client {handle}");
+                self.reader.grab(true);
+                self.state = State::Capturing(handle);
+                self.last_entered = Some(handle);
+                self.modifiers = 0;
+                self.pending.push_back((handle, CaptureEvent::Begin));
+            }
+        }
+    }
+
+    fn update_pressed(&mut self, key: u32, pressed: bool) {
+        if pressed {
+            self.pressed.insert(key);
+        } else {
+            self.pressed.remove(&key);
+        }
+    }
+
+    fn hotkey_pressed(&self) -> bool {
+        !self.hotkey.is_empty()
+            && self
+                .hotkey
+                .iter()
+                .all(|&k| self.pressed.contains(&(k as u32)))
+    }
+
+    fn modifiers(&self) -> u32 {
+        MODIFIERS
+            .iter()
+            .filter(|(k, _)| self.pressed.contains(&(*k as u32)))
+            .fold(0, |mask, (_, m)| mask | m)
+    }
+
+    /// the client entered by the hotkey: the one after the most recently entered one
+    fn next_client(&self) -> Option<CaptureHandle> {
+        let idx = self
+            .last_entered
+            .and_then(|h| self.clients.iter().position(|&(c, _)| c == h))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        self.clients
+            .get(idx)
+            .or(self.clients.first())
+            .map(|&(h, _)| h)
+    }
+
+    fn ungrab(&mut self) {
+        if let State::Capturing(_) = self.state {
+            self.reader.grab(false);
+        }
+        self.state = State::Idle;
+        self.pressed.clear();
+        self.cursor.center();
+    }
+}
+
+#[async_trait]
+impl Capture for EvdevInputCapture {
+    async fn create(&mut self, handle: CaptureHandle, pos: Position) -> Result<(), CaptureError> {
+        self.clients.retain(|&(h, _)| h != handle);
+        self.clients.push((handle, pos));
+        Ok(())
+    }
+
+    async fn destroy(&mut self, handle: CaptureHandle) -> Result<(), CaptureError> {
+        self.clients.retain(|&(h, _)| h != handle);
+        self.pending.retain(|&(h, _)| h != handle);
+        if matches!(self.state, State::Entering(h) | State::Capturing(h) if h == handle) {
+            self.ungrab();
+        }
+        Ok(())
+    }
+
+    async fn release(&mut self) -> Result<(), CaptureError> {
+        self.ungrab();
+        self.pending.clear();
+        Ok(())
+    }
+
+    async fn terminate(&mut self) -> Result<(), CaptureError> {
+        self.ungrab();
+        self.reader.stop();
+        Ok(())
+    }
+}
+
+impl Stream for EvdevInputCapture {
+    type Item = Result<(CaptureHandle, CaptureEvent), CaptureError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            match ready!(self.events.poll_recv(cx)) {
+                Some(event) => self.handle_event(event),
+                None => return Poll::Ready(Some(Err(CaptureError::EndOfStream))),
+            }
+        }
+    }
+}
+
+/// cursor moved by relative motion on a screen of a fixed size
+struct VirtualCursor {
+    pos: (f64, f64),
+    size: (f64, f64),
+}
+
+impl VirtualCursor {
+    fn new(width: f64, height: f64) -> Self {
+        let mut cursor = Self {
+            pos: (0., 0.),
+            size: (width, height),
+        };
+        cursor.center();
+        cursor
+    }
+
+    fn center(&mut self) {
+        self.pos = (self.size.0 / 2., self.size.1 / 2.);
+    }
+
+    /// move the cursor, returns the edge it was pushed against
+    fn motion(&mut self, dx: f64, dy: f64) -> Option<Position> {
+        let (x, y) = (self.pos.0 + dx, self.pos.1 + dy);
+        self.pos = (x.clamp(0., self.size.0), y.clamp(0., self.size.1));
+        if x < 0. {
+            Some(Position::Left)
+        } else if x > self.size.0 {
+            Some(Position::Right)
+        } else if y < 0. {
+            Some(Position::Top)
+        } else if y > self.size.1 {
+            Some(Position::Bottom)
+        } else {
+            None
+        }
+    }
+}
+
+enum Command {
+    Grab(bool),
+    Stop,
+}
+
+/// handle of the thread reading the devices
+struct ReaderHandle {
+    commands: std_mpsc::Sender<Command>,
+    /// write end of a pipe waking up the reader
+    wake: File,
+}
+
+impl ReaderHandle {
+    fn send(&mut self, command: Command) {
+        let _ = self.commands.send(command);
+        let _ = self.wake.write(&[0]);
+    }
+
+    fn grab(&mut self, grab: bool) {
+        self.send(Command::Grab(grab));
+    }
+
+    fn stop(&mut self) {
+        self.send(Command::Stop);
+    }
+}
+
+impl Drop for ReaderHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct Reader {
+    devices: HashMap<PathBuf, Device>,
+    /// device nodes that are not keyboards or pointers
+    ignored: HashSet<PathBuf>,
+    grabbed: bool,
+    events: UnboundedSender<Event>,
+    commands: std_mpsc::Receiver<Command>,
+    /// read end of the wake up pipe
+    wake: File,
+}
+
+impl Reader {
+    fn spawn(events: UnboundedSender<Event>) -> Result<ReaderHandle, EvdevCaptureCreationError> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let (wake_rx, wake_tx) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        let (commands_tx, commands) = std_mpsc::channel();
+        let mut reader = Reader {
+            devices: HashMap::new(),
+            ignored: HashSet::new(),
+            grabbed: false,
+            events,
+            commands,
+            wake: wake_rx,
+        };
+        reader.scan();
+        if reader.devices.is_empty() {
+            return Err(EvdevCaptureCreationError::NoDevices);
+        }
+        thread::Builder::new()
+            .name("evdev-capture".into())
+            .spawn(move || reader.run())?;
+        Ok(ReaderHandle {
+            commands: commands_tx,
+            wake: wake_tx,
+        })
+    }
+
+    fn run(mut self) {
+        let mut last_scan = Instant::now();
+        loop {
+            loop {
+                match self.commands.try_recv() {
+                    Ok(Command::Grab(grab)) => self.grab(grab),
+                    Ok(Command::Stop) | Err(std_mpsc::TryRecvError::Disconnected) => {
+                        self.grab(false);
+                        return;
+                    }
+                    Err(std_mpsc::TryRecvError::Empty) => break,
+                }
+            }
+
+            let paths = self.devices.keys().cloned().collect::<Vec<_>>();
+            let mut fds = [self.wake.as_raw_fd()]
+                .into_iter()
+                .chain(paths.iter().map(|p| self.devices[p].file.as_raw_fd()))
+                .map(|fd| libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect::<Vec<_>>();
+            let timeout = SCAN_INTERVAL.as_millis() as libc::c_int;
+            if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } == -1 {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    log::error!("poll failed: {e}");
+                    return;
+                }
+            }
+
+            if fds[0].revents != 0 {
+                let mut buf = [0; 64];
+                while let Ok(1..) = self.wake.read(&mut buf) {}
+            }
+            for (fd, path) in fds[1..].iter().zip(paths) {
+                if fd.revents == 0 {
+                    continue;
+                }
+                let device = self.devices.get_mut(&path).expect("device");
+                if let Err(e) = device.read(&self.events) {
+                    log::info!("removing {} ({}): {e}", device.name, path.display());
+                    self.devices.remove(&path);
+                }
+            }
+
+            if self.events.is_closed() {
+                self.grab(false);
+                return;
+            }
+            if last_scan.elapsed() >= SCAN_INTERVAL {
+                self.scan();
+                last_scan = Instant::now();
+            }
+        }
+    }
+
+    fn grab(&mut self, grab: bool) {
+        self.grabbed = grab;
+        for (path, device) in self.devices.iter() {
+            if let Err(e) = device.grab(grab) {
+                log::warn!("could not grab {}: {e}", path.display());
+            }
+        }
+    }
+
+    /// open newly added devices
+    fn scan(&mut self) {
+        let entries = match fs::read_dir(INPUT_DIR) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("could not read {INPUT_DIR}: {e}");
+                return;
+            }
+        };
+        let paths = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with("event"))
+            })
+            .collect::<HashSet<_>>();
+        // node numbers are reused for new devices
+        self.ignored.retain(|p| paths.contains(p));
+        for path in paths {
+            if self.devices.contains_key(&path) || self.ignored.contains(&path) {
+                continue;
+            }
+            match Device::open(&path) {
+                Ok(Some(device)) => {
+                    log::info!("using {} ({})", device.name, path.display());
+                    if self.grabbed {
+                        let _ = device.grab(true);
+                    }
+                    self.devices.insert(path, device);
+                }
+                Ok(None) => {
+                    self.ignored.insert(path);
+                }
+                Err(e) => {
+                    log::debug!("could not open {}: {e}", path.display());
+                    self.ignored.insert(path);
+                }
+            }
+        }
+    }
+}
+
+/// an evdev keyboard or pointer
+struct Device {
+    file: File,
+    name: String,
+    frame: Frame,
+}
+
+impl Device {
+    /// opens the device, if it is a keyboard or a relative pointer
+    fn open(path: &Path) -> io::Result<Option<Self>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+            .open(path)?;
+        let name = ioctl_read::<256>(&file, EVIOCGNAME)?;
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        let name = String::from_utf8_lossy(&name[..len]).to_string();
+        // do not capture the virtual devices of the uinput emulation
+        if name.starts_with("lan-mouse") {
+            return Ok(None);
+        }
+        let ev_bits = ioctl_read::<4>(&file, EVIOCGBIT)?;
+        let is_pointer = test_bit(&ev_bits, EV_REL)
+            && test_bit(&ioctl_read::<2>(&file, EVIOCGBIT + EV_REL as u32)?, REL_X);
+        let is_keyboard = test_bit(&ev_bits, EV_KEY)
+            && test_bit(&ioctl_read::<96>(&file, EVIOCGBIT + EV_KEY as u32)?, KEY_A);
+        if !is_pointer && !is_keyboard {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            file,
+            name,
+            frame: Frame::default(),
+        }))
+    }
+
+    fn grab(&self, grab: bool) -> io::Result<()> {
+        match unsafe { libc::ioctl(self.file.as_raw_fd(), EVIOCGRAB, grab as libc::c_int) } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// read all available events, sending complete frames
+    fn read(&mut self, events: &UnboundedSender<Event>) -> io::Result<()> {
+        const SIZE: usize = mem::size_of::<libc::input_event>();
+        let mut buf = [0u8; 64 * SIZE];
+        loop {
+            let len = match self.file.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            for chunk in buf[..len].chunks_exact(SIZE) {
+                let event: libc::input_event =
+                    unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const _) };
+                for event in self.frame.push(event.type_, event.code, event.value) {
+                    let _ = events.send(event);
+                }
+            }
+        }
+    }
+}
+
+/// events of a device up to the next SYN_REPORT
+#[derive(Default)]
+struct Frame {
+    motion: (i32, i32),
+    /// low resolution scroll (vertical, horizontal)
+    wheel: (i32, i32),
+    hi_res_scroll: bool,
+    events: Vec<Event>,
+}
+
+impl Frame {
+    /// add an evdev event, returns the events of the frame once it is complete
+    fn push(&mut self, type_: u16, code: u16, value: i32) -> Vec<Event> {
+        match (type_, code) {
+            (EV_SYN, SYN_REPORT) => return self.finish(),
+            (EV_SYN, SYN_DROPPED) => *self = Default::default(),
+            (EV_REL, REL_X) => self.motion.0 += value,
+            (EV_REL, REL_Y) => self.motion.1 += value,
+            (EV_REL, REL_WHEEL) => self.wheel.0 += value,
+            (EV_REL, REL_HWHEEL) => self.wheel.1 += value,
+            (EV_REL, REL_WHEEL_HI_RES | REL_HWHEEL_HI_RES) => {
+                self.hi_res_scroll = true;
+                self.events
+                    .push(scroll_event(code == REL_HWHEEL_HI_RES, value));
+            }
+            (EV_KEY, _) if value == KEY_REPEAT => {}
+            (EV_KEY, button) if BUTTONS.contains(&button) => {
+                self.events.push(Event::Pointer(PointerEvent::Button {
+                    time: 0,
+                    button: button as u32,
+                    state: value as u32,
+                }))
+            }
+            (EV_KEY, key) => self.events.push(Event::Keyboard(KeyboardEvent::Key {
+                time: 0,
+                key: key as u32,
+                state: value as u8,
+            })),
+            _ => {}
+        }
+        vec![]
+    }
+
+    fn finish(&mut self) -> Vec<Event> {
+        let frame = mem::take(self);
+        let mut events = vec![];
+        if frame.motion != (0, 0) {
+            events.push(Event::Pointer(PointerEvent::Motion {
+                time: 0,
+                dx: frame.motion.0 as f64,
+                dy: frame.motion.1 as f64,
+            }));
+        }
+        events.extend(frame.events);
+        // devices with hi-res scrolling report both
+        if !frame.hi_res_scroll {
+            for (horizontal, detents) in [(false, frame.wheel.0), (true, frame.wheel.1)] {
+                if detents != 0 {
+                    events.push(scroll_event(horizontal, detents * 120));
+                }
+            }
+        }
+        events
+    }
+}
+
+/// evdev scrolls up / right for positive values, wayland down / right
+fn scroll_event(horizontal: bool, value120: i32) -> Event {
+    let (axis, value) = match horizontal {
+        false => (0, -value120),
+        true => (1, value120),
+    };
+    Event::Pointer(PointerEvent::AxisDiscrete120 { axis, value })
+}
+
+fn ioctl_read<const N: usize>(file: &File, nr: u32) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    let request = libc::_IOR::<[u8; N]>(b'E' as u32, nr);
+    match unsafe { libc::ioctl(file.as_raw_fd(), request, buf.as_mut_ptr()) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(buf),
+    }
+}
+
+fn test_bit(bits: &[u8], bit: u16) -> bool {
+    let bit = bit as usize;
+    bits.get(bit / 8).is_some_and(|b| b & (1 << (bit % 8)) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_hits_edges() {
+        let mut cursor = VirtualCursor::new(100., 50.);
+        assert_eq!(cursor.motion(-40., 0.), None);
+        assert_eq!(cursor.motion(-20., 0.), Some(Position::Left));
+        // pushing against the edge keeps reporting it
+        assert_eq!(cursor.pos, (0., 25.));
+        assert_eq!(cursor.motion(0., 30.), Some(Position::Bottom));
+        assert_eq!(cursor.motion(101., -1.), Some(Position::Right));
+        assert_eq!(cursor.motion(0., -100.), Some(Position::Top));
+    }
+
+    #[test]
+    fn frame_merges_motion() {
+        let mut frame = Frame::default();
+        assert!(frame.push(EV_REL, REL_X, 3).is_empty());
+        assert!(frame.push(EV_REL, REL_Y, -2).is_empty());
+        assert!(frame.push(EV_KEY, 0x110, 1).is_empty());
+        assert_eq!(
+            frame.push(EV_SYN, SYN_REPORT, 0),
+            vec![
+                Event::Pointer(PointerEvent::Motion {
+                    time: 0,
+                    dx: 3.,
+                    dy: -2.
+                }),
+                Event::Pointer(PointerEvent::Button {
+                    time: 0,
+                    button: 0x110,
+                    state: 1
+                }),
+            ]
+        );
+        assert!(frame.push(EV_SYN, SYN_REPORT, 0).is_empty());
+    }
+
+    #[test]
+    fn frame_prefers_hi_res_scroll() {
+        let mut frame = Frame::default();
+        frame.push(EV_REL, REL_WHEEL, 1);
+        frame.push(EV_REL, REL_WHEEL_HI_RES, 120);
+        assert_eq!(
+            frame.push(EV_SYN, SYN_REPORT, 0),
+            vec![Event::Pointer(PointerEvent::AxisDiscrete120 {
+                axis: 0,
+                value: -120
+            })]
+        );
+        frame.push(EV_REL, REL_HWHEEL, -1);
+        assert_eq!(
+            frame.push(EV_SYN, SYN_REPORT, 0),
+            vec![Event::Pointer(PointerEvent::AxisDiscrete120 {
+                axis: 1,
+                value: -120
+            })]
+        );
+    }
+
+    #[test]
+    fn frame_ignores_repeat() {
+        let mut frame = Frame::default();
+        frame.push(EV_KEY, KEY_A, KEY_REPEAT);
+        assert!(frame.push(EV_SYN, SYN_REPORT, 0).is_empty());
+    }
+}
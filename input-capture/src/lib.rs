@@ -22,6 +22,9 @@ mod wayland;
 #[cfg(windows)]
 mod windows;
 
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+mod evdev;
+
 #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
 mod x11;
 
@@ -90,6 +93,8 @@ pub enum Backend {
     Windows,
     #[cfg(target_os = "macos")]
     MacOs,
+    #[cfg(all(target_os = "linux", feature = "evdev"))]
+    Evdev,
     Dummy,
 }
 
@@ -106,11 +111,31 @@ impl Display for Backend {
             Backend::Windows => write!(f, "windows"),
             #[cfg(target_os = "macos")]
             Backend::MacOs => write!(f, "MacOS"),
+            #[cfg(all(target_os = "linux", feature = "evdev"))]
+            Backend::Evdev => write!(f, "evdev"),
             Backend::Dummy => write!(f, "dummy"),
         }
     }
 }
 
+/// options used by some of the backends
+#[derive(Clone, Debug)]
+pub struct CaptureOptions {
+    /// keys entering the next client (evdev)
+    pub evdev_hotkey: Vec<scancode::Linux>,
+    /// size of the virtual screen whose edges enter clients (evdev)
+    pub evdev_screen_size: (u32, u32),
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            evdev_hotkey: vec![],
+            evdev_screen_size: (1920, 1080),
+        }
+    }
+}
+
 pub struct InputCapture {
     capture: Box<dyn Capture>,
     pressed_keys: HashSet<scancode::Linux>,
@@ -140,7 +165,15 @@ impl InputCapture {
 
     /// creates a new [`InputCapture`]
     pub async fn new(backend: Option<Backend>) -> Result<Self, CaptureCreationError> {
-        let capture = create(backend).await?;
+        Self::with_options(backend, CaptureOptions::default()).await
+    }
+
+    /// creates a new [`InputCapture`] with backend specific options
+    pub async fn with_options(
+        backend: Option<Backend>,
+        options: CaptureOptions,
+    ) -> Result<Self, CaptureCreationError> {
+        let capture = create(backend, &options).await?;
         Ok(Self {
             capture,
             pressed_keys: HashSet::new(),
@@ -203,6 +236,7 @@ trait Capture: Stream<Item = Result<(CaptureHandle, CaptureEvent), CaptureError>
 
 async fn create_backend(
     backend: Backend,
+    #[allow(unused_variables)] options: &CaptureOptions,
 ) -> Result<
     Box<dyn Capture<Item = Result<(CaptureHandle, CaptureEvent), CaptureError>>>,
     CaptureCreationError,
@@ -218,18 +252,21 @@ async fn create_backend(
         Backend::Windows => Ok(Box::new(windows::WindowsInputCapture::new())),
         #[cfg(target_os = "macos")]
         Backend::MacOs => Ok(Box::new(macos::MacOSInputCapture::new().await?)),
+        #[cfg(all(target_os = "linux", feature = "evdev"))]
+        Backend::Evdev => Ok(Box::new(evdev::EvdevInputCapture::new(options)?)),
         Backend::Dummy => Ok(Box::new(dummy::DummyInputCapture::new())),
     }
 }
 
 async fn create(
     backend: Option<Backend>,
+    options: &CaptureOptions,
 ) -> Result<
     Box<dyn Capture<Item = Result<(CaptureHandle, CaptureEvent), CaptureError>>>,
     CaptureCreationError,
 > {
     if let Some(backend) = backend {
-        let b = create_backend(backend).await;
+        let b = create_backend(backend, options).await;
         if b.is_ok() {
             log::info!("using capture backend: {backend}");
         }
@@ -248,7 +285,7 @@ async fn create(
         #[cfg(target_os = "macos")]
         Backend::MacOs,
     ] {
-        match create_backend(backend).await {
+        match create_backend(backend, options).await {
             Ok(b) => {
                 log::info!("using capture backend: {backend}");
                 return Ok(b);
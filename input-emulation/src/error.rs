@@ -75,6 +75,9 @@ pub enum EmulationCreationError {
     #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
     #[error("x11: `{0}`")]
     X11(#[from] X11EmulationCreationError),
+    #[cfg(all(target_os = "linux", feature = "uinput"))]
+    #[error("uinput: `{0}`")]
+    Uinput(#[from] UinputEmulationCreationError),
     #[cfg(target_os = "macos")]
     #[error("macos: `{0}`")]
     MacOs(#[from] MacOSEmulationCreationError),
@@ -167,6 +170,13 @@ pub enum X11EmulationCreationError {
     OpenDisplay,
 }
 
+#[cfg(all(target_os = "linux", feature = "uinput"))]
+#[derive(Debug, Error)]
+pub enum UinputEmulationCreationError {
+    #[error("could not open /dev/uinput: `{0}`")]
+    Io(#[from] std::io::Error),
+}
+
 #[cfg(target_os = "macos")]
 #[derive(Debug, Error)]
 pub enum MacOSEmulationCreationError {
@@ -23,6 +23,9 @@ mod xdg_desktop_portal;
 #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
 mod libei;
 
+#[cfg(all(target_os = "linux", feature = "uinput"))]
+mod uinput;
+
 #[cfg(target_os = "macos")]
 mod macos;
 
@@ -42,6 +45,8 @@ pub enum Backend {
     Xdp,
     #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
     X11,
+    #[cfg(all(target_os = "linux", feature = "uinput"))]
+    Uinput,
     #[cfg(windows)]
     Windows,
     #[cfg(target_os = "macos")]
@@ -60,6 +65,8 @@ impl Display for Backend {
             Backend::Xdp => write!(f, "xdg-desktop-portal"),
             #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
             Backend::X11 => write!(f, "X11"),
+            #[cfg(all(target_os = "linux", feature = "uinput"))]
+            Backend::Uinput => write!(f, "uinput"),
             #[cfg(windows)]
             Backend::Windows => write!(f, "windows"),
             #[cfg(target_os = "macos")]
@@ -86,6 +93,8 @@ impl InputEmulation {
             Backend::X11 => Box::new(x11::X11Emulation::new()?),
             #[cfg(all(unix, feature = "xdg_desktop_portal", not(target_os = "macos")))]
             Backend::Xdp => Box::new(xdg_desktop_portal::DesktopPortalEmulation::new().await?),
+            #[cfg(all(target_os = "linux", feature = "uinput"))]
+            Backend::Uinput => Box::new(uinput::UinputEmulation::new()?),
             #[cfg(windows)]
             Backend::Windows => Box::new(windows::WindowsEmulation::new()?),
             #[cfg(target_os = "macos")]
@@ -117,6 +126,8 @@ impl InputEmulation {
             Backend::Xdp,
             #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
             Backend::X11,
+            #[cfg(all(target_os = "linux", feature = "uinput"))]
+            Backend::Uinput,
             #[cfg(windows)]
             Backend::Windows,
             #[cfg(target_os = "macos")]
@@ -0,0 +1,404 @@
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    mem,
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+};
+
+use input_event::{scancode, Event, KeyboardEvent, PointerEvent};
+
+use crate::error::{EmulationError, UinputEmulationCreationError};
+
+use super::{Emulation, EmulationHandle};
+
+const UINPUT_PATH: &str = "/dev/uinput";
+
+/* linux/uinput.h */
+const UI_DEV_CREATE: libc::Ioctl = libc::_IO(b'U' as u32, 1);
+const UI_DEV_DESTROY: libc::Ioctl = libc::_IO(b'U' as u32, 2);
+const UI_DEV_SETUP: libc::Ioctl = libc::_IOW::<libc::uinput_setup>(b'U' as u32, 3);
+const UI_SET_EVBIT: libc::Ioctl = libc::_IOW::<libc::c_int>(b'U' as u32, 100);
+const UI_SET_KEYBIT: libc::Ioctl = libc::_IOW::<libc::c_int>(b'U' as u32, 101);
+const UI_SET_RELBIT: libc::Ioctl = libc::_IOW::<libc::c_int>(b'U' as u32, 102);
+
+/* linux/input-event-codes.h */
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const SYN_REPORT: u16 = 0;
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+const REL_WHEEL_HI_RES: u16 = 0x0b;
+const REL_HWHEEL_HI_RES: u16 = 0x0c;
+const BUS_VIRTUAL: u16 = 0x06;
+const KEY_MAX: u16 = 0x2ff;
+/// BTN_LEFT ..= BTN_TASK
+const BUTTONS: std::ops::RangeInclusive<u16> = 0x110..=0x117;
+
+/// hi-res scroll units per detent
+const HI_RES_PER_DETENT: i32 = 120;
+/// hi-res scroll units per unit of smooth scrolling
+/// (matches the conversion of the wlroots backend)
+const HI_RES_PER_SMOOTH_UNIT: f64 = 6.;
+
+/// input emulation through virtual devices created with `/dev/uinput`,
+/// which works independently of the compositor (e.g. on a tty or the login screen)
+pub(crate) struct UinputEmulation {
+    devices: HashMap<EmulationHandle, VirtualInput>,
+}
+
+impl UinputEmulation {
+    pub(crate) fn new() -> Result<Self, UinputEmulationCreationError> {
+        // fail early if uinput is not accessible
+        open_uinput()?;
+        Ok(Self {
+            devices: HashMap::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl Emulation for UinputEmulation {
+    async fn consume(
+        &mut self,
+        event: Event,
+        handle: EmulationHandle,
+    ) -> Result<(), EmulationError> {
+        if let Some(input) = self.devices.get_mut(&handle) {
+            input.consume(event)?;
+        }
+        Ok(())
+    }
+
+    async fn create(&mut self, handle: EmulationHandle) {
+        match VirtualInput::new(handle) {
+            Ok(input) => {
+                self.devices.insert(handle, input);
+            }
+            Err(e) => log::error!("could not create uinput devices for client {handle}: {e}"),
+        }
+    }
+
+    async fn destroy(&mut self, handle: EmulationHandle) {
+        self.devices.remove(&handle);
+    }
+
+    async fn terminate(&mut self) {
+        self.devices.clear();
+    }
+}
+
+/// virtual keyboard and pointer of a client
+struct VirtualInput {
+    keyboard: Device,
+    pointer: Device,
+    /// sub-pixel motion not yet emitted
+    motion: (f64, f64),
+    /// hi-res scroll not yet emitted as a whole detent (vertical, horizontal)
+    scroll: [i32; 2],
+}
+
+impl VirtualInput {
+    fn new(handle: EmulationHandle) -> io::Result<Self> {
+        let keys = (1..=KEY_MAX)
+            .filter(|&k| scancode::Linux::try_from(k as u32).is_ok())
+            .filter(|k| !BUTTONS.contains(k))
+            .collect::<Vec<_>>();
+        let keyboard = Device::new(
+            &format!("lan-mouse virtual keyboard ({handle})"),
+            &keys,
+            &[],
+        )?;
+        let buttons = BUTTONS.collect::<Vec<_>>();
+        let axes = [
+            REL_X,
+            REL_Y,
+            REL_WHEEL,
+            REL_HWHEEL,
+            REL_WHEEL_HI_RES,
+            REL_HWHEEL_HI_RES,
+        ];
+        let pointer = Device::new(
+            &format!("lan-mouse virtual pointer ({handle})"),
+            &buttons,
+            &axes,
+        )?;
+        Ok(Self {
+            keyboard,
+            pointer,
+            motion: (0., 0.),
+            scroll: [0; 2],
+        })
+    }
+
+    fn consume(&mut self, event: Event) -> io::Result<()> {
+        match event {
+            Event::Pointer(PointerEvent::Motion { dx, dy, .. }) => {
+                let (x, y) = (self.motion.0 + dx, self.motion.1 + dy);
+                let (dx, dy) = (x.trunc(), y.trunc());
+                self.motion = (x - dx, y - dy);
+                self.pointer
+                    .emit(&[(EV_REL, REL_X, dx as i32), (EV_REL, REL_Y, dy as i32)])
+            }
+            Event::Pointer(PointerEvent::Button { button, state, .. }) => {
+                self.pointer
+                    .emit(&[(EV_KEY, button as u16, (state != 0) as i32)])
+            }
+            Event::Pointer(PointerEvent::Axis { axis, value, .. }) => {
+                self.scroll(axis, (value * HI_RES_PER_SMOOTH_UNIT).round() as i32)
+            }
+            Event::Pointer(PointerEvent::AxisDiscrete120 { axis, value }) => {
+                self.scroll(axis, value)
+            }
+            Event::Keyboard(KeyboardEvent::Key { key, state, .. }) => {
+                self.keyboard.emit(&[(EV_KEY, key as u16, state as i32)])
+            }
+            /* modifier state follows from the keys pressed on the virtual keyboard */
+            Event::Keyboard(KeyboardEvent::Modifiers { .. }) => Ok(()),
+        }
+    }
+
+    fn scroll(&mut self, axis: u8, value: i32) -> io::Result<()> {
+        let events = scroll_events(&mut self.scroll, axis, value);
+        self.pointer.emit(&events)
+    }
+}
+
+/// events for scrolling by `value` hi-res units, including low-res events
+/// for legacy clients whenever a whole detent is completed
+fn scroll_events(acc: &mut [i32; 2], axis: u8, value: i32) -> Vec<(u16, u16, i32)> {
+    // wayland scrolls down / right for positive values,
+    // evdev scrolls up / right
+    let (hi_res, low_res, value, acc) = match axis {
+        0 => (REL_WHEEL_HI_RES, REL_WHEEL, -value, &mut acc[0]),
+        _ => (REL_HWHEEL_HI_RES, REL_HWHEEL, value, &mut acc[1]),
+    };
+    *acc += value;
+    let detents = *acc / HI_RES_PER_DETENT;
+    *acc %= HI_RES_PER_DETENT;
+    if detents == 0 {
+        vec![(EV_REL, hi_res, value)]
+    } else {
+        vec![(EV_REL, hi_res, value), (EV_REL, low_res, detents)]
+    }
+}
+
+/// a uinput device, destroyed when dropped
+struct Device {
+    file: File,
+}
+
+impl Device {
+    fn new(name: &str, keys: &[u16], axes: &[u16]) -> io::Result<Self> {
+        let file = open_uinput()?;
+        let fd = file.as_raw_fd();
+        let ioctl = |request, arg: libc::c_int| match unsafe { libc::ioctl(fd, request, arg) } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        };
+        if !keys.is_empty() {
+            ioctl(UI_SET_EVBIT, EV_KEY as libc::c_int)?;
+            for &key in keys {
+                ioctl(UI_SET_KEYBIT, key as libc::c_int)?;
+            }
+        }
+        if !axes.is_empty() {
+            ioctl(UI_SET_EVBIT, EV_REL as libc::c_int)?;
+            for &axis in axes {
+                ioctl(UI_SET_RELBIT, axis as libc::c_int)?;
+            }
+        }
+
+        let mut setup: libc::uinput_setup = unsafe { mem::zeroed() };
+        setup.id.bustype = BUS_VIRTUAL;
+        for (dst, src) in setup.name.iter_mut().zip(name.bytes().take(79)) {
+            *dst = src as libc::c_char;
+        }
+        if unsafe { libc::ioctl(fd, UI_DEV_SETUP, &setup) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        if unsafe { libc::ioctl(fd, UI_DEV_CREATE) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        log::info!("created uinput device \"{name}\"");
+        Ok(Self { file })
+    }
+
+    /// write the given (type, code, value) events followed by a SYN_REPORT
+    fn emit(&mut self, events: &[(u16, u16, i32)]) -> io::Result<()> {
+        self.file.write_all(&encode(events))
+    }
+}
+
+/// `struct input_event`s for the given (type, code, value) events followed by a SYN_REPORT
+fn encode(events: &[(u16, u16, i32)]) -> Vec<u8> {
+    let report = (EV_SYN, SYN_REPORT, 0);
+    let mut buf = Vec::with_capacity((events.len() + 1) * mem::size_of::<libc::input_event>());
+    for &(type_, code, value) in events.iter().chain([&report]) {
+        // the kernel fills in the time of the event
+        let mut event: libc::input_event = unsafe { mem::zeroed() };
+        event.type_ = type_;
+        event.code = code;
+        event.value = value;
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &event as *const _ as *const u8,
+                mem::size_of::<libc::input_event>(),
+            )
+        };
+        buf.extend_from_slice(bytes);
+    }
+    buf
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        unsafe { libc::ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY) };
+    }
+}
+
+fn open_uinput() -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+        .open(UINPUT_PATH)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        io::{self, Read},
+        mem,
+        os::fd::AsRawFd,
+        path::PathBuf,
+        thread,
+        time::Duration,
+    };
+
+    use input_event::{Event, PointerEvent};
+
+    use super::*;
+
+    #[test]
+    fn scroll_completes_detents() {
+        let mut acc = [0; 2];
+        // wayland down is evdev up
+        assert_eq!(
+            scroll_events(&mut acc, 0, 60),
+            vec![(EV_REL, REL_WHEEL_HI_RES, -60)]
+        );
+        assert_eq!(
+            scroll_events(&mut acc, 0, 90),
+            vec![(EV_REL, REL_WHEEL_HI_RES, -90), (EV_REL, REL_WHEEL, -1)]
+        );
+        assert_eq!(acc, [-30, 0]);
+        // reversing direction first consumes the remainder
+        assert_eq!(
+            scroll_events(&mut acc, 0, -30),
+            vec![(EV_REL, REL_WHEEL_HI_RES, 30)]
+        );
+        assert_eq!(acc, [0, 0]);
+        // several detents at once, horizontal axis keeps its direction
+        assert_eq!(
+            scroll_events(&mut acc, 1, 250),
+            vec![(EV_REL, REL_HWHEEL_HI_RES, 250), (EV_REL, REL_HWHEEL, 2)]
+        );
+        assert_eq!(acc, [0, 10]);
+    }
+
+    fn decode(buf: &[u8]) -> Vec<(u16, u16, i32)> {
+        buf.chunks_exact(mem::size_of::<libc::input_event>())
+            .map(|chunk| {
+                let event: libc::input_event =
+                    unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const _) };
+                (event.type_, event.code, event.value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encode_appends_syn_report() {
+        let events = [(EV_REL, REL_X, -3), (EV_KEY, 0x110, 1)];
+        let buf = encode(&events);
+        assert_eq!(buf.len(), 3 * mem::size_of::<libc::input_event>());
+        assert_eq!(
+            decode(&buf),
+            vec![
+                (EV_REL, REL_X, -3),
+                (EV_KEY, 0x110, 1),
+                (EV_SYN, SYN_REPORT, 0)
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn encode_matches_kernel_layout() {
+        // struct timeval (16 bytes), __u16 type, __u16 code, __s32 value
+        let buf = encode(&[(EV_REL, REL_WHEEL_HI_RES, -120)]);
+        assert_eq!(buf.len(), 2 * 24);
+        assert_eq!(&buf[..16], &[0; 16]);
+        assert_eq!(&buf[16..18], &EV_REL.to_ne_bytes());
+        assert_eq!(&buf[18..20], &REL_WHEEL_HI_RES.to_ne_bytes());
+        assert_eq!(&buf[20..24], &(-120i32).to_ne_bytes());
+    }
+
+    /// evdev node of a uinput device
+    fn event_node(device: &Device) -> io::Result<PathBuf> {
+        let mut sysname = [0u8; 64];
+        let request = libc::_IOR::<[u8; 64]>(b'U' as u32, 44);
+        if unsafe { libc::ioctl(device.file.as_raw_fd(), request, sysname.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let len = sysname
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(sysname.len());
+        let sysname = String::from_utf8_lossy(&sysname[..len]).to_string();
+        let dir = PathBuf::from("/sys/devices/virtual/input").join(sysname);
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if name.starts_with("event") {
+                return Ok(PathBuf::from("/dev/input").join(name));
+            }
+        }
+        Err(io::ErrorKind::NotFound.into())
+    }
+
+    #[test]
+    #[ignore = "requires access to /dev/uinput and /dev/input"]
+    fn scroll_is_read_back_from_evdev() {
+        let mut input = VirtualInput::new(0).expect("could not create uinput devices");
+        let node = event_node(&input.pointer).expect("no evdev node");
+        // udev may take a moment to create the node
+        let mut reader = (0..50)
+            .find_map(|_| {
+                File::open(&node)
+                    .inspect_err(|_| thread::sleep(Duration::from_millis(20)))
+                    .ok()
+            })
+            .expect("could not open evdev node");
+
+        let scroll = Event::Pointer(PointerEvent::AxisDiscrete120 {
+            axis: 0,
+            value: 120,
+        });
+        input.consume(scroll).unwrap();
+
+        let mut buf = vec![0; 3 * mem::size_of::<libc::input_event>()];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(
+            decode(&buf),
+            vec![
+                (EV_REL, REL_WHEEL_HI_RES, -120),
+                (EV_REL, REL_WHEEL, -1),
+                (EV_SYN, SYN_REPORT, 0)
+            ]
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt::{self, Display};
 
+use serde::{Deserialize, Serialize};
+
 pub mod error;
 pub mod scancode;
 
@@ -13,7 +15,7 @@ pub const BTN_MIDDLE: u32 = 0x112;
 pub const BTN_BACK: u32 = 0x113;
 pub const BTN_FORWARD: u32 = 0x114;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum PointerEvent {
     /// relative motion event
     Motion { time: u32, dx: f64, dy: f64 },
@@ -25,7 +27,7 @@ pub enum PointerEvent {
     AxisDiscrete120 { axis: u8, value: i32 },
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum KeyboardEvent {
     /// a key press / release event
     Key { time: u32, key: u32, state: u8 },
@@ -38,7 +40,7 @@ pub enum KeyboardEvent {
     },
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Event {
     /// pointer event (motion / button / axis)
     Pointer(PointerEvent),
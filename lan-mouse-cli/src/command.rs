@@ -15,6 +15,7 @@ pub(super) enum CommandType {
     List,
     SetHost,
     SetPort,
+    Wake,
 }
 
 #[derive(Debug)]
@@ -40,6 +41,7 @@ impl FromStr for CommandType {
             "list" => Ok(Self::List),
             "set-host" => Ok(Self::SetHost),
             "set-port" => Ok(Self::SetPort),
+            "wake" => Ok(Self::Wake),
             "help" => Ok(Self::Help),
             _ => Err(InvalidCommand { cmd: s.to_string() }),
         }
@@ -57,6 +59,7 @@ pub(super) enum Command {
     List,
     SetHost(ClientHandle, String),
     SetPort(ClientHandle, Option<u16>),
+    Wake(ClientHandle),
 }
 
 impl CommandType {
@@ -71,6 +74,7 @@ impl CommandType {
             CommandType::List => "list",
             CommandType::SetHost => "set-host <id> <host>",
             CommandType::SetPort => "set-port <id> <host>",
+            CommandType::Wake => "wake <id>",
         }
     }
 }
@@ -108,6 +112,7 @@ impl FromStr for Command {
             CommandType::List => Ok(Command::List),
             CommandType::SetHost => parse_set_host(args),
             CommandType::SetPort => parse_set_port(args),
+            CommandType::Wake => parse_wake(args),
         }
     }
 }
@@ -151,3 +156,9 @@ fn parse_set_port(mut args: SplitWhitespace<'_>) -> Result<Command, CommandParse
     let port = args.next().and_then(|p| p.parse().ok());
     Ok(Command::SetPort(id, port))
 }
+
+fn parse_wake(mut args: SplitWhitespace<'_>) -> Result<Command, CommandParseError> {
+    const USAGE: CommandParseError = CommandParseError::Usage(CommandType::Wake);
+    let id = args.next().ok_or(USAGE)?.parse().map_err(|_| USAGE)?;
+    Ok(Command::Wake(id))
+}
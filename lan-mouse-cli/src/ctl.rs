@@ -0,0 +1,600 @@
+use std::{env, io, net::SocketAddr, path::PathBuf, time::Duration};
+
+use clap::{Args, Subcommand};
+use futures::StreamExt;
+use serde::Serialize;
+use thiserror::Error;
+
+use lan_mouse_ipc::{
+    AsyncFrontendEventReader, AsyncFrontendRequestWriter, ClientConfig, ClientHandle, ClientState,
+    ConnectionError, EventClass, EventFilter, FocusState, FrontendEvent, FrontendMessage,
+    FrontendReply, FrontendRequest, IpcError, LivenessAction, MacAddress, Position, Status,
+    Transport, DEFAULT_PORT,
+};
+
+/// one-shot commands for controlling a running lan-mouse service
+#[derive(Args, Clone, Debug, Eq, PartialEq)]
+pub struct CtlArgs {
+    /// print results as json
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// seconds to wait for the service to respond
+    #[arg(long, global = true, default_value_t = 5)]
+    timeout: u64,
+
+    /// connect to a service listening for frontends on this tcp address.
+    /// the token is read from $LAN_MOUSE_IPC_TOKEN or the token file of a local service
+    #[arg(long, global = true)]
+    ipc_tcp: Option<SocketAddr>,
+
+    #[command(subcommand)]
+    command: CtlCommand,
+}
+
+#[derive(Subcommand, Clone, Debug, Eq, PartialEq)]
+enum CtlCommand {
+    #[command(flatten)]
+    Request(RequestCommand),
+    /// print events of the service as json lines until it exits
+    Watch,
+}
+
+/// commands that send requests and exit once they are answered
+#[derive(Subcommand, Clone, Debug, Eq, PartialEq)]
+enum RequestCommand {
+    /// list all clients
+    List,
+    /// add a new client
+    Connect {
+        /// left | right | top | bottom
+        pos: Position,
+        host: String,
+        port: Option<u16>,
+    },
+    /// remove a client
+    Disconnect { id: ClientHandle },
+    /// activate a client
+    Activate { id: ClientHandle },
+    /// deactivate a client
+    Deactivate { id: ClientHandle },
+    /// change the hostname of a client
+    SetHost { id: ClientHandle, host: String },
+    /// change the port of a client
+    SetPort { id: ClientHandle, port: Option<u16> },
+    /// change the transport of a client (udp | tcp)
+    SetTransport {
+        id: ClientHandle,
+        transport: Transport,
+    },
+    /// change how a client is checked for liveness
+    SetLiveness {
+        id: ClientHandle,
+        /// time between pings in milliseconds
+        #[arg(long)]
+        interval: Option<u64>,
+        /// unanswered pings before the action is taken
+        #[arg(long)]
+        max_missed: Option<u32>,
+        /// release | release-keys | warn
+        #[arg(long)]
+        action: Option<LivenessAction>,
+    },
+    /// change the mac address of a client used for wake-on-lan
+    SetMac {
+        id: ClientHandle,
+        mac: Option<MacAddress>,
+    },
+    /// send a wake-on-lan packet to a client
+    Wake { id: ClientHandle },
+    /// change the events forwarded to a client (motion, buttons, scroll, keys)
+    SetSendEvents {
+        id: ClientHandle,
+        #[arg(value_delimiter = ',')]
+        events: Vec<EventClass>,
+    },
+    /// change the events accepted from a client (motion, buttons, scroll, keys)
+    SetReceiveEvents {
+        id: ClientHandle,
+        #[arg(value_delimiter = ',')]
+        events: Vec<EventClass>,
+    },
+    /// change the listen port of the service
+    ChangePort { port: u16 },
+    /// reenable input capture
+    EnableCapture,
+    /// reenable input emulation
+    EnableEmulation,
+}
+
+#[derive(Debug, Error)]
+pub enum CtlError {
+    #[error(transparent)]
+    Ipc(#[from] IpcError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+    #[error("no such client: {0}")]
+    NoSuchClient(ClientHandle),
+    #[error("{0}")]
+    Failed(String),
+    #[error("timed out waiting for the lan-mouse service")]
+    Timeout,
+    #[error("connection to the lan-mouse service closed")]
+    Disconnected,
+}
+
+impl CtlError {
+    /// exit status of the process for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CtlError::Ipc(_)
+            | CtlError::Io(_)
+            | CtlError::Connection(_)
+            | CtlError::Disconnected => 1,
+            CtlError::NoSuchClient(_) => 2,
+            CtlError::Failed(_) => 3,
+            CtlError::Timeout => 4,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ClientInfo<'a> {
+    handle: ClientHandle,
+    config: &'a ClientConfig,
+    state: &'a ClientState,
+}
+
+/// a line printed by [`CtlCommand::Watch`]
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum WatchLine<'a> {
+    /// event as sent by the service
+    Event(&'a FrontendEvent),
+    /// higher level event derived from the event stream
+    Derived(DerivedEvent),
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum DerivedEvent {
+    /// the pointer entered a different client
+    ActiveClientChanged {
+        handle: Option<ClientHandle>,
+        hostname: Option<String>,
+    },
+    /// the service started capturing input for a client
+    CaptureStarted { handle: Option<ClientHandle> },
+    /// input capture was released
+    CaptureReleased { handle: Option<ClientHandle> },
+}
+
+pub fn run(
+    args: CtlArgs,
+    socket_path: Option<PathBuf>,
+    ipc_tcp: Option<SocketAddr>,
+) -> Result<(), CtlError> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()?;
+    let timeout = Duration::from_secs(args.timeout);
+    runtime.block_on(async move {
+        let connect = async {
+            match args.ipc_tcp {
+                Some(addr) => {
+                    let token = match env::var("LAN_MOUSE_IPC_TOKEN") {
+                        Ok(token) => token,
+                        Err(_) => {
+                            let path = lan_mouse_ipc::token_path(socket_path.as_deref())?;
+                            lan_mouse_ipc::read_token(&path)?
+                        }
+                    };
+                    lan_mouse_ipc::connect_async_tcp(addr, token).await
+                }
+                None => lan_mouse_ipc::connect_async(socket_path.as_deref(), ipc_tcp).await,
+            }
+        };
+        let (rx, tx) = tokio::time::timeout(timeout, connect)
+            .await
+            .map_err(|_| CtlError::Timeout)??;
+        let mut ctl = Ctl::new(rx, tx, args.json);
+        match args.command {
+            CtlCommand::Request(cmd) => tokio::time::timeout(timeout, async move {
+                ctl.sync().await?;
+                ctl.execute(cmd).await
+            })
+            .await
+            .map_err(|_| CtlError::Timeout)?,
+            // watch does not terminate and prints the initial sync as well
+            CtlCommand::Watch => ctl.watch().await,
+        }
+    })
+}
+
+struct Ctl {
+    clients: Vec<(ClientHandle, ClientConfig, ClientState)>,
+    capture_status: Status,
+    emulation_status: Status,
+    port: u16,
+    focus: FocusState,
+    active_client: Option<ClientHandle>,
+    json: bool,
+    rx: AsyncFrontendEventReader,
+    tx: AsyncFrontendRequestWriter,
+}
+
+impl Ctl {
+    fn new(rx: AsyncFrontendEventReader, tx: AsyncFrontendRequestWriter, json: bool) -> Self {
+        Self {
+            clients: vec![],
+            capture_status: Default::default(),
+            emulation_status: Default::default(),
+            port: DEFAULT_PORT,
+            focus: Default::default(),
+            active_client: None,
+            json,
+            rx,
+            tx,
+        }
+    }
+
+    /// wait for the initial state synchronization,
+    /// which is terminated by the current port
+    async fn sync(&mut self) -> Result<(), CtlError> {
+        self.wait_for(|e| matches!(e, FrontendEvent::PortChanged(_, _)).then_some(()))
+            .await
+    }
+
+    async fn next_message(&mut self) -> Result<FrontendMessage, CtlError> {
+        let msg = self.rx.next().await.ok_or(CtlError::Disconnected)??;
+        if let FrontendMessage::Event(event) = &msg {
+            self.track(event);
+        }
+        Ok(msg)
+    }
+
+    /// track the state of the service
+    fn track(&mut self, event: &FrontendEvent) {
+        match event {
+            FrontendEvent::Created(h, c, s) | FrontendEvent::State(h, c, s) => {
+                match self.clients.iter_mut().find(|(handle, _, _)| handle == h) {
+                    Some((_, config, state)) => (*config, *state) = (c.clone(), s.clone()),
+                    None => self.clients.push((*h, c.clone(), s.clone())),
+                }
+            }
+            FrontendEvent::Deleted(h) => self.clients.retain(|(handle, _, _)| handle != h),
+            FrontendEvent::Enumerate(clients) => self.clients = clients.clone(),
+            FrontendEvent::PortChanged(port, _) => self.port = *port,
+            FrontendEvent::CaptureStatus(s) => self.capture_status = *s,
+            FrontendEvent::EmulationStatus(s) => self.emulation_status = *s,
+            FrontendEvent::FocusChanged(f) => self.focus = *f,
+            FrontendEvent::ActiveClientChanged(h) => self.active_client = *h,
+            _ => {}
+        }
+    }
+
+    /// higher level events resulting from `event`,
+    /// must be called before the state is updated
+    fn derive(&self, event: &FrontendEvent) -> Vec<DerivedEvent> {
+        let mut derived = vec![];
+        match *event {
+            FrontendEvent::ActiveClientChanged(handle) if handle != self.active_client => {
+                let hostname = self
+                    .clients
+                    .iter()
+                    .find(|(h, _, _)| Some(*h) == handle)
+                    .and_then(|(_, c, _)| c.hostname.clone());
+                derived.push(DerivedEvent::ActiveClientChanged { handle, hostname });
+            }
+            FrontendEvent::FocusChanged(focus) => {
+                let handle = self.active_client;
+                match (self.focus, focus) {
+                    (FocusState::Receiving, FocusState::Sending | FocusState::AwaitAck) => {
+                        derived.push(DerivedEvent::CaptureStarted { handle })
+                    }
+                    (FocusState::Sending | FocusState::AwaitAck, FocusState::Receiving) => {
+                        derived.push(DerivedEvent::CaptureReleased { handle })
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        derived
+    }
+
+    async fn watch(&mut self) -> Result<(), CtlError> {
+        loop {
+            let event = match self.rx.next().await {
+                Some(Ok(FrontendMessage::Event(event))) => event,
+                Some(Ok(FrontendMessage::Reply(_, _))) => continue,
+                Some(Err(e)) => break Err(e.into()),
+                None => break Ok(()),
+            };
+            // fetch the new state of changed clients, which is broadcast
+            if let FrontendEvent::Changed(handle) = event {
+                self.tx.request(FrontendRequest::GetState(handle)).await?;
+            }
+            let derived = self.derive(&event);
+            self.track(&event);
+            for line in [WatchLine::Event(&event)]
+                .into_iter()
+                .chain(derived.into_iter().map(WatchLine::Derived))
+            {
+                let json = serde_json::to_string(&line).expect("failed to serialize event");
+                println!("{json}");
+            }
+        }
+    }
+
+    /// drop events until `f` returns a result
+    async fn wait_for<T>(
+        &mut self,
+        mut f: impl FnMut(&FrontendEvent) -> Option<T>,
+    ) -> Result<T, CtlError> {
+        loop {
+            if let FrontendMessage::Event(event) = self.next_message().await? {
+                if let Some(t) = f(&event) {
+                    break Ok(t);
+                }
+            }
+        }
+    }
+
+    /// send a request and wait for its reply
+    async fn request(&mut self, request: FrontendRequest) -> Result<FrontendReply, CtlError> {
+        let id = self.tx.send(request).await?;
+        loop {
+            match self.next_message().await? {
+                FrontendMessage::Reply(i, reply) if i == id => match reply {
+                    FrontendReply::NoSuchClient(handle) => {
+                        break Err(CtlError::NoSuchClient(handle))
+                    }
+                    FrontendReply::Error(e) => break Err(CtlError::Failed(e)),
+                    reply => break Ok(reply),
+                },
+                _ => continue,
+            }
+        }
+    }
+
+    async fn client_state(&mut self, handle: ClientHandle) -> Result<(), CtlError> {
+        match self.request(FrontendRequest::GetState(handle)).await? {
+            FrontendReply::State(h, c, s) => self.track(&FrontendEvent::State(h, c, s)),
+            reply => return Err(unexpected(reply)),
+        }
+        Ok(())
+    }
+
+    async fn execute(&mut self, cmd: RequestCommand) -> Result<(), CtlError> {
+        match cmd {
+            RequestCommand::List => {
+                match self.request(FrontendRequest::Enumerate()).await? {
+                    FrontendReply::Enumerate(clients) => self.clients = clients,
+                    reply => return Err(unexpected(reply)),
+                }
+                self.print_clients(None);
+            }
+            RequestCommand::Connect { pos, host, port } => {
+                let handle = match self.request(FrontendRequest::Create).await? {
+                    FrontendReply::Created(handle) => handle,
+                    reply => return Err(unexpected(reply)),
+                };
+                for request in [
+                    FrontendRequest::UpdateHostname(handle, Some(host)),
+                    FrontendRequest::UpdatePort(handle, port.unwrap_or(DEFAULT_PORT)),
+                    FrontendRequest::UpdatePosition(handle, pos),
+                ] {
+                    self.request(request).await?;
+                }
+                self.client_state(handle).await?;
+                self.print_clients(Some(handle));
+            }
+            RequestCommand::Disconnect { id } => {
+                self.request(FrontendRequest::Delete(id)).await?;
+            }
+            RequestCommand::Activate { id } => {
+                self.update(id, FrontendRequest::Activate(id, true)).await?
+            }
+            RequestCommand::Deactivate { id } => {
+                self.update(id, FrontendRequest::Activate(id, false))
+                    .await?
+            }
+            RequestCommand::SetHost { id, host } => {
+                self.update(id, FrontendRequest::UpdateHostname(id, Some(host)))
+                    .await?
+            }
+            RequestCommand::SetPort { id, port } => {
+                self.update(
+                    id,
+                    FrontendRequest::UpdatePort(id, port.unwrap_or(DEFAULT_PORT)),
+                )
+                .await?
+            }
+            RequestCommand::SetTransport { id, transport } => {
+                self.update(id, FrontendRequest::UpdateTransport(id, transport))
+                    .await?
+            }
+            RequestCommand::SetLiveness {
+                id,
+                interval,
+                max_missed,
+                action,
+            } => {
+                let Some((_, config, _)) = self.clients.iter().find(|(h, _, _)| *h == id) else {
+                    return Err(CtlError::NoSuchClient(id));
+                };
+                let mut liveness = config.liveness;
+                if let Some(interval) = interval {
+                    liveness.ping_interval_ms = interval;
+                }
+                if let Some(max_missed) = max_missed {
+                    liveness.max_missed_pings = max_missed;
+                }
+                if let Some(action) = action {
+                    liveness.action = action;
+                }
+                self.update(id, FrontendRequest::UpdateLiveness(id, liveness))
+                    .await?
+            }
+            RequestCommand::SetMac { id, mac } => {
+                self.update(id, FrontendRequest::UpdateMac(id, mac)).await?
+            }
+            RequestCommand::Wake { id } => {
+                let Some((_, config, _)) = self.clients.iter().find(|(h, _, _)| *h == id) else {
+                    return Err(CtlError::NoSuchClient(id));
+                };
+                if config.mac.is_none() {
+                    return Err(CtlError::Failed(format!("client {id} has no mac address")));
+                }
+                self.update(id, FrontendRequest::Wake(id)).await?
+            }
+            RequestCommand::SetSendEvents { id, events } => {
+                let filter = EventFilter::from_iter(events);
+                self.update(id, FrontendRequest::UpdateSendFilter(id, filter))
+                    .await?
+            }
+            RequestCommand::SetReceiveEvents { id, events } => {
+                let filter = EventFilter::from_iter(events);
+                self.update(id, FrontendRequest::UpdateReceiveFilter(id, filter))
+                    .await?
+            }
+            RequestCommand::ChangePort { port } => {
+                // the service does not respond if the port is unchanged
+                if port != self.port {
+                    self.request(FrontendRequest::ChangePort(port)).await?;
+                    let err = self
+                        .wait_for(|e| match e {
+                            FrontendEvent::PortChanged(_, err) => Some(err.clone()),
+                            _ => None,
+                        })
+                        .await?;
+                    if let Some(err) = err {
+                        return Err(CtlError::Failed(err));
+                    }
+                }
+                self.print(&self.port, format!("port: {}", self.port));
+            }
+            RequestCommand::EnableCapture => {
+                // the service only responds if capture is currently disabled
+                if !bool::from(self.capture_status) {
+                    self.request(FrontendRequest::EnableCapture).await?;
+                    let status = self
+                        .wait_for(|e| match e {
+                            FrontendEvent::CaptureStatus(s) => Some(*s),
+                            _ => None,
+                        })
+                        .await?;
+                    if !bool::from(status) {
+                        return Err(CtlError::Failed(
+                            "input capture could not be enabled".into(),
+                        ));
+                    }
+                }
+                self.print(&self.capture_status, "capture: enabled".into());
+            }
+            RequestCommand::EnableEmulation => {
+                // the service only responds if emulation is currently disabled
+                if !bool::from(self.emulation_status) {
+                    self.request(FrontendRequest::EnableEmulation).await?;
+                    let status = self
+                        .wait_for(|e| match e {
+                            FrontendEvent::EmulationStatus(s) => Some(*s),
+                            _ => None,
+                        })
+                        .await?;
+                    if !bool::from(status) {
+                        return Err(CtlError::Failed(
+                            "input emulation could not be enabled".into(),
+                        ));
+                    }
+                }
+                self.print(&self.emulation_status, "emulation: enabled".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// send a request concerning the client `handle` and print its new state
+    async fn update(
+        &mut self,
+        handle: ClientHandle,
+        request: FrontendRequest,
+    ) -> Result<(), CtlError> {
+        self.request(request).await?;
+        self.client_state(handle).await?;
+        self.print_clients(Some(handle));
+        Ok(())
+    }
+
+    /// print all clients or only the one given by `handle`
+    fn print_clients(&self, handle: Option<ClientHandle>) {
+        let clients = self
+            .clients
+            .iter()
+            .filter(|(h, _, _)| handle.is_none() || handle == Some(*h))
+            .map(|(handle, config, state)| ClientInfo {
+                handle: *handle,
+                config,
+                state,
+            })
+            .collect::<Vec<_>>();
+        if self.json {
+            let json = match handle {
+                Some(_) => serde_json::to_string(&clients.first()),
+                None => serde_json::to_string(&clients),
+            };
+            println!("{}", json.expect("failed to serialize clients"));
+        } else {
+            for ClientInfo {
+                handle,
+                config,
+                state,
+            } in clients
+            {
+                print!(
+                    "{handle}: {}:{} ({}, {}) active: {}, {}, ips: {:?}, ping: {}ms x{} ({})",
+                    config.hostname.as_deref().unwrap_or("(no hostname)"),
+                    config.port,
+                    config.pos,
+                    config.transport,
+                    state.active,
+                    state.presence,
+                    state.ips,
+                    config.liveness.ping_interval_ms,
+                    config.liveness.max_missed_pings,
+                    config.liveness.action,
+                );
+                if let Some(mac) = config.mac {
+                    print!(", mac: {mac}");
+                }
+                if config.send_filter != EventFilter::default() {
+                    print!(", send: {}", config.send_filter);
+                }
+                if config.receive_filter != EventFilter::default() {
+                    print!(", receive: {}", config.receive_filter);
+                }
+                if state.waking {
+                    print!(", waking ...");
+                }
+                println!();
+            }
+        }
+    }
+
+    fn print<T: Serialize>(&self, value: &T, text: String) {
+        if self.json {
+            let json = serde_json::to_string(value).expect("failed to serialize");
+            println!("{json}");
+        } else {
+            println!("{text}");
+        }
+    }
+}
+
+fn unexpected(reply: FrontendReply) -> CtlError {
+    CtlError::Failed(format!("unexpected reply: {reply:?}"))
+}
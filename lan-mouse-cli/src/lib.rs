@@ -4,24 +4,34 @@ use tokio::{
     task::LocalSet,
 };
 
-use std::io::{self, Write};
+use std::{
+    io::{self, Write},
+    net::SocketAddr,
+    path::PathBuf,
+};
 
 use self::command::{Command, CommandType};
 
 use lan_mouse_ipc::{
     AsyncFrontendEventReader, AsyncFrontendRequestWriter, ClientConfig, ClientHandle, ClientState,
-    FrontendEvent, FrontendRequest, IpcError, DEFAULT_PORT,
+    EventFilter, FrontendEvent, FrontendMessage, FrontendReply, FrontendRequest, IpcError,
+    DEFAULT_PORT,
 };
 
 mod command;
+mod ctl;
+
+pub use ctl::{run as run_ctl, CtlArgs, CtlError};
 
-pub fn run() -> Result<(), IpcError> {
+/// run the cli frontend, connecting to the service on `socket_path` or the default socket
+/// (on windows via tcp on `ipc_tcp`)
+pub fn run(socket_path: Option<PathBuf>, ipc_tcp: Option<SocketAddr>) -> Result<(), IpcError> {
     let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_io()
         .enable_time()
         .build()?;
     runtime.block_on(LocalSet::new().run_until(async move {
-        let (rx, tx) = lan_mouse_ipc::connect_async().await?;
+        let (rx, tx) = lan_mouse_ipc::connect_async(socket_path.as_deref(), ipc_tcp).await?;
         let mut cli = Cli::new(rx, tx);
         cli.run().await
     }))?;
@@ -53,11 +63,10 @@ impl Cli {
         /* initial state sync */
         self.clients = loop {
             match self.rx.next().await {
-                Some(Ok(e)) => {
-                    if let FrontendEvent::Enumerate(clients) = e {
-                        break clients;
-                    }
+                Some(Ok(FrontendMessage::Event(FrontendEvent::Enumerate(clients)))) => {
+                    break clients;
                 }
+                Some(Ok(_)) => {}
                 Some(Err(e)) => return Err(e),
                 None => return Ok(()),
             }
@@ -79,11 +88,14 @@ impl Cli {
                     };
                     self.execute(cmd).await?;
                 }
-                event = self.rx.next() => {
-                    if let Some(event) = event {
-                        self.handle_event(event?);
-                    } else {
-                        break Ok(());
+                message = self.rx.next() => {
+                    match message {
+                        Some(message) => {
+                            if let FrontendMessage::Event(event) = message? {
+                                self.handle_event(event);
+                            }
+                        }
+                        None => break Ok(()),
                     }
                 }
             }
@@ -93,14 +105,42 @@ impl Cli {
         }
     }
 
-    async fn update_client(&mut self, handle: ClientHandle) -> Result<(), IpcError> {
-        self.tx.request(FrontendRequest::GetState(handle)).await?;
-        while let Some(Ok(event)) = self.rx.next().await {
-            self.handle_event(event.clone());
-            if let FrontendEvent::State(_, _, _) | FrontendEvent::NoSuchClient(_) = event {
-                break;
+    /// send a request and wait for its reply, handling events in the meantime.
+    /// returns `None` if the request failed
+    async fn request(
+        &mut self,
+        request: FrontendRequest,
+    ) -> Result<Option<FrontendReply>, IpcError> {
+        let id = self.tx.send(request).await?;
+        loop {
+            let Some(message) = self.rx.next().await else {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            };
+            match message? {
+                FrontendMessage::Event(event) => self.handle_event(event),
+                FrontendMessage::Reply(reply_id, reply) if reply_id == id => {
+                    return Ok(match reply {
+                        FrontendReply::NoSuchClient(h) => {
+                            eprintln!("no such client: {h}");
+                            None
+                        }
+                        FrontendReply::Error(e) => {
+                            eprintln!("ERROR: {e}");
+                            None
+                        }
+                        reply => Some(reply),
+                    });
+                }
+                FrontendMessage::Reply(_, _) => {}
             }
         }
+    }
+
+    async fn update_client(&mut self, handle: ClientHandle) -> Result<(), IpcError> {
+        let request = FrontendRequest::GetState(handle);
+        if let Some(FrontendReply::State(h, c, s)) = self.request(request).await? {
+            self.handle_event(FrontendEvent::State(h, c, s));
+        }
         Ok(())
     }
 
@@ -108,72 +148,65 @@ impl Cli {
         match cmd {
             Command::None => {}
             Command::Connect(pos, host, port) => {
-                let request = FrontendRequest::Create;
-                self.tx.request(request).await?;
-                let handle = loop {
-                    if let Some(Ok(event)) = self.rx.next().await {
-                        match event {
-                            FrontendEvent::Created(h, c, s) => {
-                                self.clients.push((h, c, s));
-                                break h;
-                            }
-                            _ => {
-                                self.handle_event(event);
-                                continue;
-                            }
-                        }
-                    }
+                let Some(FrontendReply::Created(handle)) =
+                    self.request(FrontendRequest::Create).await?
+                else {
+                    return Ok(());
                 };
                 for request in [
                     FrontendRequest::UpdateHostname(handle, Some(host.clone())),
                     FrontendRequest::UpdatePort(handle, port.unwrap_or(DEFAULT_PORT)),
                     FrontendRequest::UpdatePosition(handle, pos),
                 ] {
-                    self.tx.request(request).await?;
+                    self.request(request).await?;
                 }
                 self.update_client(handle).await?;
             }
             Command::Disconnect(id) => {
-                self.tx.request(FrontendRequest::Delete(id)).await?;
-                loop {
-                    if let Some(Ok(event)) = self.rx.next().await {
-                        self.handle_event(event.clone());
-                        if let FrontendEvent::Deleted(_) = event {
-                            self.handle_event(event);
-                            break;
-                        }
-                    }
-                }
+                self.request(FrontendRequest::Delete(id)).await?;
             }
             Command::Activate(id) => {
-                self.tx.request(FrontendRequest::Activate(id, true)).await?;
-                self.update_client(id).await?;
+                if self
+                    .request(FrontendRequest::Activate(id, true))
+                    .await?
+                    .is_some()
+                {
+                    self.update_client(id).await?;
+                }
             }
             Command::Deactivate(id) => {
-                self.tx
+                if self
                     .request(FrontendRequest::Activate(id, false))
-                    .await?;
-                self.update_client(id).await?;
+                    .await?
+                    .is_some()
+                {
+                    self.update_client(id).await?;
+                }
             }
             Command::List => {
-                self.tx.request(FrontendRequest::Enumerate()).await?;
-                while let Some(e) = self.rx.next().await {
-                    let event = e?;
-                    self.handle_event(event.clone());
-                    if let FrontendEvent::Enumerate(_) = event {
-                        break;
-                    }
+                if let Some(FrontendReply::Enumerate(clients)) =
+                    self.request(FrontendRequest::Enumerate()).await?
+                {
+                    self.clients = clients;
+                    self.print_clients();
                 }
             }
             Command::SetHost(handle, host) => {
                 let request = FrontendRequest::UpdateHostname(handle, Some(host.clone()));
-                self.tx.request(request).await?;
-                self.update_client(handle).await?;
+                if self.request(request).await?.is_some() {
+                    self.update_client(handle).await?;
+                }
             }
             Command::SetPort(handle, port) => {
                 let request = FrontendRequest::UpdatePort(handle, port.unwrap_or(DEFAULT_PORT));
-                self.tx.request(request).await?;
-                self.update_client(handle).await?;
+                if self.request(request).await?.is_some() {
+                    self.update_client(handle).await?;
+                }
+            }
+            Command::Wake(handle) => {
+                if self.request(FrontendRequest::Wake(handle)).await?.is_some() {
+                    self.update_client(handle).await?;
+                }
             }
             Command::Help => {
                 for cmd_type in [
@@ -184,6 +217,7 @@ impl Cli {
                     CommandType::Deactivate,
                     CommandType::SetHost,
                     CommandType::SetPort,
+                    CommandType::Wake,
                 ] {
                     eprintln!("{}", cmd_type.usage());
                 }
@@ -244,6 +278,9 @@ impl Cli {
                             if s.active { "activated" } else { "deactivated" }
                         );
                     }
+                    if !state.waking && s.waking {
+                        eprintln!("client {h}: waking ...");
+                    }
                     *state = s;
                 }
             }
@@ -261,10 +298,7 @@ impl Cli {
                     eprintln!("changed port to {p}");
                 }
             }
-            FrontendEvent::Enumerate(clients) => {
-                self.clients = clients;
-                self.print_clients();
-            }
+            FrontendEvent::Enumerate(clients) => self.clients = clients,
             FrontendEvent::Error(e) => {
                 eprintln!("ERROR: {e}");
             }
@@ -274,6 +308,19 @@ impl Cli {
             FrontendEvent::EmulationStatus(s) => {
                 eprintln!("emulation status: {s:?}")
             }
+            FrontendEvent::FocusChanged(_) | FrontendEvent::ActiveClientChanged(_) => {}
+            FrontendEvent::PeerDiscovered(peer) => {
+                eprintln!(
+                    "discovered {} (port {}, ips {:?})",
+                    peer.hostname, peer.port, peer.ips
+                );
+            }
+            FrontendEvent::PeerLost(hostname) => {
+                eprintln!("{hostname} disappeared");
+            }
+            FrontendEvent::PresenceChanged(h, presence) => {
+                eprintln!("client {h} is {presence}");
+            }
         }
     }
 
@@ -296,14 +343,30 @@ fn prompt() -> io::Result<()> {
 
 fn print_config(c: &ClientConfig) {
     eprint!(
-        "{}:{} ({}), ips: {:?}",
+        "{}:{} ({}, {}), ips: {:?}, ping: {}ms x{} ({})",
         c.hostname.clone().unwrap_or("(no hostname)".into()),
         c.port,
         c.pos,
-        c.fix_ips
+        c.transport,
+        c.fix_ips,
+        c.liveness.ping_interval_ms,
+        c.liveness.max_missed_pings,
+        c.liveness.action
     );
+    if let Some(mac) = c.mac {
+        eprint!(", mac: {mac}");
+    }
+    if c.send_filter != EventFilter::default() {
+        eprint!(", send: {}", c.send_filter);
+    }
+    if c.receive_filter != EventFilter::default() {
+        eprint!(", receive: {}", c.receive_filter);
+    }
 }
 
 fn print_state(s: &ClientState) {
-    eprint!("active: {}, dns: {:?}", s.active, s.ips);
+    eprint!(
+        "active: {}, presence: {}, dns: {:?}",
+        s.active, s.presence, s.ips
+    );
 }
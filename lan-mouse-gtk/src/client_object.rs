@@ -26,6 +26,11 @@ impl ClientObject {
                     .collect::<Vec<_>>(),
             )
             .property("resolving", state.resolving)
+            .property("ping-interval", client.liveness.ping_interval_ms as u32)
+            .property("max-missed-pings", client.liveness.max_missed_pings)
+            .property("liveness-action", client.liveness.action.to_string())
+            .property("mac", client.mac.map(|mac| mac.to_string()))
+            .property("waking", state.waking)
             .build()
     }
 
@@ -43,4 +48,9 @@ pub struct ClientData {
     pub position: String,
     pub resolving: bool,
     pub ips: Vec<String>,
+    pub ping_interval: u32,
+    pub max_missed_pings: u32,
+    pub liveness_action: String,
+    pub mac: Option<String>,
+    pub waking: bool,
 }
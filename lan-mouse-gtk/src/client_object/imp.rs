@@ -19,6 +19,11 @@ pub struct ClientObject {
     #[property(name = "position", get, set, type = String, member = position)]
     #[property(name = "resolving", get, set, type = bool, member = resolving)]
     #[property(name = "ips", get, set, type = Vec<String>, member = ips)]
+    #[property(name = "ping-interval", get, set, type = u32, member = ping_interval)]
+    #[property(name = "max-missed-pings", get, set, type = u32, member = max_missed_pings)]
+    #[property(name = "liveness-action", get, set, type = String, member = liveness_action)]
+    #[property(name = "mac", get, set, type = String, member = mac)]
+    #[property(name = "waking", get, set, type = bool, member = waking)]
     pub data: RefCell<ClientData>,
 }
 
@@ -109,6 +109,66 @@ impl ClientRow {
             .sync_create()
             .build();
 
+        let ping_interval_binding = client_object
+            .bind_property("ping-interval", &self.imp().ping_interval.get(), "value")
+            .transform_to(|_, v: u32| Some(v as f64))
+            .transform_from(|_, v: f64| Some(v as u32))
+            .bidirectional()
+            .sync_create()
+            .build();
+
+        let max_missed_binding = client_object
+            .bind_property(
+                "max-missed-pings",
+                &self.imp().max_missed_pings.get(),
+                "value",
+            )
+            .transform_to(|_, v: u32| Some(v as f64))
+            .transform_from(|_, v: f64| Some(v as u32))
+            .bidirectional()
+            .sync_create()
+            .build();
+
+        let liveness_action_binding = client_object
+            .bind_property(
+                "liveness-action",
+                &self.imp().liveness_action.get(),
+                "selected",
+            )
+            .transform_from(|_, v: u32| match v {
+                1 => Some("release-keys"),
+                2 => Some("warn"),
+                _ => Some("release"),
+            })
+            .transform_to(|_, v: String| match v.as_str() {
+                "release-keys" => Some(1u32),
+                "warn" => Some(2u32),
+                _ => Some(0u32),
+            })
+            .bidirectional()
+            .sync_create()
+            .build();
+
+        let mac_binding = client_object
+            .bind_property("mac", &self.imp().mac.get(), "text")
+            .transform_to(|_, v: Option<String>| Some(v.unwrap_or_default()))
+            .transform_from(|_, v: String| {
+                if v.trim().is_empty() {
+                    Some(None)
+                } else {
+                    Some(Some(v.trim().to_string()))
+                }
+            })
+            .bidirectional()
+            .sync_create()
+            .build();
+
+        let waking_binding = client_object
+            .bind_property("waking", &self.imp().wake_button.get(), "sensitive")
+            .invert_boolean()
+            .sync_create()
+            .build();
+
         let resolve_binding = client_object
             .bind_property(
                 "resolving",
@@ -137,6 +197,11 @@ impl ClientRow {
         bindings.push(port_binding);
         bindings.push(subtitle_binding);
         bindings.push(position_binding);
+        bindings.push(ping_interval_binding);
+        bindings.push(max_missed_binding);
+        bindings.push(liveness_action_binding);
+        bindings.push(mac_binding);
+        bindings.push(waking_binding);
         bindings.push(resolve_binding);
         bindings.push(ip_binding);
     }
@@ -22,11 +22,23 @@ pub struct ClientRow {
     #[template_child]
     pub position: TemplateChild<ComboRow>,
     #[template_child]
+    pub ping_interval: TemplateChild<gtk::SpinButton>,
+    #[template_child]
+    pub max_missed_pings: TemplateChild<gtk::SpinButton>,
+    #[template_child]
+    pub liveness_action: TemplateChild<ComboRow>,
+    #[template_child]
+    pub mac: TemplateChild<gtk::Entry>,
+    #[template_child]
+    pub wake_button: TemplateChild<gtk::Button>,
+    #[template_child]
     pub delete_row: TemplateChild<ActionRow>,
     #[template_child]
     pub delete_button: TemplateChild<gtk::Button>,
     #[template_child]
     pub dns_loading_indicator: TemplateChild<gtk::Spinner>,
+    #[template_child]
+    pub focus_indicator: TemplateChild<gtk::Image>,
     pub bindings: RefCell<Vec<Binding>>,
 }
 
@@ -70,6 +82,7 @@ impl ObjectImpl for ClientRow {
                     .param_types([bool::static_type()])
                     .build(),
                 Signal::builder("request-delete").build(),
+                Signal::builder("request-wake").build(),
             ]
         })
     }
@@ -89,6 +102,11 @@ impl ClientRow {
         self.obj().emit_by_name::<()>("request-dns", &[]);
     }
 
+    #[template_callback]
+    fn handle_request_wake(&self, _: Button) {
+        self.obj().emit_by_name::<()>("request-wake", &[]);
+    }
+
     #[template_callback]
     fn handle_client_delete(&self, _button: &Button) {
         log::debug!("delete button pressed -> requesting delete");
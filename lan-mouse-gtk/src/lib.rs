@@ -1,8 +1,15 @@
 mod client_object;
 mod client_row;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod tray;
 mod window;
 
-use std::{env, process, str};
+use std::{
+    env,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process, str,
+};
 
 use window::Window;
 
@@ -16,18 +23,20 @@ use gtk::{gio, glib, prelude::ApplicationExt};
 
 use self::client_object::ClientObject;
 
-pub fn run() -> glib::ExitCode {
+/// run the gtk frontend, connecting to the service on `socket_path` or the default socket
+/// (on windows via tcp on `ipc_tcp`)
+pub fn run(socket_path: Option<PathBuf>, ipc_tcp: Option<SocketAddr>) -> glib::ExitCode {
     log::debug!("running gtk frontend");
     #[cfg(windows)]
     let ret = std::thread::Builder::new()
         .stack_size(8 * 1024 * 1024) // https://gitlab.gnome.org/GNOME/gtk/-/commit/52dbb3f372b2c3ea339e879689c1de535ba2c2c3 -> caused crash on windows
         .name("gtk".into())
-        .spawn(gtk_main)
+        .spawn(move || gtk_main(socket_path, ipc_tcp))
         .unwrap()
         .join()
         .unwrap();
     #[cfg(not(windows))]
-    let ret = gtk_main();
+    let ret = gtk_main(socket_path, ipc_tcp);
 
     if ret == glib::ExitCode::FAILURE {
         log::error!("frontend exited with failure");
@@ -38,15 +47,21 @@ pub fn run() -> glib::ExitCode {
     ret
 }
 
-fn gtk_main() -> glib::ExitCode {
+fn gtk_main(socket_path: Option<PathBuf>, ipc_tcp: Option<SocketAddr>) -> glib::ExitCode {
     gio::resources_register_include!("lan-mouse.gresource").expect("Failed to register resources.");
 
+    // frontends of other instances must not be activated instead of this one
+    let flags = match socket_path {
+        Some(_) => gio::ApplicationFlags::NON_UNIQUE,
+        None => gio::ApplicationFlags::empty(),
+    };
     let app = Application::builder()
         .application_id("de.feschber.LanMouse")
+        .flags(flags)
         .build();
 
     app.connect_startup(|_| load_icons());
-    app.connect_activate(build_ui);
+    app.connect_activate(move |app| build_ui(app, socket_path.as_deref(), ipc_tcp));
 
     let args: Vec<&'static str> = vec![];
     app.run_with_args(&args)
@@ -58,9 +73,9 @@ fn load_icons() {
     icon_theme.add_resource_path("/de/feschber/LanMouse/icons");
 }
 
-fn build_ui(app: &Application) {
+fn build_ui(app: &Application, socket_path: Option<&Path>, ipc_tcp: Option<SocketAddr>) {
     log::debug!("connecting to lan-mouse-socket");
-    let (mut frontend_rx, frontend_tx) = match lan_mouse_ipc::connect() {
+    let (mut frontend_rx, frontend_tx) = match lan_mouse_ipc::connect(socket_path, ipc_tcp) {
         Ok(conn) => conn,
         Err(e) => {
             log::error!("{e}");
@@ -85,12 +100,19 @@ fn build_ui(app: &Application) {
 
     let window = Window::new(app, frontend_tx);
 
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let tray = spawn_tray(app, &window);
+
     glib::spawn_future_local(clone!(
         #[weak]
         window,
         async move {
             loop {
                 let notify = receiver.recv().await.unwrap_or_else(|_| process::exit(1));
+                #[cfg(all(unix, not(target_os = "macos")))]
+                if let Some(tray) = &tray {
+                    tray.update(|tray| tray.handle_event(&notify));
+                }
                 match notify {
                     FrontendEvent::Changed(handle) => {
                         window.request(FrontendRequest::GetState(handle));
@@ -132,6 +154,21 @@ fn build_ui(app: &Application) {
                     FrontendEvent::EmulationStatus(s) => {
                         window.set_emulation(s.into());
                     }
+                    FrontendEvent::FocusChanged(focus) => {
+                        window.set_focus(focus);
+                    }
+                    FrontendEvent::ActiveClientChanged(handle) => {
+                        window.set_active_client(handle);
+                    }
+                    FrontendEvent::PeerDiscovered(peer) => {
+                        window.add_discovered_peer(peer);
+                    }
+                    FrontendEvent::PeerLost(hostname) => {
+                        window.remove_discovered_peer(&hostname);
+                    }
+                    FrontendEvent::PresenceChanged(handle, presence) => {
+                        window.set_client_presence(handle, presence);
+                    }
                 }
             }
         }
@@ -139,3 +176,39 @@ fn build_ui(app: &Application) {
 
     window.present();
 }
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn spawn_tray(
+    app: &Application,
+    window: &Window,
+) -> Option<ksni::blocking::Handle<tray::LanMouseTray>> {
+    let (action_tx, action_rx) = async_channel::unbounded();
+    let handle = match tray::LanMouseTray::spawn(action_tx) {
+        Ok(handle) => handle,
+        Err(e) => {
+            log::warn!("could not create tray icon: {e}");
+            return None;
+        }
+    };
+
+    // keep running in the tray when the window is closed
+    window.set_hide_on_close(true);
+
+    glib::spawn_future_local(clone!(
+        #[weak]
+        app,
+        #[weak]
+        window,
+        async move {
+            while let Ok(action) = action_rx.recv().await {
+                match action {
+                    tray::TrayAction::ShowWindow => window.present(),
+                    tray::TrayAction::Request(request) => window.request(request),
+                    tray::TrayAction::Quit => app.quit(),
+                }
+            }
+        }
+    ));
+
+    Some(handle)
+}
@@ -0,0 +1,266 @@
+use async_channel::Sender;
+use ksni::{
+    blocking::{Handle, TrayMethods},
+    menu::{CheckmarkItem, StandardItem},
+    MenuItem, Status as TrayStatus, ToolTip, Tray,
+};
+
+use lan_mouse_ipc::{
+    ClientConfig, ClientHandle, ClientState, FocusState, FrontendEvent, FrontendRequest, Presence,
+    Status,
+};
+
+/// action requested through the tray menu
+pub(crate) enum TrayAction {
+    /// present the main window
+    ShowWindow,
+    /// forward a request to the service
+    Request(FrontendRequest),
+    /// exit the frontend
+    Quit,
+}
+
+/// StatusNotifierItem mirroring the state of the service
+pub(crate) struct LanMouseTray {
+    clients: Vec<(ClientHandle, ClientConfig, ClientState)>,
+    capture_status: Status,
+    emulation_status: Status,
+    focus: FocusState,
+    active_client: Option<ClientHandle>,
+    actions: Sender<TrayAction>,
+}
+
+impl LanMouseTray {
+    /// register the tray on the session bus,
+    /// menu actions are sent to `actions`
+    pub(crate) fn spawn(actions: Sender<TrayAction>) -> Result<Handle<Self>, ksni::Error> {
+        TrayMethods::spawn(Self::new(actions))
+    }
+
+    fn new(actions: Sender<TrayAction>) -> Self {
+        Self {
+            clients: vec![],
+            capture_status: Default::default(),
+            emulation_status: Default::default(),
+            focus: Default::default(),
+            active_client: None,
+            actions,
+        }
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &FrontendEvent) {
+        match event {
+            FrontendEvent::Created(h, c, s) | FrontendEvent::State(h, c, s) => {
+                match self.clients.iter_mut().find(|(handle, _, _)| handle == h) {
+                    Some((_, config, state)) => (*config, *state) = (c.clone(), s.clone()),
+                    None => self.clients.push((*h, c.clone(), s.clone())),
+                }
+            }
+            FrontendEvent::Deleted(h) => self.clients.retain(|(handle, _, _)| handle != h),
+            FrontendEvent::Enumerate(clients) => self.clients = clients.clone(),
+            FrontendEvent::CaptureStatus(s) => self.capture_status = *s,
+            FrontendEvent::EmulationStatus(s) => self.emulation_status = *s,
+            FrontendEvent::FocusChanged(f) => self.focus = *f,
+            FrontendEvent::ActiveClientChanged(h) => self.active_client = *h,
+            _ => {}
+        }
+    }
+
+    fn send(&self, action: TrayAction) {
+        if self.actions.send_blocking(action).is_err() {
+            log::warn!("tray: frontend closed");
+        }
+    }
+
+    fn hostname(&self, handle: ClientHandle) -> String {
+        self.clients
+            .iter()
+            .find(|(h, _, _)| *h == handle)
+            .and_then(|(_, c, _)| c.hostname.clone())
+            .unwrap_or_else(|| format!("client {handle}"))
+    }
+
+    fn description(&self) -> String {
+        match (self.focus, self.active_client) {
+            (FocusState::Sending, Some(handle)) => {
+                format!("sending to {}", self.hostname(handle))
+            }
+            (FocusState::AwaitAck, Some(handle)) => {
+                format!("waiting for {} to respond", self.hostname(handle))
+            }
+            _ if !bool::from(self.capture_status) => "input capture is disabled".into(),
+            _ if !bool::from(self.emulation_status) => "input emulation is disabled".into(),
+            _ => "idle".into(),
+        }
+    }
+}
+
+impl Tray for LanMouseTray {
+    fn id(&self) -> String {
+        "de.feschber.LanMouse".into()
+    }
+
+    fn title(&self) -> String {
+        "Lan Mouse".into()
+    }
+
+    fn icon_name(&self) -> String {
+        match self.focus {
+            FocusState::Sending | FocusState::AwaitAck => "input-mouse".into(),
+            FocusState::Receiving => "de.feschber.LanMouse".into(),
+        }
+    }
+
+    fn attention_icon_name(&self) -> String {
+        "dialog-warning".into()
+    }
+
+    fn status(&self) -> TrayStatus {
+        if bool::from(self.capture_status) && bool::from(self.emulation_status) {
+            TrayStatus::Active
+        } else {
+            TrayStatus::NeedsAttention
+        }
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: "Lan Mouse".into(),
+            description: self.description(),
+            ..Default::default()
+        }
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.send(TrayAction::ShowWindow);
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let mut menu: Vec<MenuItem<Self>> = vec![
+            StandardItem {
+                label: "Show Lan Mouse".into(),
+                activate: Box::new(|tray: &mut Self| tray.send(TrayAction::ShowWindow)),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+        ];
+        for (handle, config, state) in self.clients.iter() {
+            let handle = *handle;
+            let active = state.active;
+            let mut label = match &config.hostname {
+                Some(hostname) => format!("{hostname} ({})", config.pos),
+                None => format!("client {handle} ({})", config.pos),
+            };
+            if state.waking {
+                label.push_str(" - waking ...");
+            } else if state.presence == Presence::Offline {
+                label.push_str(" - offline");
+            }
+            menu.push(
+                CheckmarkItem {
+                    label,
+                    checked: active,
+                    activate: Box::new(move |tray: &mut Self| {
+                        let request = FrontendRequest::Activate(handle, !active);
+                        tray.send(TrayAction::Request(request));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+        if !self.clients.is_empty() {
+            menu.push(MenuItem::Separator);
+        }
+        menu.extend([
+            StandardItem {
+                label: "Reenable input capture".into(),
+                enabled: !bool::from(self.capture_status),
+                activate: Box::new(|tray: &mut Self| {
+                    tray.send(TrayAction::Request(FrontendRequest::EnableCapture))
+                }),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Reenable input emulation".into(),
+                enabled: !bool::from(self.emulation_status),
+                activate: Box::new(|tray: &mut Self| {
+                    tray.send(TrayAction::Request(FrontendRequest::EnableEmulation))
+                }),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: "Quit".into(),
+                icon_name: "application-exit".into(),
+                activate: Box::new(|tray: &mut Self| tray.send(TrayAction::Quit)),
+                ..Default::default()
+            }
+            .into(),
+        ]);
+        menu
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use lan_mouse_ipc::Position;
+
+    fn labels(tray: &LanMouseTray) -> Vec<String> {
+        tray.menu()
+            .into_iter()
+            .filter_map(|item| match item {
+                MenuItem::Standard(item) => Some(item.label),
+                MenuItem::Checkmark(item) => Some(item.label),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn menu_follows_events() {
+        let (tx, rx) = async_channel::unbounded();
+        let mut tray = LanMouseTray::new(tx);
+        assert_eq!(tray.status(), TrayStatus::NeedsAttention);
+        assert_eq!(tray.description(), "input capture is disabled");
+
+        let config = ClientConfig {
+            hostname: Some("iridium".into()),
+            pos: Position::Right,
+            ..Default::default()
+        };
+        let state = ClientState {
+            presence: Presence::Offline,
+            ..Default::default()
+        };
+        tray.handle_event(&FrontendEvent::Created(0, config.clone(), state));
+        tray.handle_event(&FrontendEvent::CaptureStatus(Status::Enabled));
+        tray.handle_event(&FrontendEvent::EmulationStatus(Status::Enabled));
+        assert_eq!(tray.status(), TrayStatus::Active);
+        assert!(labels(&tray).contains(&"iridium (right) - offline".to_string()));
+
+        tray.handle_event(&FrontendEvent::ActiveClientChanged(Some(0)));
+        tray.handle_event(&FrontendEvent::FocusChanged(FocusState::Sending));
+        assert_eq!(tray.description(), "sending to iridium");
+        assert_eq!(tray.icon_name(), "input-mouse");
+
+        // toggling the client requests activation
+        let MenuItem::Checkmark(item) = tray.menu().remove(2) else {
+            panic!("expected client entry");
+        };
+        assert!(!item.checked);
+        (item.activate)(&mut tray);
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(TrayAction::Request(FrontendRequest::Activate(0, true)))
+        ));
+
+        tray.handle_event(&FrontendEvent::Deleted(0));
+        assert!(!labels(&tray).iter().any(|l| l.starts_with("iridium")));
+    }
+}
@@ -10,8 +10,8 @@ use gtk::{
 };
 
 use lan_mouse_ipc::{
-    ClientConfig, ClientHandle, ClientState, FrontendRequest, FrontendRequestWriter, Position,
-    DEFAULT_PORT,
+    ClientConfig, ClientHandle, ClientState, DiscoveredPeer, FocusState, FrontendRequest,
+    FrontendRequestWriter, Liveness, MacAddress, Position, Presence, DEFAULT_PORT,
 };
 
 use super::{client_object::ClientObject, client_row::ClientRow};
@@ -105,6 +105,20 @@ impl Window {
                             }
                         ),
                     );
+                    row.connect_closure(
+                        "request-wake",
+                        false,
+                        closure_local!(
+                            #[strong]
+                            window,
+                            move |row: ClientRow| {
+                                if let Some(client) = window.client_by_idx(row.index() as u32) {
+                                    window.request_client_update(&client);
+                                    window.request(FrontendRequest::Wake(client.handle()));
+                                }
+                            }
+                        ),
+                    );
                     row.upcast()
                 }
             ),
@@ -137,6 +151,7 @@ impl Window {
         self.clients().append(&client);
         self.set_placeholder_visible(false);
         self.update_dns_state(handle, !state.ips.is_empty());
+        self.update_focus();
     }
 
     pub fn client_idx(&self, handle: ClientHandle) -> Option<usize> {
@@ -181,6 +196,19 @@ impl Window {
         if data.position != client.pos.to_string() {
             client_object.set_position(client.pos.to_string());
         }
+        if data.ping_interval != client.liveness.ping_interval_ms as u32 {
+            client_object.set_ping_interval(client.liveness.ping_interval_ms as u32);
+        }
+        if data.max_missed_pings != client.liveness.max_missed_pings {
+            client_object.set_max_missed_pings(client.liveness.max_missed_pings);
+        }
+        if data.liveness_action != client.liveness.action.to_string() {
+            client_object.set_liveness_action(client.liveness.action.to_string());
+        }
+        let mac = client.mac.map(|mac| mac.to_string());
+        if data.mac != mac {
+            client_object.set_mac(mac.unwrap_or_default());
+        }
     }
 
     pub fn update_client_state(&self, handle: ClientHandle, state: ClientState) {
@@ -197,6 +225,14 @@ impl Window {
             log::debug!("set active to {}", state.active);
         }
 
+        if state.waking != data.waking {
+            client_object.set_waking(state.waking);
+            if state.waking {
+                let hostname = data.hostname.unwrap_or_else(|| format!("client {handle}"));
+                self.show_toast(format!("waking {hostname} ...").as_str());
+            }
+        }
+
         if state.resolving != data.resolving {
             client_object.set_resolving(state.resolving);
             log::debug!("resolving {}: {}", data.handle, state.resolving);
@@ -268,14 +304,31 @@ impl Window {
         let position = Position::try_from(data.position.as_str()).expect("invalid position");
         let hostname = data.hostname;
         let port = data.port as u16;
+        let liveness = Liveness {
+            ping_interval_ms: data.ping_interval as u64,
+            max_missed_pings: data.max_missed_pings,
+            action: data.liveness_action.parse().unwrap_or_default(),
+        };
 
         for event in [
             FrontendRequest::UpdateHostname(handle, hostname),
             FrontendRequest::UpdatePosition(handle, position),
             FrontendRequest::UpdatePort(handle, port),
+            FrontendRequest::UpdateLiveness(handle, liveness),
         ] {
             self.request(event);
         }
+
+        /* keep the previous mac address if the entered one is invalid */
+        match data
+            .mac
+            .as_deref()
+            .map(str::parse::<MacAddress>)
+            .transpose()
+        {
+            Ok(mac) => self.request(FrontendRequest::UpdateMac(handle, mac)),
+            Err(e) => self.show_toast(e.to_string().as_str()),
+        }
     }
 
     pub fn request_client_activate(&self, client: &ClientObject, active: bool) {
@@ -310,6 +363,115 @@ impl Window {
         self.update_capture_emulation_status();
     }
 
+    pub fn set_focus(&self, focus: FocusState) {
+        self.imp().focus.replace(focus);
+        self.update_focus();
+    }
+
+    /// let the user know when a client stopped responding
+    pub fn set_client_presence(&self, handle: ClientHandle, presence: Presence) {
+        if presence != Presence::Offline {
+            return;
+        }
+        let hostname = self
+            .client_idx(handle)
+            .and_then(|idx| self.client_by_idx(idx as u32))
+            .and_then(|client| client.get_data().hostname)
+            .unwrap_or_else(|| format!("client {handle}"));
+        self.show_toast(format!("{hostname} is offline").as_str());
+    }
+
+    pub fn set_active_client(&self, handle: Option<ClientHandle>) {
+        self.imp().active_client.replace(handle);
+        self.update_focus();
+    }
+
+    /// mark the client that currently receives input
+    fn update_focus(&self) {
+        let focus = self.imp().focus.get();
+        let active_client = self.imp().active_client.get();
+        let list_box: ListBox = self.imp().client_list.get();
+        for (idx, client) in self.clients().iter::<ClientObject>().enumerate() {
+            let Ok(client) = client else {
+                continue;
+            };
+            let Some(row) = list_box.row_at_index(idx as i32) else {
+                continue;
+            };
+            let client_row: ClientRow = row.downcast().expect("expected ClientRow Object");
+            let indicator = &client_row.imp().focus_indicator;
+            let tooltip = match focus {
+                _ if active_client != Some(client.handle()) => None,
+                FocusState::Receiving => None,
+                FocusState::AwaitAck => Some("waiting for the device to respond"),
+                FocusState::Sending => Some("this device currently receives input"),
+            };
+            indicator.set_visible(tooltip.is_some());
+            indicator.set_tooltip_text(tooltip);
+        }
+    }
+
+    /// offer to add a peer found on the local network
+    pub fn add_discovered_peer(&self, peer: DiscoveredPeer) {
+        self.remove_discovered_peer(&peer.hostname);
+
+        let ips = peer
+            .ips
+            .iter()
+            .map(|ip| ip.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let subtitle = match &peer.fingerprint {
+            Some(fingerprint) => format!("{ips} (port {})\n{fingerprint}", peer.port),
+            None => format!("{ips} (port {})", peer.port),
+        };
+        let row = adw::ActionRow::builder()
+            .title(peer.hostname.as_str())
+            .subtitle(subtitle)
+            .build();
+
+        let positions = ["left", "right", "top", "bottom"];
+        let position = gtk::DropDown::from_strings(&positions);
+        position.set_valign(gtk::Align::Center);
+        position.set_tooltip_text(Some("position of the device"));
+        let add_button = gtk::Button::builder()
+            .icon_name("list-add-symbolic")
+            .tooltip_text("add device")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        let hostname = peer.hostname.clone();
+        add_button.connect_clicked(clone!(
+            #[weak(rename_to = window)]
+            self,
+            #[weak]
+            position,
+            move |_| {
+                let pos = positions[position.selected() as usize];
+                let pos = Position::try_from(pos).expect("invalid position");
+                window.request(FrontendRequest::AddDiscovered(hostname.clone(), pos));
+            }
+        ));
+        row.add_suffix(&position);
+        row.add_suffix(&add_button);
+
+        self.imp().discovered_list.append(&row);
+        self.imp()
+            .discovered_rows
+            .borrow_mut()
+            .insert(peer.hostname, row);
+        self.imp().discovered_group.set_visible(true);
+    }
+
+    pub fn remove_discovered_peer(&self, hostname: &str) {
+        let Some(row) = self.imp().discovered_rows.borrow_mut().remove(hostname) else {
+            return;
+        };
+        self.imp().discovered_list.remove(&row);
+        let empty = self.imp().discovered_rows.borrow().is_empty();
+        self.imp().discovered_group.set_visible(!empty);
+    }
+
     fn update_capture_emulation_status(&self) {
         let capture = self.imp().capture_active.get();
         let emulation = self.imp().emulation_active.get();
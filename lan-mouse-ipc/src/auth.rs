@@ -0,0 +1,116 @@
+//! authentication of frontends connecting over tcp.
+//!
+//! The service writes a random token to a file only readable by its user.
+//! The first line a frontend sends over tcp must carry this token:
+//!
+//! ```text
+//! -> {"token":"5f0c..."}
+//! ```
+//!
+//! Connections sending anything else are closed.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::SocketPathError;
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+#[cfg(windows)]
+const TOKEN_FILE_NAME: &str = "lan-mouse-ipc-token";
+
+/// the first line sent on tcp connections
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AuthLine {
+    pub(crate) token: String,
+}
+
+/// location of the token authenticating tcp connections,
+/// next to the socket at `socket_path` or the default socket
+#[cfg(unix)]
+pub fn token_path(socket_path: Option<&Path>) -> Result<PathBuf, SocketPathError> {
+    Ok(crate::socket_path(socket_path)?.with_extension("token"))
+}
+
+/// location of the token authenticating tcp connections
+#[cfg(windows)]
+pub fn token_path(_socket_path: Option<&Path>) -> Result<PathBuf, SocketPathError> {
+    let app_data = std::env::var("LOCALAPPDATA").map_err(SocketPathError::LocalAppDataNotFound)?;
+    Ok(Path::new(app_data.as_str())
+        .join("lan-mouse")
+        .join(TOKEN_FILE_NAME))
+}
+
+/// generate a new token and write it to `path`, readable only by the current user
+pub(crate) fn create_token(path: &Path) -> io::Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes)?;
+    let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // recreate the file so the permissions of an old token do not apply
+    let _ = fs::remove_file(path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path)?.write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+/// read the token written by the service
+pub fn read_token(path: &Path) -> io::Result<String> {
+    Ok(fs::read_to_string(path)?.trim().to_string())
+}
+
+/// check the first line of a tcp connection
+pub(crate) fn authenticate(line: &str, token: &str) -> bool {
+    let Ok(AuthLine { token: received }) = serde_json::from_str(line) else {
+        return false;
+    };
+    // compare in constant time
+    received.len() == token.len()
+        && received
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let dir = std::env::temp_dir().join(format!("lan-mouse-token-{}", std::process::id()));
+        let path = dir.join("token");
+        let token = create_token(&path).unwrap();
+        assert_eq!(token.len(), 64);
+        assert_eq!(read_token(&path).unwrap(), token);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let line = serde_json::to_string(&AuthLine {
+            token: token.clone(),
+        })
+        .unwrap();
+        assert!(authenticate(&line, &token));
+        assert!(!authenticate(&line, &create_token(&path).unwrap()));
+        assert!(!authenticate(r#"{"token":""}"#, &token));
+        assert!(!authenticate(r#""Sync""#, &token));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
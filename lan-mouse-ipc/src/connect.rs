@@ -1,7 +1,14 @@
-use crate::{ConnectionError, FrontendEvent, FrontendRequest, IpcError};
+#[cfg(windows)]
+use crate::auth::{self, AuthLine};
+use crate::{
+    envelope::{self, RequestLine, ServiceLine},
+    ConnectionError, FrontendEvent, FrontendMessage, FrontendRequest, IpcError, RequestId,
+};
 use std::{
     cmp::min,
     io::{self, prelude::*, BufReader, LineWriter, Lines},
+    net::SocketAddr,
+    path::Path,
     thread,
     time::Duration,
 };
@@ -24,42 +31,80 @@ pub struct FrontendRequestWriter {
     line_writer: LineWriter<UnixStream>,
     #[cfg(windows)]
     line_writer: LineWriter<TcpStream>,
+    next_id: RequestId,
 }
 
 impl FrontendEventReader {
-    pub fn next_event(&mut self) -> Option<Result<FrontendEvent, IpcError>> {
+    pub fn next_message(&mut self) -> Option<Result<FrontendMessage, IpcError>> {
         match self.lines.next()? {
             Err(e) => Some(Err(e.into())),
-            Ok(l) => Some(serde_json::from_str(l.as_str()).map_err(|e| e.into())),
+            Ok(l) => Some(
+                ServiceLine::parse(l.as_str())
+                    .map(FrontendMessage::from)
+                    .map_err(|e| e.into()),
+            ),
+        }
+    }
+
+    /// next event, skipping replies
+    pub fn next_event(&mut self) -> Option<Result<FrontendEvent, IpcError>> {
+        loop {
+            match self.next_message()? {
+                Ok(FrontendMessage::Event(event)) => break Some(Ok(event)),
+                Ok(FrontendMessage::Reply(_, _)) => continue,
+                Err(e) => break Some(Err(e)),
+            }
         }
     }
 }
 
 impl FrontendRequestWriter {
+    /// send a request without expecting a reply
     pub fn request(&mut self, request: FrontendRequest) -> Result<(), io::Error> {
-        let mut json = serde_json::to_string(&request).unwrap();
+        let json = envelope::encode(&RequestLine::Bare(request));
         log::debug!("requesting: {json}");
-        json.push('\n');
         self.line_writer.write_all(json.as_bytes())?;
         Ok(())
     }
+
+    /// send a request, the service replies with
+    /// a [`FrontendMessage::Reply`] carrying the returned id
+    pub fn send(&mut self, request: FrontendRequest) -> Result<RequestId, io::Error> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let json = envelope::encode(&RequestLine::new(id, request));
+        log::debug!("requesting: {json}");
+        self.line_writer.write_all(json.as_bytes())?;
+        Ok(id)
+    }
 }
 
-pub fn connect() -> Result<(FrontendEventReader, FrontendRequestWriter), ConnectionError> {
-    let rx = wait_for_service()?;
+/// connect to the service listening on `socket_path` or the default socket.
+/// on windows, the service is always reached via tcp on `tcp` or [`crate::DEFAULT_IPC_TCP`]
+pub fn connect(
+    socket_path: Option<&Path>,
+    #[cfg_attr(unix, allow(unused_variables))] tcp: Option<SocketAddr>,
+) -> Result<(FrontendEventReader, FrontendRequestWriter), ConnectionError> {
+    #[cfg(unix)]
+    let rx = wait_for_service(socket_path)?;
+    #[cfg(windows)]
+    let rx = wait_for_service(socket_path, tcp.unwrap_or(crate::DEFAULT_IPC_TCP))?;
     let tx = rx.try_clone()?;
     let buf_reader = BufReader::new(rx);
     let lines = buf_reader.lines();
     let line_writer = LineWriter::new(tx);
     let reader = FrontendEventReader { lines };
-    let writer = FrontendRequestWriter { line_writer };
+    let writer = FrontendRequestWriter {
+        line_writer,
+        next_id: 0,
+    };
     Ok((reader, writer))
 }
 
 /// wait for the lan-mouse socket to come online
 #[cfg(unix)]
-fn wait_for_service() -> Result<UnixStream, ConnectionError> {
-    let socket_path = crate::default_socket_path()?;
+fn wait_for_service(socket_path: Option<&Path>) -> Result<UnixStream, ConnectionError> {
+    let socket_path = crate::socket_path(socket_path)?;
     let mut duration = Duration::from_millis(10);
     loop {
         if let Ok(stream) = UnixStream::connect(&socket_path) {
@@ -72,10 +117,15 @@ fn wait_for_service() -> Result<UnixStream, ConnectionError> {
 }
 
 #[cfg(windows)]
-fn wait_for_service() -> Result<TcpStream, ConnectionError> {
+fn wait_for_service(
+    socket_path: Option<&Path>,
+    addr: SocketAddr,
+) -> Result<TcpStream, ConnectionError> {
     let mut duration = Duration::from_millis(10);
     loop {
-        if let Ok(stream) = TcpStream::connect("127.0.0.1:5252") {
+        if let Ok(mut stream) = TcpStream::connect(addr) {
+            let token = auth::read_token(&auth::token_path(socket_path)?)?;
+            stream.write_all(envelope::encode(&AuthLine { token }).as_bytes())?;
             break Ok(stream);
         }
         thread::sleep(exponential_back_off(&mut duration));
@@ -1,37 +1,38 @@
-use crate::{ConnectionError, FrontendEvent, FrontendRequest, IpcError};
+use crate::{
+    auth::AuthLine,
+    envelope::{self, RequestLine, ServiceLine},
+    ConnectionError, FrontendMessage, FrontendRequest, IpcError, RequestId,
+};
 use std::{
     cmp::min,
     io,
+    net::SocketAddr,
+    path::Path,
     task::{ready, Poll},
     time::Duration,
 };
 
-use futures::{Stream, StreamExt};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, ReadHalf, WriteHalf};
+use futures::{stream::BoxStream, Stream, StreamExt};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
 use tokio_stream::wrappers::LinesStream;
 
 #[cfg(unix)]
 use tokio::net::UnixStream;
 
-#[cfg(windows)]
-use tokio::net::TcpStream;
-
 pub struct AsyncFrontendEventReader {
-    #[cfg(unix)]
-    lines_stream: LinesStream<BufReader<ReadHalf<UnixStream>>>,
-    #[cfg(windows)]
-    lines_stream: LinesStream<BufReader<ReadHalf<TcpStream>>>,
+    lines_stream: BoxStream<'static, io::Result<String>>,
 }
 
 pub struct AsyncFrontendRequestWriter {
-    #[cfg(unix)]
-    tx: WriteHalf<UnixStream>,
-    #[cfg(windows)]
-    tx: WriteHalf<TcpStream>,
+    tx: Box<dyn AsyncWrite + Unpin + Send>,
+    next_id: RequestId,
 }
 
 impl Stream for AsyncFrontendEventReader {
-    type Item = Result<FrontendEvent, IpcError>;
+    type Item = Result<FrontendMessage, IpcError>;
 
     fn poll_next(
         mut self: std::pin::Pin<&mut Self>,
@@ -40,41 +41,82 @@ impl Stream for AsyncFrontendEventReader {
         let line = ready!(self.lines_stream.poll_next_unpin(cx));
         let event = line.map(|l| {
             l.map_err(Into::<IpcError>::into)
-                .and_then(|l| serde_json::from_str(l.as_str()).map_err(|e| e.into()))
+                .and_then(|l| ServiceLine::parse(l.as_str()).map_err(|e| e.into()))
+                .map(FrontendMessage::from)
         });
         Poll::Ready(event)
     }
 }
 
 impl AsyncFrontendRequestWriter {
+    /// send a request without expecting a reply
     pub async fn request(&mut self, request: FrontendRequest) -> Result<(), io::Error> {
-        let mut json = serde_json::to_string(&request).unwrap();
+        let json = envelope::encode(&RequestLine::Bare(request));
         log::debug!("requesting: {json}");
-        json.push('\n');
         self.tx.write_all(json.as_bytes()).await?;
         Ok(())
     }
+
+    /// send a request, the service replies with
+    /// a [`FrontendMessage::Reply`] carrying the returned id
+    pub async fn send(&mut self, request: FrontendRequest) -> Result<RequestId, io::Error> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let json = envelope::encode(&RequestLine::new(id, request));
+        log::debug!("requesting: {json}");
+        self.tx.write_all(json.as_bytes()).await?;
+        Ok(id)
+    }
 }
 
+/// connect to the service listening on `socket_path` or the default socket.
+/// on windows, the service is always reached via tcp on `tcp` or [`crate::DEFAULT_IPC_TCP`]
 pub async fn connect_async(
+    socket_path: Option<&Path>,
+    #[cfg_attr(unix, allow(unused_variables))] tcp: Option<SocketAddr>,
 ) -> Result<(AsyncFrontendEventReader, AsyncFrontendRequestWriter), ConnectionError> {
-    let stream = wait_for_service().await?;
     #[cfg(unix)]
-    let (rx, tx): (ReadHalf<UnixStream>, WriteHalf<UnixStream>) = tokio::io::split(stream);
+    let stream = wait_for_service(socket_path).await?;
     #[cfg(windows)]
-    let (rx, tx): (ReadHalf<TcpStream>, WriteHalf<TcpStream>) = tokio::io::split(stream);
+    let stream = wait_for_service(socket_path, tcp.unwrap_or(crate::DEFAULT_IPC_TCP)).await?;
+    Ok(split(stream))
+}
+
+/// connect to a service listening for frontends on `addr`,
+/// authenticating with `token`
+pub async fn connect_async_tcp(
+    addr: SocketAddr,
+    token: String,
+) -> Result<(AsyncFrontendEventReader, AsyncFrontendRequestWriter), ConnectionError> {
+    let mut stream = TcpStream::connect(addr).await?;
+    authenticate(&mut stream, token).await?;
+    Ok(split(stream))
+}
+
+fn split<S: AsyncRead + AsyncWrite + Send + 'static>(
+    stream: S,
+) -> (AsyncFrontendEventReader, AsyncFrontendRequestWriter) {
+    let (rx, tx) = tokio::io::split(stream);
     let buf_reader = BufReader::new(rx);
     let lines = buf_reader.lines();
-    let lines_stream = LinesStream::new(lines);
+    let lines_stream = LinesStream::new(lines).boxed();
     let reader = AsyncFrontendEventReader { lines_stream };
-    let writer = AsyncFrontendRequestWriter { tx };
-    Ok((reader, writer))
+    let writer = AsyncFrontendRequestWriter {
+        tx: Box::new(tx),
+        next_id: 0,
+    };
+    (reader, writer)
+}
+
+async fn authenticate(stream: &mut TcpStream, token: String) -> Result<(), io::Error> {
+    let json = envelope::encode(&AuthLine { token });
+    stream.write_all(json.as_bytes()).await
 }
 
 /// wait for the lan-mouse socket to come online
 #[cfg(unix)]
-async fn wait_for_service() -> Result<UnixStream, ConnectionError> {
-    let socket_path = crate::default_socket_path()?;
+async fn wait_for_service(socket_path: Option<&Path>) -> Result<UnixStream, ConnectionError> {
+    let socket_path = crate::socket_path(socket_path)?;
     let mut duration = Duration::from_millis(10);
     loop {
         if let Ok(stream) = UnixStream::connect(&socket_path).await {
@@ -87,10 +129,15 @@ async fn wait_for_service() -> Result<UnixStream, ConnectionError> {
 }
 
 #[cfg(windows)]
-async fn wait_for_service() -> Result<TcpStream, ConnectionError> {
+async fn wait_for_service(
+    socket_path: Option<&Path>,
+    addr: SocketAddr,
+) -> Result<TcpStream, ConnectionError> {
     let mut duration = Duration::from_millis(10);
     loop {
-        if let Ok(stream) = TcpStream::connect("127.0.0.1:5252").await {
+        if let Ok(mut stream) = TcpStream::connect(addr).await {
+            let token = crate::read_token(&crate::token_path(socket_path)?)?;
+            authenticate(&mut stream, token).await?;
             break Ok(stream);
         }
         tokio::time::sleep(exponential_back_off(&mut duration)).await;
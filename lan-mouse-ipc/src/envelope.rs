@@ -0,0 +1,238 @@
+//! versioned envelope of the lines exchanged between frontends and the service.
+//!
+//! Frontends sending bare [`FrontendRequest`]s receive bare [`FrontendEvent`]s
+//! and no replies, as before the envelope was introduced.
+//! Events added since are not sent to these frontends.
+//! Once a frontend sends a request in an envelope, the service sends events
+//! and replies to this frontend in envelopes as well:
+//!
+//! ```text
+//! -> {"version":1,"id":3,"request":{"GetState":0}}
+//! <- {"version":1,"event":{"Changed":0}}
+//! <- {"version":1,"id":3,"reply":{"State":[0,{...},{...}]}}
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::{FrontendEvent, FrontendMessage, FrontendReply, FrontendRequest, RequestId};
+
+/// version of the envelope
+pub const IPC_VERSION: u32 = 1;
+
+/// a line sent by a frontend
+#[derive(Serialize)]
+#[serde(untagged)]
+pub(crate) enum RequestLine {
+    Envelope {
+        version: u32,
+        id: RequestId,
+        request: FrontendRequest,
+    },
+    Bare(FrontendRequest),
+}
+
+/// a line sent by the service
+#[derive(Serialize)]
+#[serde(untagged)]
+pub(crate) enum ServiceLine {
+    Reply {
+        version: u32,
+        id: RequestId,
+        reply: FrontendReply,
+    },
+    Event {
+        version: u32,
+        event: FrontendEvent,
+    },
+    Bare(FrontendEvent),
+}
+
+// lines are parsed by hand instead of deriving an untagged `Deserialize`:
+// buffered deserialization fails on empty tuple variants like `Enumerate()`
+
+/// the fields telling envelopes apart from bare lines
+#[derive(Deserialize)]
+struct Probe {
+    version: Option<u32>,
+    id: Option<RequestId>,
+}
+
+impl Probe {
+    fn new(line: &str) -> Self {
+        serde_json::from_str(line).unwrap_or(Probe {
+            version: None,
+            id: None,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RequestEnvelope {
+    version: u32,
+    id: RequestId,
+    request: FrontendRequest,
+}
+
+#[derive(Deserialize)]
+struct ReplyEnvelope {
+    version: u32,
+    id: RequestId,
+    reply: FrontendReply,
+}
+
+#[derive(Deserialize)]
+struct EventEnvelope {
+    version: u32,
+    event: FrontendEvent,
+}
+
+impl RequestLine {
+    pub(crate) fn parse(line: &str) -> serde_json::Result<Self> {
+        if Probe::new(line).version.is_none() {
+            return serde_json::from_str(line).map(Self::Bare);
+        }
+        let RequestEnvelope {
+            version,
+            id,
+            request,
+        } = serde_json::from_str(line)?;
+        Ok(Self::Envelope {
+            version,
+            id,
+            request,
+        })
+    }
+
+    pub(crate) fn new(id: RequestId, request: FrontendRequest) -> Self {
+        Self::Envelope {
+            version: IPC_VERSION,
+            id,
+            request,
+        }
+    }
+}
+
+impl ServiceLine {
+    pub(crate) fn parse(line: &str) -> serde_json::Result<Self> {
+        match Probe::new(line) {
+            Probe { version: None, .. } => serde_json::from_str(line).map(Self::Bare),
+            Probe { id: Some(_), .. } => {
+                let ReplyEnvelope { version, id, reply } = serde_json::from_str(line)?;
+                Ok(Self::Reply { version, id, reply })
+            }
+            Probe { id: None, .. } => {
+                let EventEnvelope { version, event } = serde_json::from_str(line)?;
+                Ok(Self::Event { version, event })
+            }
+        }
+    }
+
+    pub(crate) fn event(event: FrontendEvent, enveloped: bool) -> Self {
+        match enveloped {
+            true => Self::Event {
+                version: IPC_VERSION,
+                event,
+            },
+            false => Self::Bare(event),
+        }
+    }
+
+    pub(crate) fn reply(id: RequestId, reply: FrontendReply) -> Self {
+        Self::Reply {
+            version: IPC_VERSION,
+            id,
+            reply,
+        }
+    }
+}
+
+impl From<ServiceLine> for FrontendMessage {
+    fn from(line: ServiceLine) -> Self {
+        match line {
+            ServiceLine::Reply { id, reply, .. } => FrontendMessage::Reply(id, reply),
+            ServiceLine::Event { event, .. } | ServiceLine::Bare(event) => {
+                FrontendMessage::Event(event)
+            }
+        }
+    }
+}
+
+/// encode as a json line
+pub(crate) fn encode<T: Serialize>(line: &T) -> String {
+    let mut json = serde_json::to_string(line).expect("failed to serialize");
+    json.push('\n');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClientConfig;
+
+    #[test]
+    fn bare_lines_are_accepted() {
+        let line = RequestLine::parse(r#"{"GetState":3}"#).unwrap();
+        assert!(matches!(
+            line,
+            RequestLine::Bare(FrontendRequest::GetState(3))
+        ));
+        let line = ServiceLine::parse(r#"{"Changed":3}"#).unwrap();
+        assert!(matches!(
+            line.into(),
+            FrontendMessage::Event(FrontendEvent::Changed(3))
+        ));
+    }
+
+    #[test]
+    fn envelopes() {
+        let json = encode(&RequestLine::new(7, FrontendRequest::Create));
+        assert_eq!(json, "{\"version\":1,\"id\":7,\"request\":\"Create\"}\n");
+        let line = RequestLine::parse(&json).unwrap();
+        assert!(matches!(
+            line,
+            RequestLine::Envelope {
+                id: 7,
+                request: FrontendRequest::Create,
+                ..
+            }
+        ));
+        let json = encode(&RequestLine::new(8, FrontendRequest::Enumerate()));
+        assert!(matches!(
+            RequestLine::parse(&json).unwrap(),
+            RequestLine::Envelope {
+                id: 8,
+                request: FrontendRequest::Enumerate(),
+                ..
+            }
+        ));
+
+        let json = encode(&ServiceLine::reply(7, FrontendReply::Created(2)));
+        let line = ServiceLine::parse(&json).unwrap();
+        assert!(matches!(
+            line.into(),
+            FrontendMessage::Reply(7, FrontendReply::Created(2))
+        ));
+        let json = encode(&ServiceLine::event(FrontendEvent::Deleted(2), true));
+        assert_eq!(json, "{\"version\":1,\"event\":{\"Deleted\":2}}\n");
+        let line = ServiceLine::parse(&json).unwrap();
+        assert!(matches!(
+            line.into(),
+            FrontendMessage::Event(FrontendEvent::Deleted(2))
+        ));
+    }
+
+    #[test]
+    fn legacy_client_config() {
+        assert!(FrontendEvent::Deleted(2).is_legacy());
+        assert!(!FrontendEvent::PeerLost("peer".into()).is_legacy());
+
+        let json = r#"{"hostname":"peer","fix_ips":[],"port":4242,"pos":"Left","cmd":"echo"}"#;
+        let config: ClientConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.hostname.as_deref(), Some("peer"));
+        assert_eq!(config.hooks.enter.as_deref(), Some("echo"));
+        assert_eq!(config.transport, Default::default());
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(serde_json::from_str::<ClientConfig>(&json).unwrap(), config);
+    }
+}
@@ -14,15 +14,21 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
+mod auth;
 mod connect;
 mod connect_async;
+mod envelope;
 mod listen;
 
+pub use auth::{read_token, token_path};
 pub use connect::{connect, FrontendEventReader, FrontendRequestWriter};
-pub use connect_async::{connect_async, AsyncFrontendEventReader, AsyncFrontendRequestWriter};
-pub use listen::AsyncFrontendListener;
+pub use connect_async::{
+    connect_async, connect_async_tcp, AsyncFrontendEventReader, AsyncFrontendRequestWriter,
+};
+pub use envelope::IPC_VERSION;
+pub use listen::{AsyncFrontendListener, RequestOrigin};
 
 #[derive(Debug, Error)]
 pub enum ConnectionError {
@@ -40,6 +46,8 @@ pub enum ListenerCreationError {
     AlreadyRunning,
     #[error("failed to bind lan-mouse socket: `{0}`")]
     Bind(io::Error),
+    #[error("failed to write ipc token: `{0}`")]
+    Token(io::Error),
 }
 
 #[derive(Debug, Error)]
@@ -56,6 +64,12 @@ pub enum IpcError {
 
 pub const DEFAULT_PORT: u16 = 4242;
 
+/// address frontends connect to on windows, unless configured otherwise
+pub const DEFAULT_IPC_TCP: SocketAddr = SocketAddr::V4(std::net::SocketAddrV4::new(
+    std::net::Ipv4Addr::LOCALHOST,
+    5252,
+));
+
 #[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum Position {
     #[default]
@@ -114,7 +128,291 @@ impl TryFrom<&str> for Position {
     }
 }
 
+/// how events are transported to a client
+#[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// one datagram per event
+    #[default]
+    Udp,
+    /// persistent tcp connection, for networks that drop udp
+    Tcp,
+}
+
+#[derive(Debug, Error)]
+#[error("not a valid transport: {transport}")]
+pub struct TransportParseError {
+    transport: String,
+}
+
+impl FromStr for Transport {
+    type Err = TransportParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "udp" => Ok(Self::Udp),
+            "tcp" => Ok(Self::Tcp),
+            _ => Err(TransportParseError {
+                transport: s.into(),
+            }),
+        }
+    }
+}
+
+impl Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transport::Udp => write!(f, "udp"),
+            Transport::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+/// hardware address of a client, used for wake-on-lan
+#[derive(Debug, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MacAddress(pub [u8; 6]);
+
+#[derive(Debug, Error)]
+#[error("not a valid mac address: {mac}")]
+pub struct MacAddressParseError {
+    mac: String,
+}
+
+impl FromStr for MacAddress {
+    type Err = MacAddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || MacAddressParseError { mac: s.into() };
+        let mut mac = [0u8; 6];
+        let mut parts = s.split([':', '-']);
+        for byte in mac.iter_mut() {
+            let part = parts.next().filter(|p| p.len() == 2).ok_or_else(err)?;
+            *byte = u8::from_str_radix(part, 16).map_err(|_| err())?;
+        }
+        match parts.next() {
+            Some(_) => Err(err()),
+            None => Ok(Self(mac)),
+        }
+    }
+}
+
+impl TryFrom<String> for MacAddress {
+    type Error = MacAddressParseError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<MacAddress> for String {
+    fn from(mac: MacAddress) -> Self {
+        mac.to_string()
+    }
+}
+
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+/// what to do when a client stops responding to pings
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LivenessAction {
+    /// release the pointer and keys pressed on this device
+    #[default]
+    Release,
+    /// only release keys pressed on this device
+    ReleaseKeys,
+    /// only log a warning
+    Warn,
+}
+
+#[derive(Debug, Error)]
+#[error("not a valid liveness action: {action}")]
+pub struct LivenessActionParseError {
+    action: String,
+}
+
+impl FromStr for LivenessAction {
+    type Err = LivenessActionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "release" => Ok(Self::Release),
+            "release-keys" => Ok(Self::ReleaseKeys),
+            "warn" => Ok(Self::Warn),
+            _ => Err(LivenessActionParseError { action: s.into() }),
+        }
+    }
+}
+
+impl Display for LivenessAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LivenessAction::Release => write!(f, "release"),
+            LivenessAction::ReleaseKeys => write!(f, "release-keys"),
+            LivenessAction::Warn => write!(f, "warn"),
+        }
+    }
+}
+
+/// how a client that is sent events or has pressed keys is checked for liveness
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct Liveness {
+    /// time between pings in milliseconds
+    pub ping_interval_ms: u64,
+    /// number of unanswered pings after which `action` is taken
+    pub max_missed_pings: u32,
+    /// what to do when the client stops responding
+    pub action: LivenessAction,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self {
+            ping_interval_ms: 500,
+            max_missed_pings: 1,
+            action: Default::default(),
+        }
+    }
+}
+
+/// class of input events, used for filtering the events exchanged with a client
+#[derive(Debug, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventClass {
+    /// pointer motion
+    Motion,
+    /// pointer buttons
+    Buttons,
+    /// scroll wheel and touchpad scrolling
+    Scroll,
+    /// keys and modifiers
+    Keys,
+}
+
+#[derive(Debug, Error)]
+#[error("not a valid event class: {class}")]
+pub struct EventClassParseError {
+    class: String,
+}
+
+impl FromStr for EventClass {
+    type Err = EventClassParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "motion" => Ok(Self::Motion),
+            "buttons" => Ok(Self::Buttons),
+            "scroll" => Ok(Self::Scroll),
+            "keys" => Ok(Self::Keys),
+            _ => Err(EventClassParseError { class: s.into() }),
+        }
+    }
+}
+
+impl Display for EventClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventClass::Motion => write!(f, "motion"),
+            EventClass::Buttons => write!(f, "buttons"),
+            EventClass::Scroll => write!(f, "scroll"),
+            EventClass::Keys => write!(f, "keys"),
+        }
+    }
+}
+
+/// classes of input events that are exchanged with a client
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct EventFilter {
+    pub motion: bool,
+    pub buttons: bool,
+    pub scroll: bool,
+    pub keys: bool,
+}
+
+impl EventFilter {
+    pub const ALL: [EventClass; 4] = [
+        EventClass::Motion,
+        EventClass::Buttons,
+        EventClass::Scroll,
+        EventClass::Keys,
+    ];
+
+    pub fn allows(&self, class: EventClass) -> bool {
+        match class {
+            EventClass::Motion => self.motion,
+            EventClass::Buttons => self.buttons,
+            EventClass::Scroll => self.scroll,
+            EventClass::Keys => self.keys,
+        }
+    }
+}
+
+impl Default for EventFilter {
+    fn default() -> Self {
+        Self::from_iter(Self::ALL)
+    }
+}
+
+impl FromIterator<EventClass> for EventFilter {
+    fn from_iter<T: IntoIterator<Item = EventClass>>(iter: T) -> Self {
+        let mut filter = Self {
+            motion: false,
+            buttons: false,
+            scroll: false,
+            keys: false,
+        };
+        for class in iter {
+            match class {
+                EventClass::Motion => filter.motion = true,
+                EventClass::Buttons => filter.buttons = true,
+                EventClass::Scroll => filter.scroll = true,
+                EventClass::Keys => filter.keys = true,
+            }
+        }
+        filter
+    }
+}
+
+impl Display for EventFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let classes: Vec<String> = Self::ALL
+            .into_iter()
+            .filter(|&c| self.allows(c))
+            .map(|c| c.to_string())
+            .collect();
+        match classes.len() {
+            0 => write!(f, "none"),
+            4 => write!(f, "all"),
+            _ => write!(f, "{}", classes.join(",")),
+        }
+    }
+}
+
+/// shell commands run on events concerning a client
+#[derive(Debug, Default, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Hooks {
+    /// the pointer entered the client
+    pub enter: Option<String>,
+    /// the pointer left the client
+    pub leave: Option<String>,
+    /// the client started responding to heartbeats
+    pub online: Option<String>,
+    /// the client stopped responding to heartbeats
+    pub offline: Option<String>,
+    /// the client started sending events to this device
+    pub receive_start: Option<String>,
+    /// the client stopped sending events to this device
+    pub receive_stop: Option<String>,
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ClientConfig {
     /// hostname of this client
     pub hostname: Option<String>,
@@ -124,8 +422,19 @@ pub struct ClientConfig {
     pub port: u16,
     /// position of a client on screen
     pub pos: Position,
-    /// enter hook
-    pub cmd: Option<String>,
+    /// commands run on events concerning this client
+    #[serde(alias = "cmd", deserialize_with = "hooks_or_cmd")]
+    pub hooks: Hooks,
+    /// transport used for sending events to this client
+    pub transport: Transport,
+    /// liveness checks for this client
+    pub liveness: Liveness,
+    /// mac address for waking the client
+    pub mac: Option<MacAddress>,
+    /// events forwarded to the client
+    pub send_filter: EventFilter,
+    /// events accepted from the client
+    pub receive_filter: EventFilter,
 }
 
 impl Default for ClientConfig {
@@ -135,11 +444,33 @@ impl Default for ClientConfig {
             hostname: Default::default(),
             fix_ips: Default::default(),
             pos: Default::default(),
-            cmd: None,
+            hooks: Default::default(),
+            transport: Default::default(),
+            liveness: Default::default(),
+            mac: None,
+            send_filter: Default::default(),
+            receive_filter: Default::default(),
         }
     }
 }
 
+/// frontends predating [`Hooks`] send the enter hook as `cmd`
+fn hooks_or_cmd<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hooks, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum HooksOrCmd {
+        Hooks(Hooks),
+        Cmd(Option<String>),
+    }
+    Ok(match HooksOrCmd::deserialize(deserializer)? {
+        HooksOrCmd::Hooks(hooks) => hooks,
+        HooksOrCmd::Cmd(enter) => Hooks {
+            enter,
+            ..Default::default()
+        },
+    })
+}
+
 pub type ClientHandle = u64;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -162,6 +493,26 @@ pub struct ClientState {
     pub has_pressed_keys: bool,
     /// dns resolving in progress
     pub resolving: bool,
+    /// whether the client responds to heartbeats
+    pub presence: Presence,
+    /// a wake-on-lan packet was sent and the client has not responded yet
+    pub waking: bool,
+}
+
+/// a lan-mouse instance found on the local network
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DiscoveredPeer {
+    /// hostname advertised by the peer
+    pub hostname: String,
+    /// addresses the peer was discovered at
+    pub ips: Vec<IpAddr>,
+    /// port the peer is listening on
+    pub port: u16,
+    /// protocol version advertised by the peer
+    pub protocol_version: Option<u32>,
+    /// fingerprint of the key identifying the peer
+    #[serde(default)]
+    pub fingerprint: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -186,6 +537,39 @@ pub enum FrontendEvent {
     CaptureStatus(Status),
     /// emulation status
     EmulationStatus(Status),
+    /// the service started or stopped sending events
+    FocusChanged(FocusState),
+    /// the client that was most recently entered by the pointer
+    ActiveClientChanged(Option<ClientHandle>),
+    /// a peer was found via mDNS
+    PeerDiscovered(DiscoveredPeer),
+    /// a previously discovered peer (hostname) disappeared
+    PeerLost(String),
+    /// a client came online or went offline
+    PresenceChanged(ClientHandle, Presence),
+}
+
+impl FrontendEvent {
+    /// whether frontends predating the envelope know the event
+    pub(crate) fn is_legacy(&self) -> bool {
+        match self {
+            FrontendEvent::Changed(_)
+            | FrontendEvent::Created(_, _, _)
+            | FrontendEvent::NoSuchClient(_)
+            | FrontendEvent::State(_, _, _)
+            | FrontendEvent::Deleted(_)
+            | FrontendEvent::PortChanged(_, _)
+            | FrontendEvent::Enumerate(_)
+            | FrontendEvent::Error(_)
+            | FrontendEvent::CaptureStatus(_)
+            | FrontendEvent::EmulationStatus(_) => true,
+            FrontendEvent::FocusChanged(_)
+            | FrontendEvent::ActiveClientChanged(_)
+            | FrontendEvent::PeerDiscovered(_)
+            | FrontendEvent::PeerLost(_)
+            | FrontendEvent::PresenceChanged(_, _) => false,
+        }
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
@@ -210,6 +594,18 @@ pub enum FrontendRequest {
     UpdatePosition(ClientHandle, Position),
     /// update fix-ips
     UpdateFixIps(ClientHandle, Vec<IpAddr>),
+    /// update the transport
+    UpdateTransport(ClientHandle, Transport),
+    /// update the liveness settings
+    UpdateLiveness(ClientHandle, Liveness),
+    /// update the mac address
+    UpdateMac(ClientHandle, Option<MacAddress>),
+    /// send a wake-on-lan packet to the client
+    Wake(ClientHandle),
+    /// update the events forwarded to the client
+    UpdateSendFilter(ClientHandle, EventFilter),
+    /// update the events accepted from the client
+    UpdateReceiveFilter(ClientHandle, EventFilter),
     /// request the state of the given client
     GetState(ClientHandle),
     /// request reenabling input capture
@@ -218,6 +614,69 @@ pub enum FrontendRequest {
     EnableEmulation,
     /// synchronize all state
     Sync,
+    /// create and activate a client for the discovered peer (hostname)
+    AddDiscovered(String, Position),
+}
+
+impl FrontendRequest {
+    /// the client the request refers to
+    pub fn client_handle(&self) -> Option<ClientHandle> {
+        match self {
+            FrontendRequest::Activate(h, _)
+            | FrontendRequest::Delete(h)
+            | FrontendRequest::ResolveDns(h)
+            | FrontendRequest::UpdateHostname(h, _)
+            | FrontendRequest::UpdatePort(h, _)
+            | FrontendRequest::UpdatePosition(h, _)
+            | FrontendRequest::UpdateFixIps(h, _)
+            | FrontendRequest::UpdateTransport(h, _)
+            | FrontendRequest::UpdateLiveness(h, _)
+            | FrontendRequest::UpdateMac(h, _)
+            | FrontendRequest::Wake(h)
+            | FrontendRequest::UpdateSendFilter(h, _)
+            | FrontendRequest::UpdateReceiveFilter(h, _)
+            | FrontendRequest::GetState(h) => Some(*h),
+            FrontendRequest::Create
+            | FrontendRequest::ChangePort(_)
+            | FrontendRequest::Enumerate()
+            | FrontendRequest::EnableCapture
+            | FrontendRequest::EnableEmulation
+            | FrontendRequest::Sync
+            | FrontendRequest::AddDiscovered(_, _) => None,
+        }
+    }
+}
+
+/// id of a request, chosen by the frontend and unique per connection
+pub type RequestId = u64;
+
+/// reply of the service to a request sent with an id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)]
+pub enum FrontendReply {
+    /// the request was handled, changes of the state are broadcast as events.
+    /// for [`FrontendRequest::ChangePort`], [`FrontendRequest::EnableCapture`]
+    /// and [`FrontendRequest::EnableEmulation`] the result follows as an event
+    Ok,
+    /// a client was created ([`FrontendRequest::Create`], [`FrontendRequest::AddDiscovered`])
+    Created(ClientHandle),
+    /// state of a client ([`FrontendRequest::GetState`])
+    State(ClientHandle, ClientConfig, ClientState),
+    /// all clients ([`FrontendRequest::Enumerate`])
+    Enumerate(Vec<(ClientHandle, ClientConfig, ClientState)>),
+    /// the request refers to a client that does not exist
+    NoSuchClient(ClientHandle),
+    /// the request failed
+    Error(String),
+}
+
+/// a message from the service
+#[derive(Debug, Clone)]
+pub enum FrontendMessage {
+    /// broadcast to all frontends
+    Event(FrontendEvent),
+    /// reply to the request with the given id
+    Reply(RequestId, FrontendReply),
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
@@ -227,6 +686,41 @@ pub enum Status {
     Enabled,
 }
 
+/// whether the service is currently sending events or receiving them
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum FocusState {
+    /// Currently sending events to another device
+    Sending,
+    /// Currently receiving events from other devices
+    #[default]
+    Receiving,
+    /// Entered the deadzone of another device but waiting
+    /// for acknowledgement (Leave event) from the device
+    AwaitAck,
+}
+
+/// reachability of a client, as determined by periodic heartbeats
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Presence {
+    /// the client is not active or no heartbeat has been answered yet
+    #[default]
+    Unknown,
+    /// the client answers heartbeats
+    Online,
+    /// the client stopped answering heartbeats
+    Offline,
+}
+
+impl Display for Presence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Presence::Unknown => write!(f, "unknown"),
+            Presence::Online => write!(f, "online"),
+            Presence::Offline => write!(f, "offline"),
+        }
+    }
+}
+
 impl From<Status> for bool {
     fn from(status: Status) -> Self {
         match status {
@@ -245,6 +739,17 @@ pub enum SocketPathError {
     XdgRuntimeDirNotFound(VarError),
     #[error("could not determine $HOME: `{0}`")]
     HomeDirNotFound(VarError),
+    #[error("could not determine %LOCALAPPDATA%: `{0}`")]
+    LocalAppDataNotFound(VarError),
+}
+
+/// `socket_path` if given or the default location of the socket
+#[cfg(unix)]
+pub fn socket_path(socket_path: Option<&Path>) -> Result<PathBuf, SocketPathError> {
+    match socket_path {
+        Some(path) => Ok(path.to_path_buf()),
+        None => default_socket_path(),
+    }
 }
 
 #[cfg(all(unix, not(target_os = "macos")))]
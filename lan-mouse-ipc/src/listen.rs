@@ -1,13 +1,24 @@
-use futures::{stream::SelectAll, Stream, StreamExt};
+use futures::{
+    future::BoxFuture,
+    stream::{BoxStream, FuturesUnordered, SelectAll},
+    FutureExt, Stream, StreamExt,
+};
 #[cfg(unix)]
-use std::path::PathBuf;
+use std::{fs, os::unix::fs::PermissionsExt};
 use std::{
-    io::ErrorKind,
+    io::{self, ErrorKind},
+    net::SocketAddr,
+    path::PathBuf,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, ReadHalf, WriteHalf};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
 use tokio_stream::wrappers::LinesStream;
 
 #[cfg(unix)]
@@ -15,35 +26,59 @@ use tokio::net::UnixListener;
 #[cfg(unix)]
 use tokio::net::UnixStream;
 
-#[cfg(windows)]
-use tokio::net::TcpListener;
-#[cfg(windows)]
-use tokio::net::TcpStream;
+use crate::{
+    auth,
+    envelope::{self, RequestLine, ServiceLine},
+    FrontendEvent, FrontendReply, FrontendRequest, IpcError, ListenerCreationError, RequestId,
+};
+
+/// time a tcp connection has to authenticate
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
 
-use crate::{FrontendEvent, FrontendRequest, IpcError, ListenerCreationError};
+type Lines = BoxStream<'static, io::Result<String>>;
+type Writer = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// the connection a request was received on and its id,
+/// required to reply to the request
+#[derive(Clone, Copy, Debug)]
+pub struct RequestOrigin {
+    connection: usize,
+    id: RequestId,
+}
+
+struct Connection {
+    id: usize,
+    tx: Writer,
+    /// the frontend uses the versioned envelope
+    enveloped: bool,
+}
 
 pub struct AsyncFrontendListener {
-    #[cfg(windows)]
-    listener: TcpListener,
     #[cfg(unix)]
     listener: UnixListener,
+    /// socket to remove when the listener is dropped,
+    /// `None` for sockets passed by the service manager
     #[cfg(unix)]
-    socket_path: PathBuf,
-    #[cfg(unix)]
-    line_streams: SelectAll<LinesStream<BufReader<ReadHalf<UnixStream>>>>,
-    #[cfg(windows)]
-    line_streams: SelectAll<LinesStream<BufReader<ReadHalf<TcpStream>>>>,
-    #[cfg(unix)]
-    tx_streams: Vec<WriteHalf<UnixStream>>,
-    #[cfg(windows)]
-    tx_streams: Vec<WriteHalf<TcpStream>>,
+    socket_path: Option<PathBuf>,
+    /// tcp listener and the token connections authenticate with
+    tcp: Option<(TcpListener, Arc<String>)>,
+    authenticating: FuturesUnordered<BoxFuture<'static, Option<(Lines, Writer)>>>,
+    line_streams: SelectAll<BoxStream<'static, (usize, io::Result<String>)>>,
+    connections: Vec<Connection>,
+    next_connection: usize,
 }
 
 impl AsyncFrontendListener {
-    pub async fn new() -> Result<Self, ListenerCreationError> {
+    /// listen for frontends on the socket at `socket_path` (or the default socket)
+    /// and on `tcp` for frontends authenticated by a token.
+    /// on windows, frontends always connect via tcp ([`crate::DEFAULT_IPC_TCP`] by default)
+    pub async fn new(
+        socket_path: Option<PathBuf>,
+        tcp: Option<SocketAddr>,
+    ) -> Result<Self, ListenerCreationError> {
         #[cfg(unix)]
         let (socket_path, listener) = {
-            let socket_path = crate::default_socket_path()?;
+            let socket_path = crate::socket_path(socket_path.as_deref())?;
 
             log::debug!("remove socket: {:?}", socket_path);
             if socket_path.exists() {
@@ -67,79 +102,237 @@ impl AsyncFrontendListener {
                 }
                 Err(e) => return Err(ListenerCreationError::Bind(e)),
             };
+            // only the owner may connect, peers are checked on accept as well
+            fs::set_permissions(&socket_path, fs::Permissions::from_mode(0o600))
+                .map_err(ListenerCreationError::Bind)?;
             (socket_path, listener)
         };
 
+        #[cfg(unix)]
+        let tcp = bind_tcp(tcp, auth::token_path(Some(&socket_path))?).await?;
         #[cfg(windows)]
-        let listener = match TcpListener::bind("127.0.0.1:5252").await {
-            Ok(ls) => ls,
-            // some other lan-mouse instance has bound the socket in the meantime
-            Err(e) if e.kind() == ErrorKind::AddrInUse => {
-                return Err(ListenerCreationError::AlreadyRunning)
-            }
-            Err(e) => return Err(ListenerCreationError::Bind(e)),
-        };
+        let tcp = bind_tcp(
+            tcp.or(Some(crate::DEFAULT_IPC_TCP)),
+            auth::token_path(socket_path.as_deref())?,
+        )
+        .await?;
 
-        let adapter = Self {
+        Ok(Self::with_listeners(
+            #[cfg(unix)]
+            listener,
+            #[cfg(unix)]
+            Some(socket_path),
+            tcp,
+        ))
+    }
+
+    /// listen for frontends on a socket passed by the service manager
+    /// and on `tcp` for frontends authenticated by a token
+    #[cfg(unix)]
+    pub async fn from_std(
+        listener: std::os::unix::net::UnixListener,
+        tcp: Option<SocketAddr>,
+    ) -> Result<Self, ListenerCreationError> {
+        let socket_path = listener
+            .local_addr()
+            .ok()
+            .and_then(|addr| addr.as_pathname().map(|p| p.to_path_buf()));
+        listener
+            .set_nonblocking(true)
+            .map_err(ListenerCreationError::Bind)?;
+        let listener = UnixListener::from_std(listener).map_err(ListenerCreationError::Bind)?;
+        let tcp = bind_tcp(tcp, auth::token_path(socket_path.as_deref())?).await?;
+        Ok(Self::with_listeners(listener, None, tcp))
+    }
+
+    fn with_listeners(
+        #[cfg(unix)] listener: UnixListener,
+        #[cfg(unix)] socket_path: Option<PathBuf>,
+        tcp: Option<(TcpListener, Arc<String>)>,
+    ) -> Self {
+        Self {
+            #[cfg(unix)]
             listener,
             #[cfg(unix)]
             socket_path,
+            tcp,
+            authenticating: FuturesUnordered::new(),
             line_streams: SelectAll::new(),
-            tx_streams: vec![],
-        };
+            connections: vec![],
+            next_connection: 0,
+        }
+    }
 
-        Ok(adapter)
+    fn add_connection(&mut self, lines: Lines, tx: Writer) {
+        let id = self.next_connection;
+        self.next_connection += 1;
+        self.line_streams.push(lines.map(move |l| (id, l)).boxed());
+        self.connections.push(Connection {
+            id,
+            tx,
+            enveloped: false,
+        });
     }
 
     pub async fn broadcast(&mut self, notify: FrontendEvent) {
-        // encode event
-        let mut json = serde_json::to_string(&notify).unwrap();
-        json.push('\n');
+        let legacy = notify.is_legacy();
+        let bare = envelope::encode(&ServiceLine::event(notify.clone(), false));
+        let enveloped = envelope::encode(&ServiceLine::event(notify, true));
 
         let mut keep = vec![];
         // TODO do simultaneously
-        for tx in self.tx_streams.iter_mut() {
-            // write len + payload
-            if tx.write(json.as_bytes()).await.is_err() {
-                keep.push(false);
-                continue;
-            }
-            keep.push(true);
+        for conn in self.connections.iter_mut() {
+            let json = match conn.enveloped {
+                true => &enveloped,
+                // frontends without envelope fail to parse newer events
+                false if legacy => &bare,
+                false => {
+                    keep.push(true);
+                    continue;
+                }
+            };
+            keep.push(conn.tx.write_all(json.as_bytes()).await.is_ok());
         }
 
         // could not find a better solution because async
         let mut keep = keep.into_iter();
-        self.tx_streams.retain(|_| keep.next().unwrap());
+        self.connections.retain(|_| keep.next().unwrap());
+    }
+
+    /// reply to the request received from `origin`
+    pub async fn reply(&mut self, origin: RequestOrigin, reply: FrontendReply) {
+        let Some(pos) = self
+            .connections
+            .iter()
+            .position(|c| c.id == origin.connection)
+        else {
+            return;
+        };
+        let json = envelope::encode(&ServiceLine::reply(origin.id, reply));
+        if self.connections[pos]
+            .tx
+            .write_all(json.as_bytes())
+            .await
+            .is_err()
+        {
+            self.connections.remove(pos);
+        }
+    }
+
+    fn request(&mut self, connection: usize, line: &str) -> Result<Request, IpcError> {
+        match RequestLine::parse(line)? {
+            RequestLine::Envelope { id, request, .. } => {
+                if let Some(conn) = self.connections.iter_mut().find(|c| c.id == connection) {
+                    conn.enveloped = true;
+                }
+                Ok((request, Some(RequestOrigin { connection, id })))
+            }
+            RequestLine::Bare(request) => Ok((request, None)),
+        }
     }
 }
 
+/// a request and where it was received from,
+/// if the frontend expects a reply
+type Request = (FrontendRequest, Option<RequestOrigin>);
+
 #[cfg(unix)]
 impl Drop for AsyncFrontendListener {
     fn drop(&mut self) {
-        log::debug!("remove socket: {:?}", self.socket_path);
-        let _ = std::fs::remove_file(&self.socket_path);
+        if let Some(socket_path) = &self.socket_path {
+            log::debug!("remove socket: {socket_path:?}");
+            let _ = std::fs::remove_file(socket_path);
+        }
+    }
+}
+
+/// bind the tcp listener and write the token connections have to authenticate with
+async fn bind_tcp(
+    addr: Option<SocketAddr>,
+    token_path: PathBuf,
+) -> Result<Option<(TcpListener, Arc<String>)>, ListenerCreationError> {
+    let Some(addr) = addr else {
+        return Ok(None);
+    };
+    let listener = match TcpListener::bind(addr).await {
+        Ok(ls) => ls,
+        // some other lan-mouse instance has bound the socket in the meantime
+        #[cfg(windows)]
+        Err(e) if e.kind() == ErrorKind::AddrInUse => {
+            return Err(ListenerCreationError::AlreadyRunning)
+        }
+        Err(e) => return Err(ListenerCreationError::Bind(e)),
+    };
+    let token = auth::create_token(&token_path).map_err(ListenerCreationError::Token)?;
+    log::info!("ipc listening on {addr}, token: {token_path:?}");
+    Ok(Some((listener, Arc::new(token))))
+}
+
+fn split<S: AsyncRead + AsyncWrite + Send + 'static>(stream: S) -> (Lines, Writer) {
+    let (rx, tx) = tokio::io::split(stream);
+    let lines = LinesStream::new(BufReader::new(rx).lines()).boxed();
+    (lines, Box::new(tx))
+}
+
+/// only connections of the user running the service are accepted
+#[cfg(unix)]
+fn is_owner(stream: &UnixStream) -> bool {
+    match stream.peer_cred() {
+        // SAFETY: geteuid never fails
+        Ok(cred) => cred.uid() == unsafe { libc::geteuid() },
+        Err(e) => {
+            log::warn!("could not get credentials of ipc peer: {e}");
+            false
+        }
+    }
+}
+
+/// wait for the token on a new tcp connection
+async fn authenticate(stream: TcpStream, token: Arc<String>) -> Option<(Lines, Writer)> {
+    let peer = stream.peer_addr().ok();
+    let (mut lines, tx) = split(stream);
+    match tokio::time::timeout(AUTH_TIMEOUT, lines.next()).await {
+        Ok(Some(Ok(line))) if auth::authenticate(&line, &token) => Some((lines, tx)),
+        _ => {
+            log::warn!("ipc connection from {peer:?} failed to authenticate");
+            None
+        }
     }
 }
 
 impl Stream for AsyncFrontendListener {
-    type Item = Result<FrontendRequest, IpcError>;
+    type Item = Result<Request, IpcError>;
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if let Poll::Ready(Some(Ok(l))) = self.line_streams.poll_next_unpin(cx) {
-            let request = serde_json::from_str(l.as_str()).map_err(|e| e.into());
+        if let Poll::Ready(Some((connection, Ok(l)))) = self.line_streams.poll_next_unpin(cx) {
+            let request = self.request(connection, l.as_str());
             return Poll::Ready(Some(request));
         }
         let mut sync = false;
+        #[cfg(unix)]
         while let Poll::Ready(Ok((stream, _))) = self.listener.poll_accept(cx) {
-            let (rx, tx) = tokio::io::split(stream);
-            let buf_reader = BufReader::new(rx);
-            let lines = buf_reader.lines();
-            let lines = LinesStream::new(lines);
-            self.line_streams.push(lines);
-            self.tx_streams.push(tx);
+            if !is_owner(&stream) {
+                log::warn!("rejecting ipc connection of a different user");
+                continue;
+            }
+            let (lines, tx) = split(stream);
+            self.add_connection(lines, tx);
             sync = true;
         }
+        if let Some((listener, token)) = &self.tcp {
+            let mut accepted = vec![];
+            while let Poll::Ready(Ok((stream, _))) = listener.poll_accept(cx) {
+                accepted.push(authenticate(stream, token.clone()).boxed());
+            }
+            self.authenticating.extend(accepted);
+        }
+        while let Poll::Ready(Some(authenticated)) = self.authenticating.poll_next_unpin(cx) {
+            if let Some((lines, tx)) = authenticated {
+                self.add_connection(lines, tx);
+                sync = true;
+            }
+        }
         if sync {
-            Poll::Ready(Some(Ok(FrontendRequest::Sync)))
+            Poll::Ready(Some(Ok((FrontendRequest::Sync, None))))
         } else {
             Poll::Pending
         }
@@ -7,6 +7,9 @@ use std::{
 };
 use thiserror::Error;
 
+/// version of the wire protocol, advertised to peers via mDNS
+pub const PROTOCOL_VERSION: u32 = 2;
+
 /// defines the maximum size an encoded event can take up
 /// this is currently the pointer motion event
 /// type: u8, time: u32, dx: f64, dy: f64
@@ -38,6 +41,10 @@ pub enum ProtoEvent {
     Ping,
     /// Response to [`ProtoEvent::Ping`]
     Pong,
+    /// the session of the sender was locked, request to lock as well
+    Lock,
+    /// the session of the sender was unlocked, request to unlock as well
+    Unlock,
 }
 
 impl Display for ProtoEvent {
@@ -49,6 +56,8 @@ impl Display for ProtoEvent {
             ProtoEvent::Input(e) => write!(f, "{e}"),
             ProtoEvent::Ping => write!(f, "ping"),
             ProtoEvent::Pong => write!(f, "pong"),
+            ProtoEvent::Lock => write!(f, "lock"),
+            ProtoEvent::Unlock => write!(f, "unlock"),
         }
     }
 }
@@ -67,6 +76,8 @@ pub enum EventType {
     Enter,
     Leave,
     Ack,
+    Lock,
+    Unlock,
 }
 
 impl ProtoEvent {
@@ -89,6 +100,8 @@ impl ProtoEvent {
             ProtoEvent::Enter(_) => EventType::Enter,
             ProtoEvent::Leave(_) => EventType::Leave,
             ProtoEvent::Ack(_) => EventType::Ack,
+            ProtoEvent::Lock => EventType::Lock,
+            ProtoEvent::Unlock => EventType::Unlock,
         }
     }
 }
@@ -143,6 +156,8 @@ impl TryFrom<[u8; MAX_EVENT_SIZE]> for ProtoEvent {
             EventType::Enter => Ok(Self::Enter(decode_u32(&mut buf)?)),
             EventType::Leave => Ok(Self::Leave(decode_u32(&mut buf)?)),
             EventType::Ack => Ok(Self::Ack(decode_u32(&mut buf)?)),
+            EventType::Lock => Ok(Self::Lock),
+            EventType::Unlock => Ok(Self::Unlock),
         }
     }
 }
@@ -204,6 +219,8 @@ impl From<ProtoEvent> for ([u8; MAX_EVENT_SIZE], usize) {
                 },
                 ProtoEvent::Ping => {}
                 ProtoEvent::Pong => {}
+                ProtoEvent::Lock => {}
+                ProtoEvent::Unlock => {}
                 ProtoEvent::Enter(serial) => encode_u32(buf, len, serial),
                 ProtoEvent::Leave(serial) => encode_u32(buf, len, serial),
                 ProtoEvent::Ack(serial) => encode_u32(buf, len, serial),
@@ -0,0 +1,150 @@
+//! interoperability with [Input Leap](https://github.com/input-leap/input-leap) and Barrier.
+//!
+//! `lan-mouse barrier client <server>` receives input from a Barrier server
+//! and emulates it, `lan-mouse barrier server <position>` sends the captured input
+//! to a Barrier client at the given position.
+
+mod client;
+mod keys;
+mod proto;
+mod server;
+
+use std::{
+    io,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+};
+
+use clap::{Args, Subcommand};
+use input_capture::{CaptureOptions, InputCapture, InputCaptureError};
+use input_emulation::{InputEmulation, InputEmulationError};
+use lan_mouse_ipc::Position;
+use thiserror::Error;
+
+use crate::config::Config;
+
+pub use proto::ProtocolError;
+
+/// port used by Barrier and Input Leap
+pub const DEFAULT_BARRIER_PORT: u16 = 24800;
+
+#[derive(Args, Clone, Debug, Eq, PartialEq)]
+pub struct BarrierArgs {
+    #[command(subcommand)]
+    mode: BarrierMode,
+}
+
+#[derive(Subcommand, Clone, Debug, Eq, PartialEq)]
+enum BarrierMode {
+    /// receive input from a Barrier / Input Leap server
+    Client {
+        /// address of the server: host[:port]
+        server: String,
+        /// screen name configured on the server (defaults to the hostname)
+        #[arg(long)]
+        name: Option<String>,
+        /// screen size reported to the server
+        #[arg(long, default_value = "1920x1080", value_parser = parse_screen_size)]
+        screen_size: (i16, i16),
+    },
+    /// send input to a Barrier / Input Leap client
+    Server {
+        /// position of the client: left | right | top | bottom
+        position: Position,
+        /// port to listen on
+        #[arg(long, default_value_t = DEFAULT_BARRIER_PORT)]
+        port: u16,
+        /// address to listen on
+        #[arg(long)]
+        bind_addr: Option<IpAddr>,
+        /// only accept the client with this screen name
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum BarrierError {
+    #[error("barrier connection: `{0}`")]
+    Io(#[from] io::Error),
+    #[error("barrier protocol: `{0}`")]
+    Protocol(#[from] ProtocolError),
+    #[error("unexpected message: {0}")]
+    Unexpected(String),
+    #[error("connection rejected: {0}")]
+    Rejected(String),
+    #[error("connection timed out")]
+    Timeout,
+    #[error(transparent)]
+    Capture(#[from] InputCaptureError),
+    #[error(transparent)]
+    Emulation(#[from] InputEmulationError),
+}
+
+pub async fn run(config: Config, args: BarrierArgs) -> Result<(), BarrierError> {
+    match args.mode {
+        BarrierMode::Client {
+            server,
+            name,
+            screen_size,
+        } => {
+            let name = match name {
+                Some(name) => name,
+                None => hostname::get()?.to_string_lossy().to_string(),
+            };
+            let server = match server.contains(':') {
+                true => server,
+                false => format!("{server}:{DEFAULT_BARRIER_PORT}"),
+            };
+            let backend = config.emulation_backend.map(|b| b.into());
+            let mut emulation = InputEmulation::new(backend)
+                .await
+                .map_err(InputEmulationError::from)?;
+            emulation.create(0).await;
+            let client = client::Client {
+                server,
+                name,
+                screen_size,
+            };
+            let res = client.run(&mut emulation).await;
+            emulation.terminate().await;
+            res
+        }
+        BarrierMode::Server {
+            position,
+            port,
+            bind_addr,
+            name,
+        } => {
+            let backend = config.capture_backend.map(|b| b.into());
+            let options = CaptureOptions {
+                evdev_hotkey: config.evdev_hotkey.clone(),
+                ..Default::default()
+            };
+            let mut capture = InputCapture::with_options(backend, options)
+                .await
+                .map_err(InputCaptureError::from)?;
+            let server = server::Server {
+                addr: SocketAddr::new(bind_addr.unwrap_or(Ipv6Addr::UNSPECIFIED.into()), port),
+                name,
+                position: match position {
+                    Position::Left => input_capture::Position::Left,
+                    Position::Right => input_capture::Position::Right,
+                    Position::Top => input_capture::Position::Top,
+                    Position::Bottom => input_capture::Position::Bottom,
+                },
+                release_bind: config.release_bind.clone(),
+            };
+            let res = server.run(&mut capture).await;
+            let _ = capture.terminate().await;
+            res
+        }
+    }
+}
+
+fn parse_screen_size(s: &str) -> Result<(i16, i16), String> {
+    let (w, h) = s
+        .split_once('x')
+        .ok_or_else(|| format!("expected <width>x<height>, got `{s}`"))?;
+    let parse = |v: &str| v.parse::<i16>().map_err(|e| format!("`{v}`: {e}"));
+    Ok((parse(w)?, parse(h)?))
+}
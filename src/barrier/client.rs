@@ -0,0 +1,363 @@
+//! lan-mouse as a Barrier client: receive input from a Barrier / Input Leap server
+//! and emulate it locally.
+
+use std::{collections::HashMap, time::Duration};
+
+use input_emulation::{EmulationHandle, InputEmulation, InputEmulationError};
+use input_event::{Event, KeyboardEvent, PointerEvent};
+use tokio::{
+    io::{AsyncWriteExt, BufReader, BufWriter},
+    net::TcpStream,
+    time,
+};
+
+use super::{
+    keys,
+    proto::{self, Message, PROTOCOL_MAJOR, PROTOCOL_MINOR},
+    BarrierError,
+};
+
+/// emulation handle of the server
+const HANDLE: EmulationHandle = 0;
+
+/// the server sends a keep alive every 3s
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(9);
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(3);
+
+pub(super) struct Client {
+    pub(super) server: String,
+    pub(super) name: String,
+    pub(super) screen_size: (i16, i16),
+}
+
+impl Client {
+    pub(super) async fn run(&self, emulation: &mut InputEmulation) -> Result<(), BarrierError> {
+        loop {
+            match self.connect(emulation).await {
+                Err(e @ (BarrierError::Rejected(_) | BarrierError::Emulation(_))) => return Err(e),
+                Err(e) => log::warn!("connection to {} lost: {e}", self.server),
+                Ok(()) => log::info!("{} closed the connection", self.server),
+            }
+            if let Err(e) = emulation.release_keys(HANDLE).await {
+                return Err(InputEmulationError::from(e).into());
+            }
+            time::sleep(RECONNECT_INTERVAL).await;
+        }
+    }
+
+    async fn connect(&self, emulation: &mut InputEmulation) -> Result<(), BarrierError> {
+        log::info!("connecting to barrier server {} ...", self.server);
+        let stream = TcpStream::connect(&self.server).await?;
+        let (reader, writer) = stream.into_split();
+        let (mut reader, mut writer) = (BufReader::new(reader), BufWriter::new(writer));
+
+        match proto::read_message(&mut reader).await? {
+            Message::Hello { major, minor } => {
+                log::info!("connected to {} (protocol {major}.{minor})", self.server)
+            }
+            msg => return Err(BarrierError::Unexpected(format!("{msg:?}"))),
+        }
+        let hello = Message::HelloBack {
+            major: PROTOCOL_MAJOR,
+            minor: PROTOCOL_MINOR,
+            name: self.name.clone(),
+        };
+        proto::write_message(&mut writer, &hello).await?;
+        writer.flush().await?;
+
+        let mut session = Session::new(self.screen_size);
+        loop {
+            let msg =
+                match time::timeout(KEEP_ALIVE_TIMEOUT, proto::read_message(&mut reader)).await {
+                    Ok(msg) => msg?,
+                    Err(_) => return Err(BarrierError::Timeout),
+                };
+            log::trace!("<- {msg:?}");
+            let (reply, events) = session.handle(msg)?;
+            if let Some(reply) = reply {
+                proto::write_message(&mut writer, &reply).await?;
+                writer.flush().await?;
+            }
+            for event in events {
+                emulation
+                    .consume(event, HANDLE)
+                    .await
+                    .map_err(InputEmulationError::from)?;
+            }
+            if session.closed {
+                return Ok(());
+            }
+            if session.left {
+                session.left = false;
+                emulation
+                    .release_keys(HANDLE)
+                    .await
+                    .map_err(InputEmulationError::from)?;
+            }
+        }
+    }
+}
+
+/// translates the messages of a connection into input events
+struct Session {
+    screen_size: (i16, i16),
+    /// cursor position as known by the server
+    cursor: (i16, i16),
+    mask: u16,
+    /// scancodes of pressed keys by the server's key button
+    pressed: HashMap<u16, u32>,
+    left: bool,
+    closed: bool,
+}
+
+impl Session {
+    fn new(screen_size: (i16, i16)) -> Self {
+        Self {
+            screen_size,
+            cursor: (screen_size.0 / 2, screen_size.1 / 2),
+            mask: 0,
+            pressed: HashMap::new(),
+            left: false,
+            closed: false,
+        }
+    }
+
+    /// handle a message, returns the reply and the input events to emulate
+    fn handle(&mut self, msg: Message) -> Result<(Option<Message>, Vec<Event>), BarrierError> {
+        let mut events = vec![];
+        let reply = match msg {
+            Message::QueryInfo => Some(Message::Info {
+                x: 0,
+                y: 0,
+                w: self.screen_size.0,
+                h: self.screen_size.1,
+                warp: 0,
+                mx: self.cursor.0,
+                my: self.cursor.1,
+            }),
+            Message::KeepAlive => Some(Message::KeepAlive),
+            Message::Enter { x, y, mask, .. } => {
+                log::info!("entered at ({x}, {y})");
+                // the pointer can not be warped, motion is relative to where it is
+                self.cursor = (x, y);
+                events.extend(self.update_mask(mask));
+                None
+            }
+            Message::Leave => {
+                log::info!("left");
+                self.pressed.clear();
+                self.left = true;
+                None
+            }
+            Message::MouseMove { x, y } => {
+                let (dx, dy) = (
+                    x as i32 - self.cursor.0 as i32,
+                    y as i32 - self.cursor.1 as i32,
+                );
+                self.cursor = (x, y);
+                if (dx, dy) != (0, 0) {
+                    events.push(motion(dx, dy));
+                }
+                None
+            }
+            Message::MouseRelMove { dx, dy } => {
+                events.push(motion(dx as i32, dy as i32));
+                None
+            }
+            Message::MouseDown(id) | Message::MouseUp(id) => {
+                let state = matches!(msg, Message::MouseDown(_)) as u32;
+                match keys::button(id) {
+                    Some(button) => events.push(Event::Pointer(PointerEvent::Button {
+                        time: 0,
+                        button,
+                        state,
+                    })),
+                    None => log::debug!("unsupported button {id}"),
+                }
+                None
+            }
+            Message::MouseWheel { dx, dy } => {
+                // barrier scrolls up for positive values
+                for (axis, value) in [(0, -(dy as i32)), (1, dx as i32)] {
+                    if value != 0 {
+                        events.push(Event::Pointer(PointerEvent::AxisDiscrete120 {
+                            axis,
+                            value,
+                        }));
+                    }
+                }
+                None
+            }
+            Message::KeyDown { id, mask, button } => {
+                match keys::scancode(id) {
+                    Some(key) => {
+                        self.pressed.insert(button, key as u32);
+                        events.push(key_event(key as u32, 1));
+                    }
+                    None => log::debug!("unsupported key id {id:#x}"),
+                }
+                events.extend(self.update_mask(mask));
+                None
+            }
+            Message::KeyUp { id, mask, button } => {
+                // the key id may differ from the one pressed (e.g. shift released first)
+                let key = self
+                    .pressed
+                    .remove(&button)
+                    .or_else(|| keys::scancode(id).map(|k| k as u32));
+                if let Some(key) = key {
+                    events.push(key_event(key, 0));
+                }
+                events.extend(self.update_mask(mask));
+                None
+            }
+            // key repeat is handled by the compositor
+            Message::KeyRepeat { .. } => None,
+            Message::Close => {
+                self.closed = true;
+                None
+            }
+            Message::Incompatible { major, minor } => {
+                return Err(BarrierError::Rejected(format!(
+                    "incompatible protocol version (server: {major}.{minor})"
+                )))
+            }
+            Message::Busy => {
+                return Err(BarrierError::Rejected(
+                    "a screen with this name is already connected".into(),
+                ))
+            }
+            Message::Unknown => {
+                return Err(BarrierError::Rejected(
+                    "the server does not know this screen name".into(),
+                ))
+            }
+            Message::Bad => return Err(BarrierError::Unexpected(format!("{msg:?}"))),
+            Message::NoOp
+            | Message::ResetOptions
+            | Message::InfoAck
+            | Message::Other(_)
+            | Message::Hello { .. }
+            | Message::HelloBack { .. }
+            | Message::Info { .. } => None,
+        };
+        Ok((reply, events))
+    }
+
+    fn update_mask(&mut self, mask: u16) -> Option<Event> {
+        if mask == self.mask {
+            return None;
+        }
+        self.mask = mask;
+        let (depressed, locked) = keys::xkb_modifiers(mask);
+        Some(Event::Keyboard(KeyboardEvent::Modifiers {
+            depressed,
+            latched: 0,
+            locked,
+            group: 0,
+        }))
+    }
+}
+
+fn motion(dx: i32, dy: i32) -> Event {
+    Event::Pointer(PointerEvent::Motion {
+        time: 0,
+        dx: dx as f64,
+        dy: dy as f64,
+    })
+}
+
+fn key_event(key: u32, state: u8) -> Event {
+    Event::Keyboard(KeyboardEvent::Key {
+        time: 0,
+        key,
+        state,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use input_event::scancode::Linux;
+
+    use super::*;
+
+    #[test]
+    fn answers_queries() {
+        let mut session = Session::new((1920, 1080));
+        let (reply, _) = session.handle(Message::QueryInfo).unwrap();
+        assert!(matches!(
+            reply,
+            Some(Message::Info {
+                w: 1920,
+                h: 1080,
+                mx: 960,
+                my: 540,
+                ..
+            })
+        ));
+        let (reply, _) = session.handle(Message::KeepAlive).unwrap();
+        assert_eq!(reply, Some(Message::KeepAlive));
+    }
+
+    #[test]
+    fn translates_input() {
+        let mut session = Session::new((1920, 1080));
+        let enter = Message::Enter {
+            x: 0,
+            y: 100,
+            seq: 1,
+            mask: 0,
+        };
+        assert_eq!(session.handle(enter).unwrap().1, vec![]);
+        let (_, events) = session.handle(Message::MouseMove { x: 5, y: 90 }).unwrap();
+        assert_eq!(events, vec![motion(5, -10)]);
+
+        // shift + a, shift released before a
+        let shift = Message::KeyDown {
+            id: 0xefe1,
+            mask: 0,
+            button: 50,
+        };
+        let (_, events) = session.handle(shift).unwrap();
+        assert_eq!(events, vec![key_event(Linux::KeyLeftShift as u32, 1)]);
+        let a = Message::KeyDown {
+            id: b'A' as u16,
+            mask: 1,
+            button: 38,
+        };
+        let (_, events) = session.handle(a).unwrap();
+        assert_eq!(events[0], key_event(Linux::KeyA as u32, 1));
+        assert!(matches!(
+            events[1],
+            Event::Keyboard(KeyboardEvent::Modifiers { depressed: 1, .. })
+        ));
+        let a_up = Message::KeyUp {
+            id: b'a' as u16,
+            mask: 0,
+            button: 38,
+        };
+        let (_, events) = session.handle(a_up).unwrap();
+        assert_eq!(events[0], key_event(Linux::KeyA as u32, 0));
+
+        let (_, events) = session
+            .handle(Message::MouseWheel { dx: 0, dy: 120 })
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![Event::Pointer(PointerEvent::AxisDiscrete120 {
+                axis: 0,
+                value: -120
+            })]
+        );
+    }
+
+    #[test]
+    fn rejected() {
+        let mut session = Session::new((1920, 1080));
+        assert!(matches!(
+            session.handle(Message::Unknown),
+            Err(BarrierError::Rejected(_))
+        ));
+    }
+}
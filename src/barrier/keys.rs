@@ -0,0 +1,252 @@
+//! translation between Barrier key ids, buttons and modifier masks
+//! and the linux scancodes / xkb modifiers used by lan-mouse.
+//!
+//! Barrier identifies keys by the symbol they produce (unicode code points
+//! and X keysyms with `0xff` replaced by `0xef`), so printable keys are
+//! translated assuming a US layout.
+
+use input_event::{scancode::Linux, BTN_BACK, BTN_FORWARD, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT};
+
+/// linux scancode, key id and key id with shift held
+const KEYS: &[(Linux, u16, Option<u16>)] = &[
+    (Linux::KeyA, b'a' as u16, Some(b'A' as u16)),
+    (Linux::KeyB, b'b' as u16, Some(b'B' as u16)),
+    (Linux::KeyC, b'c' as u16, Some(b'C' as u16)),
+    (Linux::KeyD, b'd' as u16, Some(b'D' as u16)),
+    (Linux::KeyE, b'e' as u16, Some(b'E' as u16)),
+    (Linux::KeyF, b'f' as u16, Some(b'F' as u16)),
+    (Linux::KeyG, b'g' as u16, Some(b'G' as u16)),
+    (Linux::KeyH, b'h' as u16, Some(b'H' as u16)),
+    (Linux::KeyI, b'i' as u16, Some(b'I' as u16)),
+    (Linux::KeyJ, b'j' as u16, Some(b'J' as u16)),
+    (Linux::KeyK, b'k' as u16, Some(b'K' as u16)),
+    (Linux::KeyL, b'l' as u16, Some(b'L' as u16)),
+    (Linux::KeyM, b'm' as u16, Some(b'M' as u16)),
+    (Linux::KeyN, b'n' as u16, Some(b'N' as u16)),
+    (Linux::KeyO, b'o' as u16, Some(b'O' as u16)),
+    (Linux::KeyP, b'p' as u16, Some(b'P' as u16)),
+    (Linux::KeyQ, b'q' as u16, Some(b'Q' as u16)),
+    (Linux::KeyR, b'r' as u16, Some(b'R' as u16)),
+    (Linux::KeyS, b's' as u16, Some(b'S' as u16)),
+    (Linux::KeyT, b't' as u16, Some(b'T' as u16)),
+    (Linux::KeyU, b'u' as u16, Some(b'U' as u16)),
+    (Linux::KeyV, b'v' as u16, Some(b'V' as u16)),
+    (Linux::KeyW, b'w' as u16, Some(b'W' as u16)),
+    (Linux::KeyX, b'x' as u16, Some(b'X' as u16)),
+    (Linux::KeyY, b'y' as u16, Some(b'Y' as u16)),
+    (Linux::KeyZ, b'z' as u16, Some(b'Z' as u16)),
+    (Linux::Key1, b'1' as u16, Some(b'!' as u16)),
+    (Linux::Key2, b'2' as u16, Some(b'@' as u16)),
+    (Linux::Key3, b'3' as u16, Some(b'#' as u16)),
+    (Linux::Key4, b'4' as u16, Some(b'$' as u16)),
+    (Linux::Key5, b'5' as u16, Some(b'%' as u16)),
+    (Linux::Key6, b'6' as u16, Some(b'^' as u16)),
+    (Linux::Key7, b'7' as u16, Some(b'&' as u16)),
+    (Linux::Key8, b'8' as u16, Some(b'*' as u16)),
+    (Linux::Key9, b'9' as u16, Some(b'(' as u16)),
+    (Linux::Key0, b'0' as u16, Some(b')' as u16)),
+    (Linux::KeyMinus, b'-' as u16, Some(b'_' as u16)),
+    (Linux::KeyEqual, b'=' as u16, Some(b'+' as u16)),
+    (Linux::KeyLeftbrace, b'[' as u16, Some(b'{' as u16)),
+    (Linux::KeyRightbrace, b']' as u16, Some(b'}' as u16)),
+    (Linux::KeyBackslash, b'\\' as u16, Some(b'|' as u16)),
+    (Linux::KeySemicolon, b';' as u16, Some(b':' as u16)),
+    (Linux::KeyApostrophe, b'\'' as u16, Some(b'"' as u16)),
+    (Linux::KeyGrave, b'`' as u16, Some(b'~' as u16)),
+    (Linux::KeyComma, b',' as u16, Some(b'<' as u16)),
+    (Linux::KeyDot, b'.' as u16, Some(b'>' as u16)),
+    (Linux::KeySlash, b'/' as u16, Some(b'?' as u16)),
+    (Linux::KeySpace, b' ' as u16, None),
+    (Linux::KeyEsc, 0xef1b, None),
+    (Linux::KeyBackspace, 0xef08, None),
+    (Linux::KeyTab, 0xef09, Some(0xee20)),
+    (Linux::KeyEnter, 0xef0d, None),
+    (Linux::KeyPause, 0xef13, None),
+    (Linux::KeyScrollLock, 0xef14, None),
+    (Linux::KeySysrq, 0xef61, None),
+    (Linux::KeyHome, 0xef50, None),
+    (Linux::KeyLeft, 0xef51, None),
+    (Linux::KeyUp, 0xef52, None),
+    (Linux::KeyRight, 0xef53, None),
+    (Linux::KeyDown, 0xef54, None),
+    (Linux::KeyPageup, 0xef55, None),
+    (Linux::KeyPagedown, 0xef56, None),
+    (Linux::KeyEnd, 0xef57, None),
+    (Linux::KeyInsert, 0xef63, None),
+    (Linux::KeyDelete, 0xefff, None),
+    (Linux::KeyCompose, 0xef67, None),
+    (Linux::KeyNumlock, 0xef7f, None),
+    (Linux::KeyKpEnter, 0xef8d, None),
+    (Linux::KeyKpAsterisk, 0xefaa, None),
+    (Linux::KeyKpplus, 0xefab, None),
+    (Linux::KeyKpMinus, 0xefad, None),
+    (Linux::KeyKpDot, 0xefae, None),
+    (Linux::KeyKpslash, 0xefaf, None),
+    (Linux::KeyKp0, 0xefb0, None),
+    (Linux::KeyKp1, 0xefb1, None),
+    (Linux::KeyKp2, 0xefb2, None),
+    (Linux::KeyKp3, 0xefb3, None),
+    (Linux::KeyKp4, 0xefb4, None),
+    (Linux::KeyKp5, 0xefb5, None),
+    (Linux::KeyKp6, 0xefb6, None),
+    (Linux::KeyKp7, 0xefb7, None),
+    (Linux::KeyKp8, 0xefb8, None),
+    (Linux::KeyKp9, 0xefb9, None),
+    (Linux::KeyKpequal, 0xefbd, None),
+    (Linux::KeyF1, 0xefbe, None),
+    (Linux::KeyF2, 0xefbf, None),
+    (Linux::KeyF3, 0xefc0, None),
+    (Linux::KeyF4, 0xefc1, None),
+    (Linux::KeyF5, 0xefc2, None),
+    (Linux::KeyF6, 0xefc3, None),
+    (Linux::KeyF7, 0xefc4, None),
+    (Linux::KeyF8, 0xefc5, None),
+    (Linux::KeyF9, 0xefc6, None),
+    (Linux::KeyF10, 0xefc7, None),
+    (Linux::KeyF11, 0xefc8, None),
+    (Linux::KeyF12, 0xefc9, None),
+    (Linux::KeyLeftShift, 0xefe1, None),
+    (Linux::KeyRightShift, 0xefe2, None),
+    (Linux::KeyLeftCtrl, 0xefe3, None),
+    (Linux::KeyRightCtrl, 0xefe4, None),
+    (Linux::KeyCapsLock, 0xefe5, None),
+    (Linux::KeyLeftAlt, 0xefe9, None),
+    (Linux::KeyRightalt, 0xefea, None),
+    (Linux::KeyLeftMeta, 0xefeb, None),
+    (Linux::KeyRightmeta, 0xefec, None),
+    (Linux::KeyMute, 0xe0ad, None),
+    (Linux::KeyVolumeDown, 0xe0ae, None),
+    (Linux::KeyVolumeUp, 0xe0af, None),
+];
+
+/// key ids only sent by some servers
+const ALIASES: &[(Linux, u16)] = &[
+    (Linux::KeyRightalt, 0xef7e),  /* AltGr */
+    (Linux::KeyLeftMeta, 0xefe7),  /* Meta_L */
+    (Linux::KeyRightmeta, 0xefe8), /* Meta_R */
+    (Linux::KeyKpDot, 0xef9f),     /* KP_Delete */
+    (Linux::KeyKp0, 0xef9e),       /* KP_Insert */
+    (Linux::KeyKp1, 0xef9c),       /* KP_End */
+    (Linux::KeyKp2, 0xef99),       /* KP_Down */
+    (Linux::KeyKp3, 0xef9b),       /* KP_Next */
+    (Linux::KeyKp4, 0xef96),       /* KP_Left */
+    (Linux::KeyKp5, 0xef9d),       /* KP_Begin */
+    (Linux::KeyKp6, 0xef98),       /* KP_Right */
+    (Linux::KeyKp7, 0xef95),       /* KP_Home */
+    (Linux::KeyKp8, 0xef97),       /* KP_Up */
+    (Linux::KeyKp9, 0xef9a),       /* KP_Prior */
+];
+
+/// scancode of the key producing the given key id
+pub(crate) fn scancode(id: u16) -> Option<Linux> {
+    KEYS.iter()
+        .find(|(_, k, s)| *k == id || *s == Some(id))
+        .map(|(l, _, _)| *l)
+        .or_else(|| ALIASES.iter().find(|(_, k)| *k == id).map(|(l, _)| *l))
+}
+
+/// key id of the given key, depending on shift being held
+pub(crate) fn key_id(key: Linux, shift: bool) -> Option<u16> {
+    KEYS.iter()
+        .find(|(l, _, _)| *l == key)
+        .map(|(_, k, s)| match s {
+            Some(s) if shift => *s,
+            _ => *k,
+        })
+}
+
+/// barrier button id of a linux button
+pub(crate) fn button_id(button: u32) -> Option<u8> {
+    match button {
+        BTN_LEFT => Some(1),
+        BTN_MIDDLE => Some(2),
+        BTN_RIGHT => Some(3),
+        BTN_BACK => Some(4),
+        BTN_FORWARD => Some(5),
+        _ => None,
+    }
+}
+
+/// linux button of a barrier button id
+pub(crate) fn button(id: u8) -> Option<u32> {
+    match id {
+        1 => Some(BTN_LEFT),
+        2 => Some(BTN_MIDDLE),
+        3 => Some(BTN_RIGHT),
+        4 => Some(BTN_BACK),
+        5 => Some(BTN_FORWARD),
+        _ => None,
+    }
+}
+
+/// barrier modifier mask and the xkb modifier mask of the default keymap
+const MODIFIERS: [(u16, u32); 6] = [
+    (0x0001, 1 << 0), /* shift */
+    (0x0002, 1 << 2), /* control */
+    (0x0004, 1 << 3), /* alt */
+    (0x0008, 1 << 3), /* meta */
+    (0x0010, 1 << 6), /* super */
+    (0x0020, 1 << 7), /* altgr */
+];
+/// locks: caps lock and num lock
+const LOCKS: [(u16, u32); 2] = [(0x1000, 1 << 1), (0x2000, 1 << 4)];
+
+pub(crate) const MASK_SHIFT: u16 = 0x0001;
+
+/// xkb depressed and locked modifiers of a barrier modifier mask
+pub(crate) fn xkb_modifiers(mask: u16) -> (u32, u32) {
+    let xkb = |mods: &[(u16, u32)]| {
+        mods.iter()
+            .filter(|(m, _)| mask & m != 0)
+            .fold(0, |acc, (_, x)| acc | x)
+    };
+    (xkb(&MODIFIERS), xkb(&LOCKS))
+}
+
+/// barrier modifier mask of xkb depressed and locked modifiers
+pub(crate) fn modifier_mask(depressed: u32, locked: u32) -> u16 {
+    let mask = |mods: &[(u16, u32)], xkb: u32| {
+        mods.iter()
+            .filter(|(_, x)| xkb & x != 0)
+            .fold(0, |acc, (m, _)| acc | m)
+    };
+    // alt and meta share a modifier
+    mask(&MODIFIERS, depressed) & !0x0008 | mask(&LOCKS, locked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_roundtrip() {
+        for &(key, id, shifted) in KEYS {
+            assert_eq!(scancode(id), Some(key));
+            assert_eq!(key_id(key, false), Some(id));
+            if let Some(shifted) = shifted {
+                assert_eq!(scancode(shifted), Some(key));
+                assert_eq!(key_id(key, true), Some(shifted));
+            }
+        }
+        assert_eq!(scancode(0xef7e), Some(Linux::KeyRightalt));
+        assert_eq!(key_id(Linux::KeySpace, true), Some(b' ' as u16));
+    }
+
+    #[test]
+    fn modifiers() {
+        assert_eq!(xkb_modifiers(0x1003), (1 | 4, 2));
+        assert_eq!(modifier_mask(1 | 4 | 8, 2 | 16), 0x3007);
+        for mask in [0x0001, 0x0002, 0x0004, 0x0010, 0x0020, 0x1000, 0x2000] {
+            let (depressed, locked) = xkb_modifiers(mask);
+            assert_eq!(modifier_mask(depressed, locked), mask);
+        }
+    }
+
+    #[test]
+    fn buttons() {
+        for id in 1..=5 {
+            assert_eq!(button(id).and_then(button_id), Some(id));
+        }
+        assert_eq!(button(6), None);
+    }
+}
@@ -0,0 +1,484 @@
+//! wire format of the Barrier / Input Leap protocol (version 1.6).
+//!
+//! Every message is prefixed with its length as a big endian `u32`
+//! and starts with a four letter code, except for the hello messages.
+//! Integers are big endian, strings are prefixed with their length (`u32`).
+
+use std::io;
+
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+pub(crate) const PROTOCOL_MAJOR: u16 = 1;
+pub(crate) const PROTOCOL_MINOR: u16 = 6;
+
+/// messages larger than this are rejected (same limit as Barrier)
+const MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+const HELLO: &[u8] = b"Barrier";
+/// older servers and clients
+const HELLO_SYNERGY: &[u8] = b"Synergy";
+
+#[derive(Debug, Error)]
+pub enum ProtocolError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("message too large ({0} bytes)")]
+    TooLarge(usize),
+    #[error("message `{0}` truncated")]
+    Truncated(String),
+    #[error("invalid hello message")]
+    InvalidHello,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Message {
+    /// server greeting: protocol version
+    Hello { major: u16, minor: u16 },
+    /// client greeting: protocol version and screen name
+    HelloBack {
+        major: u16,
+        minor: u16,
+        name: String,
+    },
+    /// CNOP
+    NoOp,
+    /// CBYE: close connection
+    Close,
+    /// CINN: cursor entered the screen at the given position
+    Enter { x: i16, y: i16, seq: u32, mask: u16 },
+    /// COUT: cursor left the screen
+    Leave,
+    /// CALV: keep alive, the client has to echo it
+    KeepAlive,
+    /// CROP: reset options
+    ResetOptions,
+    /// CIAK: screen info was received
+    InfoAck,
+    /// QINF: query screen info
+    QueryInfo,
+    /// DINF: screen info
+    Info {
+        x: i16,
+        y: i16,
+        w: i16,
+        h: i16,
+        warp: i16,
+        mx: i16,
+        my: i16,
+    },
+    /// DKDN
+    KeyDown { id: u16, mask: u16, button: u16 },
+    /// DKRP
+    KeyRepeat {
+        id: u16,
+        mask: u16,
+        count: u16,
+        button: u16,
+    },
+    /// DKUP
+    KeyUp { id: u16, mask: u16, button: u16 },
+    /// DMDN
+    MouseDown(u8),
+    /// DMUP
+    MouseUp(u8),
+    /// DMMV: absolute motion
+    MouseMove { x: i16, y: i16 },
+    /// DMRM: relative motion
+    MouseRelMove { dx: i16, dy: i16 },
+    /// DMWM: scroll, 120 per wheel step, positive values scroll up / right
+    MouseWheel { dx: i16, dy: i16 },
+    /// EICV: incompatible protocol version
+    Incompatible { major: u16, minor: u16 },
+    /// EBSY: a client with this name is already connected
+    Busy,
+    /// EUNK: unknown screen name
+    Unknown,
+    /// EBAD: protocol violation
+    Bad,
+    /// any other message (clipboard, options, screen saver, ...)
+    Other([u8; 4]),
+}
+
+impl Message {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut w = Writer(vec![]);
+        match self {
+            Message::Hello { major, minor } => {
+                w.bytes(HELLO);
+                w.u16(*major);
+                w.u16(*minor);
+            }
+            Message::HelloBack { major, minor, name } => {
+                w.bytes(HELLO);
+                w.u16(*major);
+                w.u16(*minor);
+                w.u32(name.len() as u32);
+                w.bytes(name.as_bytes());
+            }
+            Message::NoOp => w.bytes(b"CNOP"),
+            Message::Close => w.bytes(b"CBYE"),
+            Message::Enter { x, y, seq, mask } => {
+                w.bytes(b"CINN");
+                w.i16(*x);
+                w.i16(*y);
+                w.u32(*seq);
+                w.u16(*mask);
+            }
+            Message::Leave => w.bytes(b"COUT"),
+            Message::KeepAlive => w.bytes(b"CALV"),
+            Message::ResetOptions => w.bytes(b"CROP"),
+            Message::InfoAck => w.bytes(b"CIAK"),
+            Message::QueryInfo => w.bytes(b"QINF"),
+            Message::Info {
+                x,
+                y,
+                w: width,
+                h,
+                warp,
+                mx,
+                my,
+            } => {
+                w.bytes(b"DINF");
+                for v in [*x, *y, *width, *h, *warp, *mx, *my] {
+                    w.i16(v);
+                }
+            }
+            Message::KeyDown { id, mask, button } => {
+                w.bytes(b"DKDN");
+                for v in [*id, *mask, *button] {
+                    w.u16(v);
+                }
+            }
+            Message::KeyRepeat {
+                id,
+                mask,
+                count,
+                button,
+            } => {
+                w.bytes(b"DKRP");
+                for v in [*id, *mask, *count, *button] {
+                    w.u16(v);
+                }
+            }
+            Message::KeyUp { id, mask, button } => {
+                w.bytes(b"DKUP");
+                for v in [*id, *mask, *button] {
+                    w.u16(v);
+                }
+            }
+            Message::MouseDown(b) => {
+                w.bytes(b"DMDN");
+                w.0.push(*b);
+            }
+            Message::MouseUp(b) => {
+                w.bytes(b"DMUP");
+                w.0.push(*b);
+            }
+            Message::MouseMove { x, y } => {
+                w.bytes(b"DMMV");
+                w.i16(*x);
+                w.i16(*y);
+            }
+            Message::MouseRelMove { dx, dy } => {
+                w.bytes(b"DMRM");
+                w.i16(*dx);
+                w.i16(*dy);
+            }
+            Message::MouseWheel { dx, dy } => {
+                w.bytes(b"DMWM");
+                w.i16(*dx);
+                w.i16(*dy);
+            }
+            Message::Incompatible { major, minor } => {
+                w.bytes(b"EICV");
+                w.u16(*major);
+                w.u16(*minor);
+            }
+            Message::Busy => w.bytes(b"EBSY"),
+            Message::Unknown => w.bytes(b"EUNK"),
+            Message::Bad => w.bytes(b"EBAD"),
+            Message::Other(code) => w.bytes(code),
+        }
+        w.0
+    }
+
+    pub(crate) fn decode(buf: &[u8]) -> Result<Self, ProtocolError> {
+        if buf.starts_with(HELLO) || buf.starts_with(HELLO_SYNERGY) {
+            let mut r = Reader::new("hello", &buf[HELLO.len()..]);
+            let (major, minor) = (r.u16()?, r.u16()?);
+            if r.is_empty() {
+                return Ok(Message::Hello { major, minor });
+            }
+            let len = r.u32()? as usize;
+            let name = String::from_utf8_lossy(r.take(len)?).to_string();
+            return Ok(Message::HelloBack { major, minor, name });
+        }
+        let Some((code, payload)) = buf.split_first_chunk::<4>() else {
+            return Err(ProtocolError::Truncated(
+                String::from_utf8_lossy(buf).to_string(),
+            ));
+        };
+        let mut r = Reader::new(&String::from_utf8_lossy(code), payload);
+        let msg = match code {
+            b"CNOP" => Message::NoOp,
+            b"CBYE" => Message::Close,
+            b"CINN" => Message::Enter {
+                x: r.i16()?,
+                y: r.i16()?,
+                seq: r.u32()?,
+                mask: r.u16()?,
+            },
+            b"COUT" => Message::Leave,
+            b"CALV" => Message::KeepAlive,
+            b"CROP" => Message::ResetOptions,
+            b"CIAK" => Message::InfoAck,
+            b"QINF" => Message::QueryInfo,
+            b"DINF" => Message::Info {
+                x: r.i16()?,
+                y: r.i16()?,
+                w: r.i16()?,
+                h: r.i16()?,
+                warp: r.i16()?,
+                mx: r.i16()?,
+                my: r.i16()?,
+            },
+            // protocol 1.0 key events without button are not supported
+            b"DKDN" if payload.len() >= 6 => Message::KeyDown {
+                id: r.u16()?,
+                mask: r.u16()?,
+                button: r.u16()?,
+            },
+            b"DKRP" if payload.len() >= 8 => Message::KeyRepeat {
+                id: r.u16()?,
+                mask: r.u16()?,
+                count: r.u16()?,
+                button: r.u16()?,
+            },
+            b"DKUP" if payload.len() >= 6 => Message::KeyUp {
+                id: r.u16()?,
+                mask: r.u16()?,
+                button: r.u16()?,
+            },
+            b"DMDN" => Message::MouseDown(r.u8()?),
+            b"DMUP" => Message::MouseUp(r.u8()?),
+            b"DMMV" => Message::MouseMove {
+                x: r.i16()?,
+                y: r.i16()?,
+            },
+            b"DMRM" => Message::MouseRelMove {
+                dx: r.i16()?,
+                dy: r.i16()?,
+            },
+            b"DMWM" => Message::MouseWheel {
+                dx: r.i16()?,
+                dy: r.i16()?,
+            },
+            b"EICV" => Message::Incompatible {
+                major: r.u16()?,
+                minor: r.u16()?,
+            },
+            b"EBSY" => Message::Busy,
+            b"EUNK" => Message::Unknown,
+            b"EBAD" => Message::Bad,
+            code => Message::Other(*code),
+        };
+        Ok(msg)
+    }
+}
+
+/// read a length prefixed message
+pub(crate) async fn read_message(
+    reader: &mut (impl AsyncReadExt + Unpin),
+) -> Result<Message, ProtocolError> {
+    let len = reader.read_u32().await? as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(ProtocolError::TooLarge(len));
+    }
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).await?;
+    Message::decode(&buf)
+}
+
+/// write a length prefixed message
+pub(crate) async fn write_message(
+    writer: &mut (impl AsyncWriteExt + Unpin),
+    msg: &Message,
+) -> io::Result<()> {
+    let payload = msg.encode();
+    let mut buf = Vec::with_capacity(payload.len() + 4);
+    buf.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    buf.extend_from_slice(&payload);
+    writer.write_all(&buf).await
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn bytes(&mut self, b: &[u8]) {
+        self.0.extend_from_slice(b);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.bytes(&v.to_be_bytes());
+    }
+
+    fn i16(&mut self, v: i16) {
+        self.bytes(&v.to_be_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.bytes(&v.to_be_bytes());
+    }
+}
+
+struct Reader<'a> {
+    code: String,
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(code: &str, buf: &'a [u8]) -> Self {
+        Self {
+            code: code.to_string(),
+            buf,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ProtocolError> {
+        if self.buf.len() < n {
+            return Err(ProtocolError::Truncated(self.code.clone()));
+        }
+        let (v, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(v)
+    }
+
+    fn u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ProtocolError> {
+        Ok(u16::from_be_bytes(
+            self.take(2)?.try_into().expect("2 bytes"),
+        ))
+    }
+
+    fn i16(&mut self) -> Result<i16, ProtocolError> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, ProtocolError> {
+        Ok(u32::from_be_bytes(
+            self.take(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_wire_format() {
+        assert_eq!(
+            Message::Hello { major: 1, minor: 6 }.encode(),
+            b"Barrier\x00\x01\x00\x06"
+        );
+        assert_eq!(
+            Message::HelloBack {
+                major: 1,
+                minor: 6,
+                name: "iridium".into()
+            }
+            .encode(),
+            b"Barrier\x00\x01\x00\x06\x00\x00\x00\x07iridium"
+        );
+        assert_eq!(
+            Message::Enter {
+                x: 1,
+                y: -1,
+                seq: 2,
+                mask: 0x1001
+            }
+            .encode(),
+            b"CINN\x00\x01\xff\xff\x00\x00\x00\x02\x10\x01"
+        );
+        assert_eq!(
+            Message::KeyDown {
+                id: 0x61,
+                mask: 0,
+                button: 38
+            }
+            .encode(),
+            b"DKDN\x00\x61\x00\x00\x00\x26"
+        );
+        assert_eq!(Message::MouseDown(1).encode(), b"DMDN\x01");
+    }
+
+    #[test]
+    fn roundtrip() {
+        for msg in [
+            Message::Hello { major: 1, minor: 6 },
+            Message::HelloBack {
+                major: 1,
+                minor: 6,
+                name: "iridium".into(),
+            },
+            Message::Enter {
+                x: 10,
+                y: 20,
+                seq: 3,
+                mask: 1,
+            },
+            Message::Info {
+                x: 0,
+                y: 0,
+                w: 1920,
+                h: 1080,
+                warp: 0,
+                mx: 960,
+                my: 540,
+            },
+            Message::KeyRepeat {
+                id: 0xef08,
+                mask: 2,
+                count: 3,
+                button: 22,
+            },
+            Message::KeyUp {
+                id: 0x41,
+                mask: 1,
+                button: 38,
+            },
+            Message::MouseMove { x: -5, y: 7 },
+            Message::MouseWheel { dx: 0, dy: -120 },
+            Message::KeepAlive,
+            Message::Other(*b"DCLP"),
+        ] {
+            assert_eq!(Message::decode(&msg.encode()).unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated() {
+        assert!(matches!(
+            Message::decode(b"DMMV\x00\x01"),
+            Err(ProtocolError::Truncated(code)) if code == "DMMV"
+        ));
+        assert!(Message::decode(b"CA").is_err());
+    }
+
+    #[tokio::test]
+    async fn frames_messages() {
+        let mut buf = vec![];
+        write_message(&mut buf, &Message::KeepAlive).await.unwrap();
+        assert_eq!(buf, b"\x00\x00\x00\x04CALV");
+        let msg = read_message(&mut buf.as_slice()).await.unwrap();
+        assert_eq!(msg, Message::KeepAlive);
+    }
+}
@@ -0,0 +1,454 @@
+//! lan-mouse as a Barrier server: capture input and send it to a
+//! Barrier / Input Leap client next to this device.
+
+use std::{collections::HashMap, io, net::SocketAddr, time::Duration};
+
+use futures::StreamExt;
+use input_capture::{CaptureError, CaptureEvent, InputCapture, InputCaptureError, Position};
+use input_event::{scancode::Linux, Event, KeyboardEvent, PointerEvent};
+use tokio::{
+    io::{AsyncWriteExt, BufReader},
+    net::{tcp::OwnedWriteHalf, TcpListener, TcpStream},
+    sync::mpsc,
+    time::{self, Instant},
+};
+
+use super::{
+    keys,
+    proto::{self, Message, ProtocolError, PROTOCOL_MAJOR, PROTOCOL_MINOR},
+    BarrierError,
+};
+
+/// capture handle of the client
+const HANDLE: u64 = 0;
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(3);
+/// clients are disconnected after missing three keep alives
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(9);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// wayland scroll units per wheel step
+const UNITS_PER_STEP: f64 = 15.;
+
+pub(super) struct Server {
+    pub(super) addr: SocketAddr,
+    pub(super) name: Option<String>,
+    pub(super) position: Position,
+    pub(super) release_bind: Vec<Linux>,
+}
+
+impl Server {
+    pub(super) async fn run(&self, capture: &mut InputCapture) -> Result<(), BarrierError> {
+        let listener = TcpListener::bind(self.addr).await?;
+        log::info!("waiting for barrier clients on {}", self.addr);
+        loop {
+            let (stream, addr) = listener.accept().await?;
+            log::info!("barrier client connected from {addr}");
+            match self.serve(stream, capture).await {
+                Err(e @ BarrierError::Capture(_)) => return Err(e),
+                Err(e) => log::warn!("{addr}: {e}"),
+                Ok(()) => log::info!("{addr} disconnected"),
+            }
+            capture.destroy(HANDLE).await.map_err(capture_error)?;
+        }
+    }
+
+    async fn serve(
+        &self,
+        stream: TcpStream,
+        capture: &mut InputCapture,
+    ) -> Result<(), BarrierError> {
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let hello = Message::Hello {
+            major: PROTOCOL_MAJOR,
+            minor: PROTOCOL_MINOR,
+        };
+        send(&mut writer, &hello).await?;
+        let name = match handshake(proto::read_message(&mut reader)).await? {
+            Message::HelloBack { name, .. } => name,
+            msg => return Err(BarrierError::Unexpected(format!("{msg:?}"))),
+        };
+        if self.name.as_ref().is_some_and(|n| *n != name) {
+            send(&mut writer, &Message::Unknown).await?;
+            return Err(BarrierError::Rejected(format!("unknown screen `{name}`")));
+        }
+        send(&mut writer, &Message::QueryInfo).await?;
+        let screen = loop {
+            match handshake(proto::read_message(&mut reader)).await? {
+                Message::Info { x, y, w, h, .. } => break Screen { x, y, w, h },
+                Message::NoOp | Message::KeepAlive | Message::Other(_) => {}
+                msg => return Err(BarrierError::Unexpected(format!("{msg:?}"))),
+            }
+        };
+        send(&mut writer, &Message::InfoAck).await?;
+        log::info!(
+            "screen `{name}`: {}x{} at ({}, {})",
+            screen.w,
+            screen.h,
+            screen.x,
+            screen.y
+        );
+
+        capture
+            .create(HANDLE, self.position)
+            .await
+            .map_err(capture_error)?;
+
+        // reading is not cancel safe and happens in a separate task
+        let (tx, mut rx) = mpsc::channel(16);
+        let reader = tokio::task::spawn_local(async move {
+            loop {
+                let msg = proto::read_message(&mut reader).await;
+                let failed = msg.is_err();
+                if tx.send(msg).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let res = self
+            .session(
+                Remote::new(self.position, screen),
+                &mut rx,
+                &mut writer,
+                capture,
+            )
+            .await;
+        reader.abort();
+        res
+    }
+
+    async fn session(
+        &self,
+        mut remote: Remote,
+        rx: &mut mpsc::Receiver<Result<Message, ProtocolError>>,
+        writer: &mut OwnedWriteHalf,
+        capture: &mut InputCapture,
+    ) -> Result<(), BarrierError> {
+        let mut keep_alive = time::interval(KEEP_ALIVE_INTERVAL);
+        let mut last_seen = Instant::now();
+        loop {
+            tokio::select! {
+                msg = rx.recv() => {
+                    last_seen = Instant::now();
+                    let msg = msg.ok_or(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+                    match msg? {
+                        Message::Info { x, y, w, h, .. } => {
+                            remote.screen = Screen { x, y, w, h };
+                            send(writer, &Message::InfoAck).await?;
+                        }
+                        Message::Close => return Ok(()),
+                        msg => log::trace!("<- {msg:?}"),
+                    }
+                }
+                _ = keep_alive.tick() => {
+                    if last_seen.elapsed() > KEEP_ALIVE_TIMEOUT {
+                        capture.release().await.map_err(capture_error)?;
+                        return Err(BarrierError::Timeout);
+                    }
+                    send(writer, &Message::KeepAlive).await?;
+                }
+                event = capture.next() => {
+                    let (_, event) = event
+                        .ok_or(CaptureError::EndOfStream)
+                        .and_then(|e| e)
+                        .map_err(capture_error)?;
+                    let (messages, mut release) = remote.handle(event);
+                    if capture.keys_pressed(&self.release_bind) {
+                        release = true;
+                    }
+                    for msg in messages {
+                        send(writer, &msg).await?;
+                    }
+                    if release {
+                        capture.release().await.map_err(capture_error)?;
+                        if remote.release() {
+                            send(writer, &Message::Leave).await?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn capture_error(e: CaptureError) -> BarrierError {
+    InputCaptureError::from(e).into()
+}
+
+async fn handshake(
+    f: impl std::future::Future<Output = Result<Message, ProtocolError>>,
+) -> Result<Message, BarrierError> {
+    match time::timeout(HANDSHAKE_TIMEOUT, f).await {
+        Ok(msg) => Ok(msg?),
+        Err(_) => Err(BarrierError::Timeout),
+    }
+}
+
+async fn send(writer: &mut OwnedWriteHalf, msg: &Message) -> Result<(), BarrierError> {
+    log::trace!("-> {msg:?}");
+    proto::write_message(writer, msg).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// screen area of the client
+#[derive(Clone, Copy, Debug)]
+struct Screen {
+    x: i16,
+    y: i16,
+    w: i16,
+    h: i16,
+}
+
+impl Screen {
+    fn max(&self) -> (f64, f64) {
+        (
+            (self.x as f64 + self.w as f64 - 1.).max(self.x as f64),
+            (self.y as f64 + self.h as f64 - 1.).max(self.y as f64),
+        )
+    }
+}
+
+/// translates captured events into messages for the client
+struct Remote {
+    /// position of the client relative to this device
+    position: Position,
+    screen: Screen,
+    cursor: (f64, f64),
+    entered: bool,
+    seq: u32,
+    mask: u16,
+    /// key ids sent for pressed keys
+    pressed: HashMap<u32, u16>,
+}
+
+impl Remote {
+    fn new(position: Position, screen: Screen) -> Self {
+        Self {
+            position,
+            screen,
+            cursor: (0., 0.),
+            entered: false,
+            seq: 0,
+            mask: 0,
+            pressed: HashMap::new(),
+        }
+    }
+
+    /// returns the messages for the client and whether capture must be released
+    fn handle(&mut self, event: CaptureEvent) -> (Vec<Message>, bool) {
+        let event = match event {
+            CaptureEvent::Begin => return (vec![self.enter()], false),
+            CaptureEvent::Input(_) if !self.entered => return (vec![], false),
+            CaptureEvent::Input(e) => e,
+        };
+        let msg = match event {
+            Event::Pointer(PointerEvent::Motion { dx, dy, .. }) => {
+                let (x, y) = (self.cursor.0 + dx, self.cursor.1 + dy);
+                let (min, max) = (
+                    (self.screen.x as f64, self.screen.y as f64),
+                    self.screen.max(),
+                );
+                // moving back onto this device
+                let back = match self.position {
+                    Position::Left => x > max.0,
+                    Position::Right => x < min.0,
+                    Position::Top => y > max.1,
+                    Position::Bottom => y < min.1,
+                };
+                if back {
+                    return (vec![], true);
+                }
+                let previous = (self.cursor.0 as i16, self.cursor.1 as i16);
+                self.cursor = (x.clamp(min.0, max.0), y.clamp(min.1, max.1));
+                let (x, y) = (self.cursor.0 as i16, self.cursor.1 as i16);
+                if (x, y) == previous {
+                    return (vec![], false);
+                }
+                Message::MouseMove { x, y }
+            }
+            Event::Pointer(PointerEvent::Button { button, state, .. }) => {
+                let Some(id) = keys::button_id(button) else {
+                    return (vec![], false);
+                };
+                match state {
+                    0 => Message::MouseUp(id),
+                    _ => Message::MouseDown(id),
+                }
+            }
+            Event::Pointer(PointerEvent::AxisDiscrete120 { axis, value }) => wheel(axis, value),
+            Event::Pointer(PointerEvent::Axis { axis, value, .. }) => {
+                wheel(axis, (value * 120. / UNITS_PER_STEP) as i32)
+            }
+            Event::Keyboard(KeyboardEvent::Key { key, state, .. }) => {
+                let button = key as u16;
+                if state == 0 {
+                    let Some(id) = self.pressed.remove(&key) else {
+                        return (vec![], false);
+                    };
+                    Message::KeyUp {
+                        id,
+                        mask: self.mask,
+                        button,
+                    }
+                } else {
+                    let shift = self.mask & keys::MASK_SHIFT != 0;
+                    let Some(id) = Linux::try_from(key)
+                        .ok()
+                        .and_then(|k| keys::key_id(k, shift))
+                    else {
+                        log::debug!("no barrier key id for key {key}");
+                        return (vec![], false);
+                    };
+                    self.pressed.insert(key, id);
+                    Message::KeyDown {
+                        id,
+                        mask: self.mask,
+                        button,
+                    }
+                }
+            }
+            Event::Keyboard(KeyboardEvent::Modifiers {
+                depressed, locked, ..
+            }) => {
+                self.mask = keys::modifier_mask(depressed, locked);
+                return (vec![], false);
+            }
+        };
+        (vec![msg], false)
+    }
+
+    fn enter(&mut self) -> Message {
+        let (min, max) = (
+            (self.screen.x as f64, self.screen.y as f64),
+            self.screen.max(),
+        );
+        let center = ((min.0 + max.0) / 2., (min.1 + max.1) / 2.);
+        self.cursor = match self.position {
+            Position::Left => (max.0, center.1),
+            Position::Right => (min.0, center.1),
+            Position::Top => (center.0, max.1),
+            Position::Bottom => (center.0, min.1),
+        };
+        self.entered = true;
+        self.seq += 1;
+        Message::Enter {
+            x: self.cursor.0 as i16,
+            y: self.cursor.1 as i16,
+            seq: self.seq,
+            mask: self.mask,
+        }
+    }
+
+    /// capture was released, returns whether the client has to be left
+    fn release(&mut self) -> bool {
+        // the client releases its keys when it is left
+        self.pressed.clear();
+        std::mem::take(&mut self.entered)
+    }
+}
+
+fn wheel(axis: u8, value: i32) -> Message {
+    let value = value.clamp(i16::MIN as i32 + 1, i16::MAX as i32) as i16;
+    // barrier scrolls up for positive values
+    match axis {
+        0 => Message::MouseWheel { dx: 0, dy: -value },
+        _ => Message::MouseWheel { dx: value, dy: 0 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Screen = Screen {
+        x: 0,
+        y: 0,
+        w: 1000,
+        h: 500,
+    };
+
+    fn input(event: Event) -> CaptureEvent {
+        CaptureEvent::Input(event)
+    }
+
+    fn motion(dx: f64, dy: f64) -> CaptureEvent {
+        input(Event::Pointer(PointerEvent::Motion { time: 0, dx, dy }))
+    }
+
+    #[test]
+    fn enters_and_leaves_at_edge() {
+        let mut remote = Remote::new(Position::Right, SCREEN);
+        assert_eq!(remote.handle(motion(1., 0.)), (vec![], false));
+        let (msgs, _) = remote.handle(CaptureEvent::Begin);
+        assert_eq!(
+            msgs,
+            vec![Message::Enter {
+                x: 0,
+                y: 249,
+                seq: 1,
+                mask: 0
+            }]
+        );
+        assert_eq!(
+            remote.handle(motion(10., 1000.)),
+            (vec![Message::MouseMove { x: 10, y: 499 }], false)
+        );
+        assert_eq!(remote.handle(motion(-11., 0.)), (vec![], true));
+        assert!(remote.release());
+        assert!(!remote.release());
+    }
+
+    #[test]
+    fn translates_keys() {
+        let mut remote = Remote::new(Position::Left, SCREEN);
+        remote.handle(CaptureEvent::Begin);
+        let key = |key: Linux, state| {
+            input(Event::Keyboard(KeyboardEvent::Key {
+                time: 0,
+                key: key as u32,
+                state,
+            }))
+        };
+        remote.handle(key(Linux::KeyLeftShift, 1));
+        remote.handle(input(Event::Keyboard(KeyboardEvent::Modifiers {
+            depressed: 1,
+            latched: 0,
+            locked: 0,
+            group: 0,
+        })));
+        assert_eq!(
+            remote.handle(key(Linux::KeyA, 1)).0,
+            vec![Message::KeyDown {
+                id: b'A' as u16,
+                mask: 1,
+                button: Linux::KeyA as u16
+            }]
+        );
+        remote.handle(input(Event::Keyboard(KeyboardEvent::Modifiers {
+            depressed: 0,
+            latched: 0,
+            locked: 0,
+            group: 0,
+        })));
+        // released with the id it was pressed with
+        assert_eq!(
+            remote.handle(key(Linux::KeyA, 0)).0,
+            vec![Message::KeyUp {
+                id: b'A' as u16,
+                mask: 0,
+                button: Linux::KeyA as u16
+            }]
+        );
+        let scroll = input(Event::Pointer(PointerEvent::AxisDiscrete120 {
+            axis: 0,
+            value: 120,
+        }));
+        assert_eq!(
+            remote.handle(scroll).0,
+            vec![Message::MouseWheel { dx: 0, dy: -120 }]
+        );
+    }
+}
@@ -1,32 +1,69 @@
-use crate::config::Config;
+use std::io;
+
+use crate::{config::Config, record::Recorder};
 use futures::StreamExt;
 use input_capture::{self, CaptureError, CaptureEvent, InputCapture, InputCaptureError, Position};
 use input_event::{Event, KeyboardEvent};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CaptureTestError {
+    #[error(transparent)]
+    Capture(#[from] InputCaptureError),
+    #[error("could not write recording: `{0}`")]
+    Record(#[from] io::Error),
+}
 
-pub async fn run(config: Config) -> Result<(), InputCaptureError> {
+impl From<CaptureError> for CaptureTestError {
+    fn from(e: CaptureError) -> Self {
+        Self::Capture(e.into())
+    }
+}
+
+pub async fn run(config: Config) -> Result<(), CaptureTestError> {
     log::info!("running input capture test");
+    let mut recorder = match &config.record {
+        Some(path) => {
+            log::info!("recording to {}", path.display());
+            Some(Recorder::create(path)?)
+        }
+        None => None,
+    };
     log::info!("creating input capture");
     let backend = config.capture_backend.map(|b| b.into());
     loop {
-        let mut input_capture = InputCapture::new(backend).await?;
+        let mut input_capture = InputCapture::new(backend)
+            .await
+            .map_err(InputCaptureError::from)?;
         log::info!("creating clients");
         input_capture.create(0, Position::Left).await?;
         input_capture.create(1, Position::Right).await?;
         input_capture.create(2, Position::Top).await?;
         input_capture.create(3, Position::Bottom).await?;
-        if let Err(e) = do_capture(&mut input_capture).await {
-            log::warn!("{e} - recreating capture");
+        match do_capture(&mut input_capture, recorder.as_mut()).await {
+            Err(CaptureTestError::Capture(e)) => log::warn!("{e} - recreating capture"),
+            Err(e) => {
+                let _ = input_capture.terminate().await;
+                return Err(e);
+            }
+            Ok(()) => {}
         }
         let _ = input_capture.terminate().await;
     }
 }
 
-async fn do_capture(input_capture: &mut InputCapture) -> Result<(), CaptureError> {
+async fn do_capture(
+    input_capture: &mut InputCapture,
+    mut recorder: Option<&mut Recorder>,
+) -> Result<(), CaptureTestError> {
     loop {
         let (client, event) = input_capture
             .next()
             .await
             .ok_or(CaptureError::EndOfStream)??;
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.write(client, event)?;
+        }
         let pos = match client {
             0 => Position::Left,
             1 => Position::Right,
@@ -1,14 +1,21 @@
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
 use std::env::{self, VarError};
 use std::fmt::Display;
 use std::fs;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use std::{collections::HashSet, io};
 use thiserror::Error;
 use toml;
 
-use lan_mouse_ipc::{Position, DEFAULT_PORT};
+use crate::{barrier::BarrierArgs, simulate_peer::SimulatePeerArgs};
+use lan_mouse_cli::CtlArgs;
+use lan_mouse_ipc::{
+    EventClass, EventFilter, Hooks, Liveness, LivenessAction, MacAddress, Position, Transport,
+    DEFAULT_PORT,
+};
 
 use input_event::scancode::{
     self,
@@ -22,6 +29,21 @@ pub struct ConfigToml {
     pub port: Option<u16>,
     pub frontend: Option<Frontend>,
     pub release_bind: Option<Vec<scancode::Linux>>,
+    pub evdev_hotkey: Option<Vec<scancode::Linux>>,
+    pub evdev_screen_size: Option<(u32, u32)>,
+    pub notifications: Option<bool>,
+    pub discovery: Option<bool>,
+    pub dbus: Option<bool>,
+    pub ipc_tcp: Option<SocketAddr>,
+    pub ipc_socket: Option<PathBuf>,
+    pub bind_addr: Option<IpAddr>,
+    pub interface: Option<String>,
+    pub refuse_offline_clients: Option<bool>,
+    pub sync_lock: Option<bool>,
+    pub sync_unlock: Option<bool>,
+    pub hook_shell: Option<Vec<String>>,
+    pub hook_timeout: Option<u64>,
+    pub hook_kill_policy: Option<HookKillPolicy>,
     pub left: Option<TomlClient>,
     pub right: Option<TomlClient>,
     pub top: Option<TomlClient>,
@@ -37,6 +59,18 @@ pub struct TomlClient {
     pub port: Option<u16>,
     pub activate_on_startup: Option<bool>,
     pub enter_hook: Option<String>,
+    pub leave_hook: Option<String>,
+    pub online_hook: Option<String>,
+    pub offline_hook: Option<String>,
+    pub receive_start_hook: Option<String>,
+    pub receive_stop_hook: Option<String>,
+    pub transport: Option<Transport>,
+    pub ping_interval: Option<u64>,
+    pub max_missed_pings: Option<u32>,
+    pub liveness_action: Option<LivenessAction>,
+    pub mac: Option<MacAddress>,
+    pub send_events: Option<Vec<EventClass>>,
+    pub receive_events: Option<Vec<EventClass>>,
 }
 
 impl ConfigToml {
@@ -74,6 +108,18 @@ struct CliArgs {
     #[arg(long)]
     test_emulation: bool,
 
+    /// record the events captured in the input capture test to a file
+    #[arg(long, requires = "test_capture")]
+    record: Option<PathBuf>,
+
+    /// replay a recording through input emulation
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// replay speed factor (0 replays without delays)
+    #[arg(long, default_value_t = 1.0, requires = "replay")]
+    replay_speed: f64,
+
     /// capture backend override
     #[arg(long)]
     capture_backend: Option<CaptureBackend>,
@@ -81,6 +127,64 @@ struct CliArgs {
     /// emulation backend override
     #[arg(long)]
     emulation_backend: Option<EmulationBackend>,
+
+    /// show desktop notifications when a device becomes unreachable
+    /// or the service runs into errors
+    #[arg(long)]
+    notifications: bool,
+
+    /// advertise this device and discover other devices via mDNS
+    #[arg(long)]
+    discovery: bool,
+
+    /// do not provide the de.feschber.LanMouse service on the session bus
+    #[arg(long)]
+    no_dbus: bool,
+
+    /// additionally accept frontends on the given tcp address,
+    /// authenticated by a token only readable by the current user
+    #[arg(long)]
+    ipc_tcp: Option<SocketAddr>,
+
+    /// location of the socket frontends connect to,
+    /// allows running multiple instances of lan-mouse
+    #[arg(long, global = true)]
+    socket: Option<PathBuf>,
+
+    /// address to listen on for events (defaults to all addresses, ipv4 and ipv6)
+    #[arg(long)]
+    bind_addr: Option<IpAddr>,
+
+    /// restrict lan-mouse to the given network interface
+    #[arg(long)]
+    interface: Option<String>,
+
+    /// do not move the pointer to clients that stopped responding to heartbeats
+    #[arg(long)]
+    refuse_offline_clients: bool,
+
+    /// lock and unlock connected devices together with this one
+    /// and keep this device from going idle while it receives input
+    #[arg(long)]
+    sync_lock: bool,
+
+    /// also unlock the session when the device currently sending input unlocks
+    /// (insecure: anyone able to send packets on the network can unlock this device)
+    #[arg(long)]
+    sync_unlock: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    /// control a running lan-mouse service
+    Ctl(CtlArgs),
+    /// run a simulated peer on loopback for testing
+    SimulatePeer(SimulatePeerArgs),
+    /// interoperate with Input Leap / Barrier
+    Barrier(BarrierArgs),
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ValueEnum)]
@@ -95,6 +199,8 @@ pub enum CaptureBackend {
     Windows,
     #[cfg(target_os = "macos")]
     MacOs,
+    #[cfg(all(target_os = "linux", feature = "evdev"))]
+    Evdev,
     Dummy,
 }
 
@@ -111,6 +217,8 @@ impl Display for CaptureBackend {
             CaptureBackend::Windows => write!(f, "windows"),
             #[cfg(target_os = "macos")]
             CaptureBackend::MacOs => write!(f, "MacOS"),
+            #[cfg(all(target_os = "linux", feature = "evdev"))]
+            CaptureBackend::Evdev => write!(f, "evdev"),
             CaptureBackend::Dummy => write!(f, "dummy"),
         }
     }
@@ -129,6 +237,8 @@ impl From<CaptureBackend> for input_capture::Backend {
             CaptureBackend::Windows => Self::Windows,
             #[cfg(target_os = "macos")]
             CaptureBackend::MacOs => Self::MacOs,
+            #[cfg(all(target_os = "linux", feature = "evdev"))]
+            CaptureBackend::Evdev => Self::Evdev,
             CaptureBackend::Dummy => Self::Dummy,
         }
     }
@@ -144,6 +254,8 @@ pub enum EmulationBackend {
     Xdp,
     #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
     X11,
+    #[cfg(all(target_os = "linux", feature = "uinput"))]
+    Uinput,
     #[cfg(windows)]
     Windows,
     #[cfg(target_os = "macos")]
@@ -162,6 +274,8 @@ impl From<EmulationBackend> for input_emulation::Backend {
             EmulationBackend::Xdp => Self::Xdp,
             #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
             EmulationBackend::X11 => Self::X11,
+            #[cfg(all(target_os = "linux", feature = "uinput"))]
+            EmulationBackend::Uinput => Self::Uinput,
             #[cfg(windows)]
             EmulationBackend::Windows => Self::Windows,
             #[cfg(target_os = "macos")]
@@ -182,6 +296,8 @@ impl Display for EmulationBackend {
             EmulationBackend::Xdp => write!(f, "xdg-desktop-portal"),
             #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
             EmulationBackend::X11 => write!(f, "X11"),
+            #[cfg(all(target_os = "linux", feature = "uinput"))]
+            EmulationBackend::Uinput => write!(f, "uinput"),
             #[cfg(windows)]
             EmulationBackend::Windows => write!(f, "windows"),
             #[cfg(target_os = "macos")]
@@ -191,6 +307,19 @@ impl Display for EmulationBackend {
     }
 }
 
+/// what to do with hook commands that exceed `hook_timeout`
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookKillPolicy {
+    /// send SIGTERM and SIGKILL if the command does not exit shortly after
+    #[default]
+    Terminate,
+    /// send SIGKILL
+    Kill,
+    /// keep the command running
+    Detach,
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize, ValueEnum)]
 pub enum Frontend {
     Gtk,
@@ -216,8 +345,36 @@ pub struct Config {
     pub clients: Vec<(TomlClient, Position)>,
     pub daemon: bool,
     pub release_bind: Vec<scancode::Linux>,
+    /// keys entering the next client with the evdev capture backend
+    pub evdev_hotkey: Vec<scancode::Linux>,
+    /// size of the virtual screen of the evdev capture backend
+    pub evdev_screen_size: Option<(u32, u32)>,
+    pub notifications: bool,
+    pub discovery: bool,
+    /// file the key identifying this device is kept in, next to the config file
+    pub device_key: PathBuf,
+    /// provide the `de.feschber.LanMouse` d-bus service
+    pub dbus: bool,
+    /// address frontends authenticated by a token can connect to
+    pub ipc_tcp: Option<SocketAddr>,
+    /// socket frontends connect to, defaults to `$XDG_RUNTIME_DIR/lan-mouse-socket.sock`
+    pub ipc_socket: Option<PathBuf>,
+    pub bind_addr: Option<IpAddr>,
+    pub interface: Option<String>,
+    pub refuse_offline_clients: bool,
+    pub sync_lock: bool,
+    /// honour unlock requests of the peer sending input
+    pub sync_unlock: bool,
+    /// shell and arguments hook commands are passed to
+    pub hook_shell: Vec<String>,
+    pub hook_timeout: Option<Duration>,
+    pub hook_kill_policy: HookKillPolicy,
     pub test_capture: bool,
     pub test_emulation: bool,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub replay_speed: f64,
+    pub command: Option<Command>,
 }
 
 pub struct ConfigClient {
@@ -226,7 +383,12 @@ pub struct ConfigClient {
     pub port: u16,
     pub pos: Position,
     pub active: bool,
-    pub enter_hook: Option<String>,
+    pub hooks: Hooks,
+    pub transport: Transport,
+    pub liveness: Liveness,
+    pub mac: Option<MacAddress>,
+    pub send_filter: EventFilter,
+    pub receive_filter: EventFilter,
 }
 
 #[derive(Debug, Error)]
@@ -239,12 +401,23 @@ pub enum ConfigError {
     Var(#[from] VarError),
 }
 
+const DEFAULT_HOOK_SHELL: [&str; 2] = ["sh", "-c"];
+
 const DEFAULT_RELEASE_KEYS: [scancode::Linux; 4] =
     [KeyLeftCtrl, KeyLeftShift, KeyLeftMeta, KeyLeftAlt];
 
 impl Config {
     pub fn new() -> Result<Self, ConfigError> {
-        let args = CliArgs::parse();
+        Self::from_args(env::args_os())
+    }
+
+    /// configuration from the given command line arguments
+    pub(crate) fn from_args<I, T>(args: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let args = CliArgs::parse_from(args);
         let config_file = "config.toml";
         #[cfg(unix)]
         let config_path = {
@@ -262,6 +435,7 @@ impl Config {
 
         // --config <file> overrules default location
         let config_path = args.config.unwrap_or(config_path);
+        let device_key = Path::new(&config_path).with_file_name("device_key");
 
         let config_toml = match ConfigToml::new(config_path.as_str()) {
             Err(e) => {
@@ -287,6 +461,13 @@ impl Config {
             .and_then(|c| c.release_bind.clone())
             .unwrap_or(Vec::from_iter(DEFAULT_RELEASE_KEYS.iter().cloned()));
 
+        let evdev_hotkey = config_toml
+            .as_ref()
+            .and_then(|c| c.evdev_hotkey.clone())
+            .unwrap_or_default();
+
+        let evdev_screen_size = config_toml.as_ref().and_then(|c| c.evdev_screen_size);
+
         let capture_backend = args
             .capture_backend
             .or(config_toml.as_ref().and_then(|c| c.capture_backend));
@@ -295,6 +476,70 @@ impl Config {
             .emulation_backend
             .or(config_toml.as_ref().and_then(|c| c.emulation_backend));
 
+        let notifications = args.notifications
+            || config_toml
+                .as_ref()
+                .and_then(|c| c.notifications)
+                .unwrap_or(false);
+
+        let discovery = args.discovery
+            || config_toml
+                .as_ref()
+                .and_then(|c| c.discovery)
+                .unwrap_or(false);
+
+        let dbus = !args.no_dbus && config_toml.as_ref().and_then(|c| c.dbus).unwrap_or(true);
+
+        let ipc_tcp = args
+            .ipc_tcp
+            .or(config_toml.as_ref().and_then(|c| c.ipc_tcp));
+
+        let ipc_socket = args
+            .socket
+            .or(config_toml.as_ref().and_then(|c| c.ipc_socket.clone()));
+
+        let bind_addr = args
+            .bind_addr
+            .or(config_toml.as_ref().and_then(|c| c.bind_addr));
+
+        let interface = args
+            .interface
+            .or(config_toml.as_ref().and_then(|c| c.interface.clone()));
+
+        let refuse_offline_clients = args.refuse_offline_clients
+            || config_toml
+                .as_ref()
+                .and_then(|c| c.refuse_offline_clients)
+                .unwrap_or(false);
+
+        let sync_lock = args.sync_lock
+            || config_toml
+                .as_ref()
+                .and_then(|c| c.sync_lock)
+                .unwrap_or(false);
+
+        let sync_unlock = args.sync_unlock
+            || config_toml
+                .as_ref()
+                .and_then(|c| c.sync_unlock)
+                .unwrap_or(false);
+
+        let hook_shell = config_toml
+            .as_ref()
+            .and_then(|c| c.hook_shell.clone())
+            .filter(|shell| !shell.is_empty())
+            .unwrap_or_else(|| DEFAULT_HOOK_SHELL.map(String::from).to_vec());
+
+        let hook_timeout = config_toml
+            .as_ref()
+            .and_then(|c| c.hook_timeout)
+            .map(Duration::from_secs);
+
+        let hook_kill_policy = config_toml
+            .as_ref()
+            .and_then(|c| c.hook_kill_policy)
+            .unwrap_or_default();
+
         let mut clients: Vec<(TomlClient, Position)> = vec![];
 
         if let Some(config_toml) = config_toml {
@@ -315,6 +560,10 @@ impl Config {
         let daemon = args.daemon;
         let test_capture = args.test_capture;
         let test_emulation = args.test_emulation;
+        let record = args.record;
+        let replay = args.replay;
+        let replay_speed = args.replay_speed;
+        let command = args.command;
 
         Ok(Config {
             capture_backend,
@@ -324,8 +573,28 @@ impl Config {
             clients,
             port,
             release_bind,
+            evdev_hotkey,
+            evdev_screen_size,
+            notifications,
+            discovery,
+            device_key,
+            dbus,
+            ipc_tcp,
+            ipc_socket,
+            bind_addr,
+            interface,
+            refuse_offline_clients,
+            sync_lock,
+            sync_unlock,
+            hook_shell,
+            hook_timeout,
+            hook_kill_policy,
             test_capture,
             test_emulation,
+            record,
+            replay,
+            replay_speed,
+            command,
         })
     }
 
@@ -344,14 +613,43 @@ impl Config {
                     None => c.host_name.clone(),
                 };
                 let active = c.activate_on_startup.unwrap_or(false);
-                let enter_hook = c.enter_hook.clone();
+                let hooks = Hooks {
+                    enter: c.enter_hook.clone(),
+                    leave: c.leave_hook.clone(),
+                    online: c.online_hook.clone(),
+                    offline: c.offline_hook.clone(),
+                    receive_start: c.receive_start_hook.clone(),
+                    receive_stop: c.receive_stop_hook.clone(),
+                };
+                let transport = c.transport.unwrap_or_default();
+                let default_liveness = Liveness::default();
+                let liveness = Liveness {
+                    ping_interval_ms: c.ping_interval.unwrap_or(default_liveness.ping_interval_ms),
+                    max_missed_pings: c
+                        .max_missed_pings
+                        .unwrap_or(default_liveness.max_missed_pings),
+                    action: c.liveness_action.unwrap_or(default_liveness.action),
+                };
                 ConfigClient {
                     ips,
                     hostname,
                     port,
                     pos: *pos,
                     active,
-                    enter_hook,
+                    hooks,
+                    transport,
+                    liveness,
+                    mac: c.mac,
+                    send_filter: c
+                        .send_events
+                        .as_ref()
+                        .map(|e| e.iter().copied().collect())
+                        .unwrap_or_default(),
+                    receive_filter: c
+                        .receive_events
+                        .as_ref()
+                        .map(|e| e.iter().copied().collect())
+                        .unwrap_or_default(),
                 }
             })
             .collect()
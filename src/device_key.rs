@@ -0,0 +1,77 @@
+//! key identifying this device.
+//!
+//! A random key is created on the first start and kept next to the config file.
+//! Its fingerprint is advertised via mDNS discovery, so a discovered device
+//! can be told apart from another one announcing the same hostname.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use sha1::{Digest, Sha1};
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+const KEY_LEN: usize = 32;
+
+/// read the key at `path` or create a new one, readable only by the current user
+pub fn load_or_create(path: &Path) -> io::Result<Vec<u8>> {
+    match fs::read(path) {
+        Ok(key) if key.len() == KEY_LEN => return Ok(key),
+        Ok(_) => log::warn!("{}: invalid key, creating a new one", path.display()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let mut key = vec![0u8; KEY_LEN];
+    getrandom::getrandom(&mut key)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let _ = fs::remove_file(path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path)?.write_all(&key)?;
+    Ok(key)
+}
+
+/// fingerprint of the key, e.g. `3f:a2:...:9c`
+pub fn fingerprint(key: &[u8]) -> String {
+    Sha1::digest(key)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let dir = std::env::temp_dir().join(format!("lan-mouse-key-{}", std::process::id()));
+        let path = dir.join("device_key");
+        let key = load_or_create(&path).unwrap();
+        assert_eq!(key.len(), KEY_LEN);
+        assert_eq!(load_or_create(&path).unwrap(), key);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let fingerprint = fingerprint(&key);
+        assert_eq!(fingerprint.len(), 20 * 3 - 1);
+        assert_ne!(fingerprint, super::fingerprint(&[0; KEY_LEN]));
+
+        fs::write(&path, b"truncated").unwrap();
+        assert_ne!(load_or_create(&path).unwrap(), key);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use mdns_sd::{IfKind, ServiceDaemon, ServiceEvent, ServiceInfo};
+
+use crate::{device_key, server::Server};
+use lan_mouse_ipc::DiscoveredPeer;
+use lan_mouse_proto::PROTOCOL_VERSION;
+
+/// DNS-SD service type lan-mouse instances advertise themselves as
+const SERVICE_TYPE: &str = "_lan-mouse._udp.local.";
+
+pub(crate) struct Discovery {
+    daemon: ServiceDaemon,
+    hostname: String,
+    /// fingerprint of the device key
+    fingerprint: Option<String>,
+    /// fullname of the service registered by this instance
+    fullname: Option<String>,
+}
+
+impl Discovery {
+    /// create a new mDNS daemon, optionally restricted to the given interface
+    pub(crate) fn new(interface: Option<&str>, device_key: &Path) -> Result<Self, mdns_sd::Error> {
+        let daemon = ServiceDaemon::new()?;
+        if let Some(interface) = interface {
+            daemon.disable_interface(IfKind::All)?;
+            daemon.enable_interface(IfKind::Name(interface.to_owned()))?;
+        }
+        let hostname = hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_else(|| "lan-mouse".into());
+        let fingerprint = match device_key::load_or_create(device_key) {
+            Ok(key) => Some(device_key::fingerprint(&key)),
+            Err(e) => {
+                log::warn!(
+                    "{}: {e}, advertising without fingerprint",
+                    device_key.display()
+                );
+                None
+            }
+        };
+        Ok(Self {
+            daemon,
+            hostname,
+            fingerprint,
+            fullname: None,
+        })
+    }
+
+    pub(crate) async fn run(mut self, server: Server) {
+        tokio::select! {
+            _ = server.cancelled() => {},
+            r = self.do_discovery(&server) => if let Err(e) = r {
+                log::warn!("mdns discovery failed: {e}");
+            },
+        }
+        if let Some(fullname) = self.fullname.take() {
+            let _ = self.daemon.unregister(&fullname);
+        }
+        let _ = self.daemon.shutdown();
+    }
+
+    async fn do_discovery(&mut self, server: &Server) -> Result<(), mdns_sd::Error> {
+        self.advertise(server.get_port())?;
+        let browser = self.daemon.browse(SERVICE_TYPE)?;
+        loop {
+            tokio::select! {
+                event = browser.recv_async() => match event {
+                    Ok(ServiceEvent::ServiceResolved(info)) => {
+                        if Some(info.get_fullname()) == self.fullname.as_deref() {
+                            continue;
+                        }
+                        let peer = to_peer(&info);
+                        if peer.ips.is_empty() {
+                            continue;
+                        }
+                        log::info!("discovered peer {} ({:?})", peer.hostname, peer.ips);
+                        server.add_discovered_peer(info.get_fullname().to_owned(), peer);
+                    }
+                    Ok(ServiceEvent::ServiceRemoved(_, fullname)) => {
+                        server.remove_discovered_peer(&fullname);
+                    }
+                    Ok(_) => {}
+                    Err(_) => break,
+                },
+                _ = server.port_bound() => self.advertise(server.get_port())?,
+            }
+        }
+        Ok(())
+    }
+
+    /// (re-)register this instance with the given port
+    fn advertise(&mut self, port: u16) -> Result<(), mdns_sd::Error> {
+        if let Some(fullname) = self.fullname.take() {
+            self.daemon.unregister(&fullname)?;
+        }
+        let host = format!("{}.local.", self.hostname);
+        let version = PROTOCOL_VERSION.to_string();
+        let mut properties = vec![
+            ("hostname", self.hostname.as_str()),
+            ("version", version.as_str()),
+        ];
+        if let Some(fingerprint) = &self.fingerprint {
+            properties.push(("fingerprint", fingerprint.as_str()));
+        }
+        let info = ServiceInfo::new(
+            SERVICE_TYPE,
+            &self.hostname,
+            &host,
+            (),
+            port,
+            &properties[..],
+        )?
+        .enable_addr_auto();
+        log::info!("advertising {} on port {port}", info.get_fullname());
+        self.fullname = Some(info.get_fullname().to_owned());
+        self.daemon.register(info)
+    }
+}
+
+fn to_peer(info: &ServiceInfo) -> DiscoveredPeer {
+    let hostname = match info.get_property_val_str("hostname") {
+        Some(hostname) => hostname.to_owned(),
+        None => info.get_hostname().trim_end_matches(".local.").to_owned(),
+    };
+    let mut ips: Vec<_> = info
+        .get_addresses()
+        .iter()
+        .copied()
+        /* link-local ipv6 addresses are not usable without a scope id */
+        .filter(|ip| match ip {
+            std::net::IpAddr::V4(_) => true,
+            std::net::IpAddr::V6(ip) => !ip.is_unicast_link_local(),
+        })
+        .collect();
+    ips.sort();
+    DiscoveredPeer {
+        hostname,
+        ips,
+        port: info.get_port(),
+        protocol_version: info
+            .get_property_val_str("version")
+            .and_then(|v| v.parse().ok()),
+        fingerprint: info.get_property_val_str("fingerprint").map(str::to_owned),
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn discover_over_loopback() {
+        let dir = std::env::temp_dir().join(format!("lan-mouse-mdns-{}", std::process::id()));
+        let mut discovery = Discovery::new(Some("lo"), &dir.join("device_key")).unwrap();
+        discovery
+            .daemon
+            .enable_interface(IfKind::LoopbackV4)
+            .unwrap();
+        discovery.hostname = format!("lan-mouse-test-{}", std::process::id());
+        discovery.advertise(4343).unwrap();
+
+        let browser = ServiceDaemon::new().unwrap();
+        browser.disable_interface(IfKind::All).unwrap();
+        browser.enable_interface(IfKind::LoopbackV4).unwrap();
+        let events = browser.browse(SERVICE_TYPE).unwrap();
+        let peer = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                match events.recv_async().await.unwrap() {
+                    ServiceEvent::ServiceResolved(info)
+                        if Some(info.get_fullname()) == discovery.fullname.as_deref() =>
+                    {
+                        break to_peer(&info);
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("service not discovered");
+        assert_eq!(peer.hostname, discovery.hostname);
+        assert_eq!(peer.port, 4343);
+        assert_eq!(peer.protocol_version, Some(PROTOCOL_VERSION));
+        assert!(discovery.fingerprint.is_some());
+        assert_eq!(peer.fingerprint, discovery.fingerprint);
+        assert!(peer.ips.contains(&"127.0.0.1".parse().unwrap()));
+        let _ = browser.shutdown();
+        let _ = discovery.daemon.shutdown();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,14 +1,27 @@
 use local_channel::mpsc::Receiver;
-use std::net::IpAddr;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
 
 use hickory_resolver::{error::ResolveError, TokioAsyncResolver};
 
 use crate::server::Server;
 use lan_mouse_ipc::ClientHandle;
 
+/// lower bound for re-resolving a hostname,
+/// so that records with a ttl of 0 do not flood the resolver
+const MIN_TTL: Duration = Duration::from_secs(30);
+
+/// retry interval after a failed lookup
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
 pub(crate) struct DnsResolver {
     resolver: TokioAsyncResolver,
     dns_request: Receiver<ClientHandle>,
+    /// active clients scheduled for re-resolution
+    expiry: HashMap<ClientHandle, Instant>,
 }
 
 impl DnsResolver {
@@ -17,15 +30,18 @@ impl DnsResolver {
         Ok(Self {
             resolver,
             dns_request,
+            expiry: HashMap::new(),
         })
     }
 
-    async fn resolve(&self, host: &str) -> Result<Vec<IpAddr>, ResolveError> {
+    async fn resolve(&self, host: &str) -> Result<(Vec<IpAddr>, Instant), ResolveError> {
+        /* cached records may be the reason we are asked again */
+        self.resolver.clear_cache();
         let response = self.resolver.lookup_ip(host).await?;
         for ip in response.iter() {
             log::info!("{host}: adding ip {ip}");
         }
-        Ok(response.iter().collect())
+        Ok((response.iter().collect(), response.valid_until()))
     }
 
     pub(crate) async fn run(mut self, server: Server) {
@@ -37,7 +53,11 @@ impl DnsResolver {
 
     async fn do_dns(&mut self, server: &Server) {
         loop {
-            let handle = self.dns_request.recv().await.expect("channel closed");
+            let handle = tokio::select! {
+                handle = self.dns_request.recv() => handle.expect("channel closed"),
+                handle = next_expiry(&self.expiry) => handle,
+            };
+            self.expiry.remove(&handle);
 
             /* update resolving status */
             let hostname = match server.get_hostname(handle) {
@@ -48,16 +68,43 @@ impl DnsResolver {
             log::info!("resolving ({handle}) `{hostname}` ...");
             server.set_resolving(handle, true);
 
-            let ips = match self.resolve(&hostname).await {
-                Ok(ips) => ips,
+            let valid_until = match self.resolve(&hostname).await {
+                Ok((ips, valid_until)) => {
+                    server.update_dns_ips(handle, ips);
+                    valid_until.max(Instant::now() + MIN_TTL)
+                }
                 Err(e) => {
+                    /* keep the previous ips, they may still work */
                     log::warn!("could not resolve host '{hostname}': {e}");
-                    vec![]
+                    Instant::now() + RETRY_INTERVAL
                 }
             };
-
-            server.update_dns_ips(handle, ips);
             server.set_resolving(handle, false);
+
+            /* only active clients are kept up to date */
+            let active = server
+                .client_manager
+                .borrow()
+                .get(handle)
+                .is_some_and(|(_, s)| s.active);
+            if active {
+                log::debug!(
+                    "({handle}) `{hostname}`: re-resolving in {:?}",
+                    valid_until - Instant::now()
+                );
+                self.expiry.insert(handle, valid_until);
+            }
+        }
+    }
+}
+
+/// wait for the next client whose dns records expire
+async fn next_expiry(expiry: &HashMap<ClientHandle, Instant>) -> ClientHandle {
+    match expiry.iter().min_by_key(|(_, &t)| t) {
+        Some((&handle, &t)) => {
+            tokio::time::sleep_until(t.into()).await;
+            handle
         }
+        None => std::future::pending().await,
     }
 }
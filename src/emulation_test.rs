@@ -1,7 +1,11 @@
-use crate::config::Config;
+use crate::{
+    config::Config,
+    record::{self, RecordedEvent, ReplayError},
+};
 use input_emulation::{InputEmulation, InputEmulationError};
 use input_event::{Event, PointerEvent};
 use std::f64::consts::PI;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
 const FREQUENCY_HZ: f64 = 1.0;
@@ -33,3 +37,40 @@ pub async fn run(config: Config) -> Result<(), InputEmulationError> {
         }
     }
 }
+
+/// replay a recording made with `--test-capture --record <file>`.
+/// `speed` scales the recorded timing, `0` replays as fast as possible
+pub async fn replay(config: Config, path: &Path, speed: f64) -> Result<(), ReplayError> {
+    let records = record::read(path)?;
+    log::info!("replaying {} events from {}", records.len(), path.display());
+
+    let backend = config.emulation_backend.map(|b| b.into());
+    let mut emulation = InputEmulation::new(backend)
+        .await
+        .map_err(InputEmulationError::from)?;
+
+    let start = tokio::time::Instant::now();
+    let mut result = Ok(());
+    for record in records {
+        if speed > 0. {
+            tokio::time::sleep_until(start + record.offset().div_f64(speed)).await;
+        }
+        if emulation.create(record.handle).await {
+            log::info!("created emulation handle {}", record.handle);
+        }
+        match record.event {
+            RecordedEvent::Begin => log::info!("{}: begin capture", record.handle),
+            RecordedEvent::Input(event) => {
+                log::info!("{}: {event}", record.handle);
+                if let Err(e) = emulation.consume(event, record.handle).await {
+                    result = Err(InputEmulationError::from(e).into());
+                    break;
+                }
+            }
+        }
+    }
+
+    // keys still pressed at the end of the recording are released (and logged)
+    emulation.terminate().await;
+    result
+}
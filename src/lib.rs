@@ -1,7 +1,13 @@
+pub mod barrier;
 pub mod client;
 pub mod config;
+pub mod device_key;
+pub mod discovery;
 pub mod dns;
 pub mod server;
+pub mod wol;
 
 pub mod capture_test;
 pub mod emulation_test;
+pub mod record;
+pub mod simulate_peer;
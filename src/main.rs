@@ -1,12 +1,15 @@
 use env_logger::Env;
-use input_capture::InputCaptureError;
 use input_emulation::InputEmulationError;
 use lan_mouse::{
-    capture_test,
-    config::{Config, ConfigError, Frontend},
+    barrier::{self, BarrierError},
+    capture_test::{self, CaptureTestError},
+    config::{self, Config, ConfigError, Frontend},
     emulation_test,
-    server::{Server, ServiceError},
+    record::ReplayError,
+    server::{Server, ServiceError, Systemd},
+    simulate_peer::{self, SimulatePeerError},
 };
+use lan_mouse_cli::CtlError;
 use lan_mouse_ipc::IpcError;
 use std::{
     future::Future,
@@ -27,9 +30,26 @@ enum LanMouseError {
     #[error(transparent)]
     Io(#[from] io::Error),
     #[error(transparent)]
-    Capture(#[from] InputCaptureError),
+    Capture(#[from] CaptureTestError),
     #[error(transparent)]
     Emulation(#[from] InputEmulationError),
+    #[error(transparent)]
+    Replay(#[from] ReplayError),
+    #[error(transparent)]
+    SimulatePeer(#[from] SimulatePeerError),
+    #[error(transparent)]
+    Barrier(#[from] BarrierError),
+    #[error(transparent)]
+    Ctl(#[from] CtlError),
+}
+
+impl LanMouseError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            LanMouseError::Ctl(e) => e.exit_code(),
+            _ => 1,
+        }
+    }
 }
 
 pub fn main() {
@@ -39,38 +59,52 @@ pub fn main() {
 
     if let Err(e) = run() {
         log::error!("{e}");
-        process::exit(1);
+        process::exit(e.exit_code());
     }
 }
 
 fn run() -> Result<(), LanMouseError> {
     // parse config file + cli args
-    let config = Config::new()?;
+    let mut config = Config::new()?;
     log::debug!("{config:?}");
     log::info!("release bind: {:?}", config.release_bind);
 
-    if config.test_capture {
-        run_async(capture_test::run(config))?;
-    } else if config.test_emulation {
-        run_async(emulation_test::run(config))?;
-    } else if config.daemon {
-        // if daemon is specified we run the service
-        run_async(run_service(config))?;
-    } else {
-        //  otherwise start the service as a child process and
-        //  run a frontend
-        let mut service = start_service()?;
-        run_frontend(&config)?;
-        #[cfg(unix)]
-        {
-            // on unix we give the service a chance to terminate gracefully
-            let pid = service.id() as libc::pid_t;
-            unsafe {
-                libc::kill(pid, libc::SIGINT);
-            }
-            service.wait()?;
+    match config.command.take() {
+        Some(config::Command::Ctl(args)) => {
+            lan_mouse_cli::run_ctl(args, config.ipc_socket, config.ipc_tcp)?;
         }
-        service.kill()?;
+        Some(config::Command::SimulatePeer(args)) => run_async(simulate_peer::run(args))?,
+        Some(config::Command::Barrier(args)) => run_async(barrier::run(config, args))?,
+        None if config.test_capture => run_async(capture_test::run(config))?,
+        None => match config.replay.take() {
+            Some(path) => {
+                let speed = config.replay_speed;
+                run_async(emulation_test::replay(config, &path, speed))?;
+            }
+            None if config.test_emulation => run_async(emulation_test::run(config))?,
+            None if config.daemon => {
+                // if daemon is specified we run the service,
+                // the environment must be read before the runtime starts any threads
+                let systemd = Systemd::from_env();
+                run_async(run_service(config, systemd))?;
+            }
+            None => {
+                //  otherwise start the service as a child process and
+                //  run a frontend
+                let mut service = start_service()?;
+                run_frontend(&config)?;
+                #[cfg(unix)]
+                {
+                    // on unix we give the service a chance to terminate gracefully
+                    let pid = service.id() as libc::pid_t;
+                    unsafe {
+                        libc::kill(pid, libc::SIGINT);
+                    }
+                    service.wait()?;
+                }
+                service.kill()?;
+            }
+        },
     }
 
     Ok(())
@@ -99,9 +133,9 @@ fn start_service() -> Result<Child, io::Error> {
     Ok(child)
 }
 
-async fn run_service(config: Config) -> Result<(), ServiceError> {
+async fn run_service(config: Config, systemd: Systemd) -> Result<(), ServiceError> {
     log::info!("Press {:?} to release the mouse", config.release_bind);
-    Server::new(config).run().await?;
+    Server::new(config, systemd).run().await?;
     log::info!("service exited!");
     Ok(())
 }
@@ -110,12 +144,12 @@ fn run_frontend(config: &Config) -> Result<(), IpcError> {
     match config.frontend {
         #[cfg(feature = "gtk")]
         Frontend::Gtk => {
-            lan_mouse_gtk::run();
+            lan_mouse_gtk::run(config.ipc_socket.clone(), config.ipc_tcp);
         }
         #[cfg(not(feature = "gtk"))]
         Frontend::Gtk => panic!("gtk frontend requested but feature not enabled!"),
         Frontend::Cli => {
-            lan_mouse_cli::run()?;
+            lan_mouse_cli::run(config.ipc_socket.clone(), config.ipc_tcp)?;
         }
     };
     Ok(())
@@ -0,0 +1,172 @@
+//! recordings of captured input (`--test-capture --record <file>`)
+//! that can be replayed through input emulation (`--replay <file>`).
+//!
+//! A recording is a text file with one JSON object per captured event:
+//!
+//! ```text
+//! {"time":0,"handle":1,"event":"Begin"}
+//! {"time":8012,"handle":1,"event":{"Input":{"Pointer":{"Motion":{"time":0,"dx":-3.0,"dy":1.0}}}}}
+//! {"time":9240,"handle":1,"event":{"Input":{"Keyboard":{"Key":{"time":0,"key":30,"state":1}}}}}
+//! ```
+//!
+//! `time` is the time in microseconds since the start of the recording,
+//! `handle` the capture handle (one per position in the capture test)
+//! and `event` the captured event.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use input_capture::{CaptureEvent, CaptureHandle};
+use input_emulation::InputEmulationError;
+use input_event::Event;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("could not read recording: `{0}`")]
+    Io(#[from] io::Error),
+    #[error("invalid recording (line {line}): `{inner}`")]
+    Parse {
+        line: usize,
+        inner: serde_json::Error,
+    },
+    #[error(transparent)]
+    Emulation(#[from] InputEmulationError),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Record {
+    /// microseconds since the start of the recording
+    pub time: u64,
+    pub handle: CaptureHandle,
+    pub event: RecordedEvent,
+}
+
+impl Record {
+    pub fn offset(&self) -> Duration {
+        Duration::from_micros(self.time)
+    }
+}
+
+/// serializable [`CaptureEvent`]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum RecordedEvent {
+    Begin,
+    Input(Event),
+}
+
+impl From<CaptureEvent> for RecordedEvent {
+    fn from(event: CaptureEvent) -> Self {
+        match event {
+            CaptureEvent::Begin => Self::Begin,
+            CaptureEvent::Input(e) => Self::Input(e),
+        }
+    }
+}
+
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    /// append an event, flushing it so recordings survive a crash
+    pub fn write(&mut self, handle: CaptureHandle, event: CaptureEvent) -> io::Result<()> {
+        let record = Record {
+            time: self.start.elapsed().as_micros() as u64,
+            handle,
+            event: event.into(),
+        };
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// read a recording, skipping empty lines
+pub fn read(path: &Path) -> Result<Vec<Record>, ReplayError> {
+    parse(BufReader::new(File::open(path)?))
+}
+
+fn parse(reader: impl BufRead) -> Result<Vec<Record>, ReplayError> {
+    let mut records = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|inner| ReplayError::Parse { line: i + 1, inner })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use input_event::{KeyboardEvent, PointerEvent};
+
+    use super::*;
+
+    #[test]
+    fn documented_format() {
+        let recording = r#"{"time":0,"handle":1,"event":"Begin"}
+
+{"time":8012,"handle":1,"event":{"Input":{"Pointer":{"Motion":{"time":0,"dx":-3.0,"dy":1.0}}}}}
+{"time":9240,"handle":1,"event":{"Input":{"Keyboard":{"Key":{"time":0,"key":30,"state":1}}}}}
+"#;
+        let records = parse(recording.as_bytes()).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                Record {
+                    time: 0,
+                    handle: 1,
+                    event: RecordedEvent::Begin,
+                },
+                Record {
+                    time: 8012,
+                    handle: 1,
+                    event: RecordedEvent::Input(Event::Pointer(PointerEvent::Motion {
+                        time: 0,
+                        dx: -3.,
+                        dy: 1.,
+                    })),
+                },
+                Record {
+                    time: 9240,
+                    handle: 1,
+                    event: RecordedEvent::Input(Event::Keyboard(KeyboardEvent::Key {
+                        time: 0,
+                        key: 30,
+                        state: 1,
+                    })),
+                },
+            ]
+        );
+        let line = serde_json::to_string(&records[1]).unwrap();
+        assert_eq!(recording.lines().nth(2), Some(line.as_str()));
+    }
+
+    #[test]
+    fn reports_line() {
+        let recording = "{\"time\":0,\"handle\":1,\"event\":\"Begin\"}\n{\"time\":1}\n";
+        assert!(matches!(
+            parse(recording.as_bytes()),
+            Err(ReplayError::Parse { line: 2, .. })
+        ));
+    }
+}
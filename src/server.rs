@@ -2,30 +2,46 @@ use capture_task::CaptureRequest;
 use emulation_task::EmulationRequest;
 use futures::StreamExt;
 use hickory_resolver::error::ResolveError;
+use hook::HookEvent;
 use local_channel::mpsc::{channel, Sender};
 use log;
+use notification_task::Notification;
 use std::{
     cell::{Cell, RefCell},
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     io,
     net::{IpAddr, SocketAddr},
     rc::Rc,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 use tokio::{join, signal, sync::Notify};
 use tokio_util::sync::CancellationToken;
 
-use crate::{client::ClientManager, config::Config, dns::DnsResolver};
+use crate::{client::ClientManager, config::Config, discovery::Discovery, dns::DnsResolver, wol};
 
 use lan_mouse_ipc::{
-    AsyncFrontendListener, ClientConfig, ClientHandle, ClientState, FrontendEvent, FrontendRequest,
-    ListenerCreationError, Position, Status,
+    AsyncFrontendListener, ClientConfig, ClientHandle, ClientState, DiscoveredPeer, EventClass,
+    EventFilter, FocusState, FrontendEvent, FrontendReply, FrontendRequest, ListenerCreationError,
+    Liveness, MacAddress, Position, Presence, Status, Transport,
 };
 
 mod capture_task;
+mod dbus_task;
 mod emulation_task;
+mod hook;
 mod network_task;
+mod notification_task;
 mod ping_task;
+mod presence_task;
+mod session_task;
+mod systemd;
+
+pub use systemd::Systemd;
+
+/// time after which a client that was sent a wake-on-lan packet
+/// is no longer considered to be waking up
+const WAKE_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum State {
@@ -38,6 +54,16 @@ enum State {
     AwaitAck,
 }
 
+impl From<State> for FocusState {
+    fn from(state: State) -> Self {
+        match state {
+            State::Sending => FocusState::Sending,
+            State::Receiving => FocusState::Receiving,
+            State::AwaitAck => FocusState::AwaitAck,
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum ServiceError {
     #[error(transparent)]
@@ -58,8 +84,21 @@ pub struct Server {
     notifies: Rc<Notifies>,
     config: Rc<Config>,
     pending_frontend_events: Rc<RefCell<VecDeque<FrontendEvent>>>,
+    pending_notifications: Rc<RefCell<VecDeque<Notification>>>,
     capture_status: Rc<Cell<Status>>,
     emulation_status: Rc<Cell<Status>>,
+    /// peers found via mDNS, indexed by their service name
+    discovered_peers: Rc<RefCell<HashMap<String, DiscoveredPeer>>>,
+    /// time anything was last received from a client
+    last_seen: Rc<RefCell<HashMap<ClientHandle, Instant>>>,
+    /// client that currently sends events to this device
+    receiving_from: Rc<Cell<Option<ClientHandle>>>,
+    /// lock state of the session requested by a peer
+    requested_lock: Rc<Cell<Option<bool>>>,
+    /// readiness, watchdog and status notifications for systemd
+    systemd: Rc<RefCell<systemd::Notifier>>,
+    /// sockets passed by systemd, taken when the service starts
+    listen_fds: Rc<Cell<systemd::ListenFds>>,
 }
 
 #[derive(Default)]
@@ -68,12 +107,16 @@ struct Notifies {
     emulation: Notify,
     ping: Notify,
     port_changed: Notify,
+    port_bound: Notify,
     frontend_event_pending: Notify,
+    notification_pending: Notify,
+    receiving_changed: Notify,
+    lock_requested: Notify,
     cancel: CancellationToken,
 }
 
 impl Server {
-    pub fn new(config: Config) -> Self {
+    pub fn new(config: Config, systemd: Systemd) -> Self {
         let active_client = Rc::new(Cell::new(None));
         let client_manager = Rc::new(RefCell::new(ClientManager::default()));
         let state = Rc::new(Cell::new(State::Receiving));
@@ -84,7 +127,12 @@ impl Server {
                 fix_ips: config_client.ips.into_iter().collect(),
                 port: config_client.port,
                 pos: config_client.pos,
-                cmd: config_client.enter_hook,
+                hooks: config_client.hooks,
+                transport: config_client.transport,
+                liveness: config_client.liveness,
+                mac: config_client.mac,
+                send_filter: config_client.send_filter,
+                receive_filter: config_client.receive_filter,
             };
             let state = ClientState {
                 active: config_client.active,
@@ -112,14 +160,31 @@ impl Server {
             release_bind,
             notifies,
             pending_frontend_events: Rc::new(RefCell::new(VecDeque::new())),
+            pending_notifications: Rc::new(RefCell::new(VecDeque::new())),
             capture_status: Default::default(),
             emulation_status: Default::default(),
+            discovered_peers: Default::default(),
+            last_seen: Default::default(),
+            receiving_from: Default::default(),
+            requested_lock: Default::default(),
+            systemd: Rc::new(RefCell::new(systemd.notifier)),
+            listen_fds: Rc::new(Cell::new(systemd.listen_fds)),
         }
     }
 
     pub async fn run(&mut self) -> Result<(), ServiceError> {
+        // sockets passed by systemd
+        let listen_fds = self.listen_fds.take();
+
         // create frontend communication adapter, exit if already running
-        let mut frontend = match AsyncFrontendListener::new().await {
+        let frontend = match listen_fds.ipc {
+            Some(listener) => AsyncFrontendListener::from_std(listener, self.config.ipc_tcp).await,
+            None => {
+                AsyncFrontendListener::new(self.config.ipc_socket.clone(), self.config.ipc_tcp)
+                    .await
+            }
+        };
+        let mut frontend = match frontend {
             Ok(f) => f,
             Err(ListenerCreationError::AlreadyRunning) => {
                 log::info!("service already running, exiting");
@@ -133,13 +198,40 @@ impl Server {
         let (udp_recv_tx, udp_recv_rx) = channel(); /* udp receiver */
         let (udp_send_tx, udp_send_rx) = channel(); /* udp sender */
         let (dns_tx, dns_rx) = channel(); /* dns requests */
+        let (dbus_tx, dbus_rx) = channel(); /* frontend events for d-bus */
+        let (dbus_request_tx, mut dbus_request_rx) = tokio::sync::mpsc::unbounded_channel();
 
-        let network = network_task::new(self.clone(), udp_recv_tx.clone(), udp_send_rx).await?;
+        let network = network_task::new(
+            self.clone(),
+            listen_fds.udp,
+            udp_recv_tx.clone(),
+            udp_send_rx,
+        )?;
+        // frontends and peers can connect from now on
+        self.systemd.borrow_mut().ready();
         let capture = capture_task::new(self.clone(), capture_rx, udp_send_tx.clone());
         let emulation =
             emulation_task::new(self.clone(), emulation_rx, udp_recv_rx, udp_send_tx.clone());
         let resolver = DnsResolver::new(dns_rx)?;
         let dns_task = tokio::task::spawn_local(resolver.run(self.clone()));
+        let notifications = notification_task::new(self.clone());
+        let discovery = match self.config.discovery {
+            true => match Discovery::new(self.config.interface.as_deref(), &self.config.device_key)
+            {
+                Ok(discovery) => Some(discovery),
+                Err(e) => {
+                    log::warn!("mdns discovery unavailable: {e}");
+                    None
+                }
+            },
+            false => None,
+        };
+        let server = self.clone();
+        let discovery = tokio::task::spawn_local(async move {
+            if let Some(discovery) = discovery {
+                discovery.run(server).await;
+            }
+        });
 
         // task that pings clients to see if they are responding
         let ping = ping_task::new(
@@ -147,16 +239,31 @@ impl Server {
             udp_send_tx.clone(),
             emulation_tx.clone(),
             capture_tx.clone(),
+            dns_tx.clone(),
         );
 
+        // task that monitors whether active clients are online
+        let presence = presence_task::new(self.clone(), udp_send_tx.clone());
+
+        // task that synchronizes the session lock state with peers
+        let session = session_task::new(self.clone(), udp_send_tx.clone());
+
+        // d-bus interface, alongside the frontend socket
+        let dbus = dbus_task::new(self.clone(), dbus_rx, dbus_request_tx);
+
         for handle in self.active_clients() {
             dns_tx.send(handle).expect("channel closed");
         }
 
+        let watchdog_interval = self.systemd.borrow().watchdog_interval();
+        let mut watchdog =
+            tokio::time::interval(watchdog_interval.unwrap_or(Duration::from_secs(3600)));
+        self.update_status();
+
         loop {
             tokio::select! {
                 request = frontend.next() => {
-                    let request = match request {
+                    let (request, origin) = match request {
                         Some(Ok(r)) => r,
                         Some(Err(e)) => {
                             log::error!("error receiving request: {e}");
@@ -165,7 +272,16 @@ impl Server {
                         None => break,
                     };
                     log::debug!("handle frontend request: {request:?}");
-                    self.handle_request(&capture_tx.clone(), &emulation_tx.clone(), request, &dns_tx);
+                    let reply = self.handle_request(&capture_tx, &emulation_tx, request, &dns_tx);
+                    match origin {
+                        Some(origin) => frontend.reply(origin, reply).await,
+                        None => self.broadcast_error(reply),
+                    }
+                }
+                Some((request, reply_tx)) = dbus_request_rx.recv() => {
+                    log::debug!("handle d-bus request: {request:?}");
+                    let reply = self.handle_request(&capture_tx, &emulation_tx, request, &dns_tx);
+                    let _ = reply_tx.send(reply);
                 }
                 _ = self.notifies.frontend_event_pending.notified() => {
                     while let Some(event) = {
@@ -173,9 +289,13 @@ impl Server {
                         let event = self.pending_frontend_events.borrow_mut().pop_front();
                         event
                     } {
+                        let _ = dbus_tx.send(event.clone());
                         frontend.broadcast(event).await;
                     }
                 },
+                _ = watchdog.tick(), if watchdog_interval.is_some() => {
+                    self.systemd.borrow().watchdog();
+                }
                 _ = self.cancelled() => break,
                 r = signal::ctrl_c() => {
                     r.expect("failed to wait for CTRL+C");
@@ -185,9 +305,21 @@ impl Server {
         }
 
         log::info!("terminating service");
+        self.systemd.borrow().stopping();
 
         self.cancel();
-        let _ = join!(capture, dns_task, emulation, network, ping);
+        let _ = join!(
+            capture,
+            dbus,
+            discovery,
+            dns_task,
+            emulation,
+            network,
+            notifications,
+            ping,
+            presence,
+            session
+        );
 
         Ok(())
     }
@@ -197,6 +329,31 @@ impl Server {
         self.notifies.frontend_event_pending.notify_one();
     }
 
+    /// frontends that do not receive replies are notified of errors by events
+    fn broadcast_error(&self, reply: FrontendReply) {
+        match reply {
+            FrontendReply::NoSuchClient(handle) => {
+                self.notify_frontend(FrontendEvent::NoSuchClient(handle))
+            }
+            FrontendReply::Error(e) => self.notify_frontend(FrontendEvent::Error(e)),
+            _ => {}
+        }
+    }
+
+    fn notify_desktop(&self, notification: Notification) {
+        if !self.config.notifications {
+            return;
+        }
+        self.pending_notifications
+            .borrow_mut()
+            .push_back(notification);
+        self.notifies.notification_pending.notify_one();
+    }
+
+    async fn notification_pending(&self) {
+        self.notifies.notification_pending.notified().await
+    }
+
     fn cancel(&self) {
         self.notifies.cancel.cancel();
     }
@@ -241,10 +398,19 @@ impl Server {
     }
 
     fn notify_port_changed(&self, port: u16, msg: Option<String>) {
+        if let Some(e) = &msg {
+            let requested = self.port.get();
+            self.notify_desktop(Notification::PortBindFailed(requested, e.clone()));
+        }
         self.port.replace(port);
+        self.notifies.port_bound.notify_waiters();
         self.notify_frontend(FrontendEvent::PortChanged(port, msg));
     }
 
+    pub(crate) async fn port_bound(&self) {
+        self.notifies.port_bound.notified().await
+    }
+
     pub(crate) fn client_updated(&self, handle: ClientHandle) {
         self.notify_frontend(FrontendEvent::Changed(handle));
     }
@@ -264,55 +430,134 @@ impl Server {
         emulate: &Sender<EmulationRequest>,
         event: FrontendRequest,
         dns: &Sender<ClientHandle>,
-    ) -> bool {
+    ) -> FrontendReply {
         log::debug!("frontend: {event:?}");
-        match event {
-            FrontendRequest::EnableCapture => self.notify_capture(),
-            FrontendRequest::EnableEmulation => self.notify_emulation(),
-            FrontendRequest::Create => {
-                self.add_client();
+        let no_such_client = event
+            .client_handle()
+            .filter(|&h| self.client_manager.borrow().get(h).is_none());
+        let reply = match event {
+            FrontendRequest::EnableCapture => {
+                self.notify_capture();
+                FrontendReply::Ok
+            }
+            FrontendRequest::EnableEmulation => {
+                self.notify_emulation();
+                FrontendReply::Ok
             }
+            FrontendRequest::Create => FrontendReply::Created(self.add_client()),
             FrontendRequest::Activate(handle, active) => {
                 if active {
                     self.activate_client(capture, emulate, handle);
                 } else {
                     self.deactivate_client(capture, emulate, handle);
                 }
+                FrontendReply::Ok
+            }
+            FrontendRequest::ChangePort(port) => {
+                self.request_port_change(port);
+                FrontendReply::Ok
             }
-            FrontendRequest::ChangePort(port) => self.request_port_change(port),
             FrontendRequest::Delete(handle) => {
                 self.remove_client(capture, emulate, handle);
                 self.notify_frontend(FrontendEvent::Deleted(handle));
+                FrontendReply::Ok
+            }
+            FrontendRequest::Enumerate() => {
+                let clients = self.enumerate();
+                self.notify_frontend(FrontendEvent::Enumerate(clients.clone()));
+                FrontendReply::Enumerate(clients)
+            }
+            FrontendRequest::GetState(handle) => match self.broadcast_client(handle) {
+                Some((config, state)) => FrontendReply::State(handle, config, state),
+                None => FrontendReply::NoSuchClient(handle),
+            },
+            FrontendRequest::UpdateFixIps(handle, fix_ips) => {
+                self.update_fix_ips(handle, fix_ips);
+                FrontendReply::Ok
             }
-            FrontendRequest::Enumerate() => self.enumerate(),
-            FrontendRequest::GetState(handle) => self.broadcast_client(handle),
-            FrontendRequest::UpdateFixIps(handle, fix_ips) => self.update_fix_ips(handle, fix_ips),
             FrontendRequest::UpdateHostname(handle, host) => {
-                self.update_hostname(handle, host, dns)
+                self.update_hostname(handle, host, dns);
+                FrontendReply::Ok
+            }
+            FrontendRequest::UpdatePort(handle, port) => {
+                self.update_port(handle, port);
+                FrontendReply::Ok
+            }
+            FrontendRequest::UpdateTransport(handle, transport) => {
+                self.update_transport(handle, transport);
+                FrontendReply::Ok
+            }
+            FrontendRequest::UpdateLiveness(handle, liveness) => {
+                self.update_liveness(handle, liveness);
+                FrontendReply::Ok
+            }
+            FrontendRequest::UpdateMac(handle, mac) => {
+                self.update_mac(handle, mac);
+                FrontendReply::Ok
+            }
+            FrontendRequest::UpdateSendFilter(handle, filter) => {
+                self.update_send_filter(handle, filter);
+                FrontendReply::Ok
+            }
+            FrontendRequest::UpdateReceiveFilter(handle, filter) => {
+                self.update_receive_filter(handle, filter);
+                FrontendReply::Ok
+            }
+            FrontendRequest::Wake(handle) => {
+                let mac = self
+                    .client_manager
+                    .borrow()
+                    .get(handle)
+                    .and_then(|(c, _)| c.mac);
+                match mac {
+                    Some(mac) => {
+                        self.wake_client(handle, mac);
+                        FrontendReply::Ok
+                    }
+                    None => FrontendReply::Error(format!("client {handle} has no mac address")),
+                }
             }
-            FrontendRequest::UpdatePort(handle, port) => self.update_port(handle, port),
             FrontendRequest::UpdatePosition(handle, pos) => {
-                self.update_pos(handle, capture, emulate, pos)
+                self.update_pos(handle, capture, emulate, pos);
+                FrontendReply::Ok
+            }
+            FrontendRequest::ResolveDns(handle) => {
+                dns.send(handle).expect("channel closed");
+                FrontendReply::Ok
             }
-            FrontendRequest::ResolveDns(handle) => dns.send(handle).expect("channel closed"),
             FrontendRequest::Sync => {
-                self.enumerate();
+                let clients = self.enumerate();
+                self.notify_frontend(FrontendEvent::Enumerate(clients));
                 self.notify_frontend(FrontendEvent::EmulationStatus(self.emulation_status.get()));
                 self.notify_frontend(FrontendEvent::CaptureStatus(self.capture_status.get()));
+                self.notify_frontend(FrontendEvent::ActiveClientChanged(self.active_client.get()));
+                self.notify_frontend(FrontendEvent::FocusChanged(self.state.get().into()));
+                let peers: Vec<_> = self.discovered_peers.borrow().values().cloned().collect();
+                for peer in peers {
+                    self.notify_frontend(FrontendEvent::PeerDiscovered(peer));
+                }
                 self.notify_frontend(FrontendEvent::PortChanged(self.port.get(), None));
+                FrontendReply::Ok
+            }
+            FrontendRequest::AddDiscovered(hostname, pos) => {
+                match self.add_discovered_client(capture, emulate, hostname, pos) {
+                    Ok(handle) => FrontendReply::Created(handle),
+                    Err(msg) => FrontendReply::Error(msg),
+                }
             }
         };
-        false
+        match no_such_client {
+            Some(handle) => FrontendReply::NoSuchClient(handle),
+            None => reply,
+        }
     }
 
-    fn enumerate(&self) {
-        let clients = self
-            .client_manager
+    fn enumerate(&self) -> Vec<(ClientHandle, ClientConfig, ClientState)> {
+        self.client_manager
             .borrow()
             .get_client_states()
             .map(|(h, (c, s))| (h, c.clone(), s.clone()))
-            .collect();
-        self.notify_frontend(FrontendEvent::Enumerate(clients));
+            .collect()
     }
 
     fn add_client(&self) -> ClientHandle {
@@ -323,6 +568,53 @@ impl Server {
         handle
     }
 
+    fn add_discovered_client(
+        &self,
+        capture: &Sender<CaptureRequest>,
+        emulate: &Sender<EmulationRequest>,
+        hostname: String,
+        pos: Position,
+    ) -> Result<ClientHandle, String> {
+        let peer = self
+            .discovered_peers
+            .borrow()
+            .values()
+            .find(|p| p.hostname == hostname)
+            .cloned();
+        let Some(peer) = peer else {
+            return Err(format!("no discovered peer named {hostname}"));
+        };
+
+        let handle = self.add_client();
+        if let Some((c, _)) = self.client_manager.borrow_mut().get_mut(handle) {
+            c.hostname = Some(peer.hostname);
+            c.fix_ips = peer.ips;
+            c.port = peer.port;
+            c.pos = pos;
+        }
+        self.update_ips(handle);
+        self.activate_client(capture, emulate, handle);
+        Ok(handle)
+    }
+
+    pub(crate) fn add_discovered_peer(&self, name: String, peer: DiscoveredPeer) {
+        let previous = self
+            .discovered_peers
+            .borrow_mut()
+            .insert(name, peer.clone());
+        if previous.as_ref() != Some(&peer) {
+            self.notify_frontend(FrontendEvent::PeerDiscovered(peer));
+        }
+    }
+
+    pub(crate) fn remove_discovered_peer(&self, name: &str) {
+        let peer = self.discovered_peers.borrow_mut().remove(name);
+        if let Some(peer) = peer {
+            log::info!("peer {} disappeared", peer.hostname);
+            self.notify_frontend(FrontendEvent::PeerLost(peer.hostname));
+        }
+    }
+
     fn deactivate_client(
         &self,
         capture: &Sender<CaptureRequest>,
@@ -332,8 +624,12 @@ impl Server {
         match self.client_manager.borrow_mut().get_mut(handle) {
             None => return,
             Some((_, s)) if !s.active => return,
-            Some((_, s)) => s.active = false,
+            Some((_, s)) => {
+                s.active = false;
+                s.presence = Presence::Unknown;
+            }
         };
+        self.stopped_receiving_from(handle);
 
         let _ = capture.send(CaptureRequest::Destroy(handle));
         let _ = emulate.send(EmulationRequest::Destroy(handle));
@@ -390,6 +686,14 @@ impl Server {
             return;
         };
 
+        if self.active_client.get() == Some(handle) {
+            self.set_active(None);
+        }
+        if self.receiving_from.get() == Some(handle) {
+            self.receiving_from.take();
+            self.notifies.receiving_changed.notify_one();
+        }
+
         if active {
             let _ = capture.send(CaptureRequest::Destroy(handle));
             let _ = emulate.send(EmulationRequest::Destroy(handle));
@@ -410,8 +714,19 @@ impl Server {
         self.client_updated(handle);
     }
 
-    pub(crate) fn update_dns_ips(&self, handle: ClientHandle, dns_ips: Vec<IpAddr>) {
+    pub(crate) fn update_dns_ips(&self, handle: ClientHandle, mut dns_ips: Vec<IpAddr>) {
         if let Some((_, s)) = self.client_manager.borrow_mut().get_mut(handle) {
+            // keep the ip the client is currently reached at
+            // until it stops answering, e.g. while the records are being updated
+            if let Some(ip) = s.active_addr.map(|a| a.ip()) {
+                if s.dns_ips.contains(&ip)
+                    && !dns_ips.contains(&ip)
+                    && s.presence != Presence::Offline
+                {
+                    log::info!("({handle}) keeping active ip {ip} removed from dns");
+                    dns_ips.push(ip);
+                }
+            }
             s.dns_ips = dns_ips;
         };
         self.update_ips(handle);
@@ -464,6 +779,66 @@ impl Server {
         }
     }
 
+    fn update_transport(&self, handle: ClientHandle, transport: Transport) {
+        if let Some((c, _)) = self.client_manager.borrow_mut().get_mut(handle) {
+            c.transport = transport;
+        };
+        self.client_updated(handle);
+    }
+
+    fn update_liveness(&self, handle: ClientHandle, liveness: Liveness) {
+        if let Some((c, _)) = self.client_manager.borrow_mut().get_mut(handle) {
+            c.liveness = liveness;
+        };
+        self.client_updated(handle);
+    }
+
+    fn update_mac(&self, handle: ClientHandle, mac: Option<MacAddress>) {
+        if let Some((c, _)) = self.client_manager.borrow_mut().get_mut(handle) {
+            c.mac = mac;
+        };
+        self.client_updated(handle);
+    }
+
+    fn update_send_filter(&self, handle: ClientHandle, filter: EventFilter) {
+        if let Some((c, _)) = self.client_manager.borrow_mut().get_mut(handle) {
+            c.send_filter = filter;
+        };
+        self.client_updated(handle);
+    }
+
+    fn update_receive_filter(&self, handle: ClientHandle, filter: EventFilter) {
+        if let Some((c, _)) = self.client_manager.borrow_mut().get_mut(handle) {
+            c.receive_filter = filter;
+        };
+        self.client_updated(handle);
+    }
+
+    /// send a wake-on-lan packet and mark the client as waking up
+    fn wake_client(&self, handle: ClientHandle, mac: MacAddress) {
+        if let Err(e) = wol::wake(mac, self.config.interface.as_deref(), self.config.bind_addr) {
+            log::warn!("could not wake client {handle}: {e}");
+            self.notify_frontend(FrontendEvent::Error(format!(
+                "could not send wake-on-lan packet: {e}"
+            )));
+            return;
+        }
+        self.set_waking(handle, true);
+        let server = self.clone();
+        tokio::task::spawn_local(async move {
+            tokio::time::sleep(WAKE_TIMEOUT).await;
+            server.set_waking(handle, false);
+        });
+    }
+
+    fn set_waking(&self, handle: ClientHandle, waking: bool) {
+        match self.client_manager.borrow_mut().get_mut(handle) {
+            Some((_, s)) if s.waking != waking => s.waking = waking,
+            _ => return,
+        }
+        self.client_updated(handle);
+    }
+
     fn update_pos(
         &self,
         handle: ClientHandle,
@@ -494,24 +869,22 @@ impl Server {
         }
     }
 
-    fn broadcast_client(&self, handle: ClientHandle) {
-        let client = self.client_manager.borrow().get(handle).cloned();
-        let event = if let Some((config, state)) = client {
-            FrontendEvent::State(handle, config, state)
-        } else {
-            FrontendEvent::NoSuchClient(handle)
-        };
-        self.notify_frontend(event);
+    fn broadcast_client(&self, handle: ClientHandle) -> Option<(ClientConfig, ClientState)> {
+        let (config, state) = self.client_manager.borrow().get(handle).cloned()?;
+        self.notify_frontend(FrontendEvent::State(handle, config.clone(), state.clone()));
+        Some((config, state))
     }
 
     fn set_emulation_status(&self, status: Status) {
         self.emulation_status.replace(status);
+        self.update_status();
         let status = FrontendEvent::EmulationStatus(status);
         self.notify_frontend(status);
     }
 
     fn set_capture_status(&self, status: Status) {
         self.capture_status.replace(status);
+        self.update_status();
         let status = FrontendEvent::CaptureStatus(status);
         self.notify_frontend(status);
     }
@@ -530,18 +903,158 @@ impl Server {
             .and_then(|(c, _)| c.hostname.clone())
     }
 
+    pub(crate) fn get_port(&self) -> u16 {
+        self.port.get()
+    }
+
     fn get_state(&self) -> State {
         self.state.get()
     }
 
     fn set_state(&self, state: State) {
-        log::debug!("state => {state:?}");
-        self.state.replace(state);
+        let prev = self.state.replace(state);
+        if prev != state {
+            log::debug!("state => {state:?}");
+            self.notify_frontend(FrontendEvent::FocusChanged(state.into()));
+            self.update_status();
+            if let Some(handle) = self.active_client.get() {
+                match (prev, state) {
+                    (State::Receiving, _) => hook::spawn(self, handle, HookEvent::Enter),
+                    (_, State::Receiving) => hook::spawn(self, handle, HookEvent::Leave),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// track the client that sends events to this device
+    fn set_receiving_from(&self, handle: Option<ClientHandle>) {
+        let prev = self.receiving_from.replace(handle);
+        if prev == handle {
+            return;
+        }
+        self.notifies.receiving_changed.notify_one();
+        self.update_status();
+        if let Some(prev) = prev {
+            hook::spawn(self, prev, HookEvent::ReceiveStop);
+        }
+        if let Some(handle) = handle {
+            hook::spawn(self, handle, HookEvent::ReceiveStart);
+        }
+    }
+
+    async fn receiving_changed(&self) -> Option<ClientHandle> {
+        self.notifies.receiving_changed.notified().await;
+        self.receiving_from.get()
+    }
+
+    /// a peer asked to lock / unlock the session.
+    /// unlocking is only accepted if enabled explicitly
+    /// and only from the peer currently sending input
+    fn request_lock(&self, handle: ClientHandle, locked: bool) {
+        let unlock_allowed = self.config.sync_unlock && self.receiving_from.get() == Some(handle);
+        if !locked && !unlock_allowed {
+            log::warn!("ignoring unlock request of client {handle}");
+            return;
+        }
+        self.requested_lock.replace(Some(locked));
+        self.notifies.lock_requested.notify_one();
+    }
+
+    async fn lock_requested(&self) -> bool {
+        loop {
+            self.notifies.lock_requested.notified().await;
+            if let Some(locked) = self.requested_lock.take() {
+                return locked;
+            }
+        }
+    }
+
+    /// addresses of all active clients
+    fn active_client_addrs(&self) -> Vec<SocketAddr> {
+        self.client_manager
+            .borrow()
+            .get_client_states()
+            .filter(|(_, (_, s))| s.active)
+            .flat_map(|(_, (c, s))| match s.active_addr {
+                Some(addr) => vec![addr],
+                None => s
+                    .ips
+                    .iter()
+                    .map(|&ip| SocketAddr::new(ip, c.port))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn stopped_receiving_from(&self, handle: ClientHandle) {
+        if self.receiving_from.get() == Some(handle) {
+            self.set_receiving_from(None);
+        }
     }
 
     fn set_active(&self, handle: Option<ClientHandle>) {
-        log::debug!("active client => {handle:?}");
-        self.active_client.replace(handle);
+        if self.active_client.replace(handle) != handle {
+            log::debug!("active client => {handle:?}");
+            self.notify_frontend(FrontendEvent::ActiveClientChanged(handle));
+            self.update_status();
+        }
+    }
+
+    /// report what the service is doing to systemd
+    fn update_status(&self) {
+        let name = |handle| {
+            self.get_hostname(handle)
+                .unwrap_or_else(|| format!("client {handle}"))
+        };
+        let mut status = match (self.state.get(), self.active_client.get()) {
+            (State::Sending, Some(handle)) => format!("sending to {}", name(handle)),
+            (State::AwaitAck, Some(handle)) => format!("entering {}", name(handle)),
+            _ => match self.receiving_from.get() {
+                Some(handle) => format!("receiving from {}", name(handle)),
+                None => "idle".to_string(),
+            },
+        };
+        if !bool::from(self.capture_status.get()) {
+            status.push_str(", input capture unavailable");
+        }
+        if !bool::from(self.emulation_status.get()) {
+            status.push_str(", input emulation unavailable");
+        }
+        self.systemd.borrow_mut().status(status);
+    }
+
+    /// record that something was received from the client
+    fn client_seen(&self, handle: ClientHandle) {
+        self.last_seen.borrow_mut().insert(handle, Instant::now());
+        self.set_waking(handle, false);
+    }
+
+    fn last_seen(&self, handle: ClientHandle) -> Option<Instant> {
+        self.last_seen.borrow().get(&handle).copied()
+    }
+
+    fn get_presence(&self, handle: ClientHandle) -> Presence {
+        self.client_manager
+            .borrow()
+            .get(handle)
+            .map(|(_, s)| s.presence)
+            .unwrap_or_default()
+    }
+
+    fn set_presence(&self, handle: ClientHandle, presence: Presence) {
+        match self.client_manager.borrow_mut().get_mut(handle) {
+            Some((_, s)) if s.active && s.presence != presence => s.presence = presence,
+            _ => return,
+        }
+        log::info!("client {handle} is {presence}");
+        self.notify_frontend(FrontendEvent::PresenceChanged(handle, presence));
+        self.client_updated(handle);
+        match presence {
+            Presence::Online => hook::spawn(self, handle, HookEvent::Online),
+            Presence::Offline => hook::spawn(self, handle, HookEvent::Offline),
+            Presence::Unknown => {}
+        }
     }
 
     fn active_addr(&self, handle: ClientHandle) -> Option<SocketAddr> {
@@ -550,6 +1063,42 @@ impl Server {
             .get(handle)
             .and_then(|(_, s)| s.active_addr)
     }
+
+    /// whether the event may be sent to the client
+    fn may_send(&self, handle: ClientHandle, event: &input_event::Event) -> bool {
+        self.client_manager
+            .borrow()
+            .get(handle)
+            .map(|(c, _)| passes(&c.send_filter, event))
+            .unwrap_or(true)
+    }
+
+    /// whether the event received from the client may be emulated
+    fn may_receive(&self, handle: ClientHandle, event: &input_event::Event) -> bool {
+        self.client_manager
+            .borrow()
+            .get(handle)
+            .map(|(c, _)| passes(&c.receive_filter, event))
+            .unwrap_or(true)
+    }
+}
+
+/// releases and modifier state always pass, so changing a filter
+/// can not leave keys, modifiers or buttons stuck
+fn passes(filter: &EventFilter, event: &input_event::Event) -> bool {
+    use input_event::{Event, KeyboardEvent, PointerEvent};
+    let class = match event {
+        Event::Pointer(PointerEvent::Motion { .. }) => EventClass::Motion,
+        Event::Pointer(PointerEvent::Button { state: 0, .. }) => return true,
+        Event::Pointer(PointerEvent::Button { .. }) => EventClass::Buttons,
+        Event::Pointer(PointerEvent::Axis { .. } | PointerEvent::AxisDiscrete120 { .. }) => {
+            EventClass::Scroll
+        }
+        Event::Keyboard(KeyboardEvent::Key { state: 0, .. }) => return true,
+        Event::Keyboard(KeyboardEvent::Modifiers { .. }) => return true,
+        Event::Keyboard(KeyboardEvent::Key { .. }) => EventClass::Keys,
+    };
+    filter.allows(class)
 }
 
 fn to_capture_pos(pos: Position) -> input_capture::Position {
@@ -560,3 +1109,30 @@ fn to_capture_pos(pos: Position) -> input_capture::Position {
         Position::Bottom => input_capture::Position::Bottom,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_event::{Event, KeyboardEvent};
+
+    #[test]
+    fn filters_keep_releases_and_modifiers() {
+        let filter = EventFilter::from_iter([EventClass::Motion]);
+        let key = |state| {
+            Event::Keyboard(KeyboardEvent::Key {
+                time: 0,
+                key: 30,
+                state,
+            })
+        };
+        assert!(!passes(&filter, &key(1)));
+        assert!(passes(&filter, &key(0)));
+        let modifiers = Event::Keyboard(KeyboardEvent::Modifiers {
+            depressed: 0,
+            latched: 0,
+            locked: 0,
+            group: 0,
+        });
+        assert!(passes(&filter, &modifiers));
+    }
+}
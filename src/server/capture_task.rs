@@ -3,16 +3,17 @@ use lan_mouse_proto::ProtoEvent;
 use local_channel::mpsc::{Receiver, Sender};
 use std::net::SocketAddr;
 
-use tokio::{process::Command, task::JoinHandle};
+use tokio::task::JoinHandle;
 
 use input_capture::{
-    self, CaptureError, CaptureEvent, CaptureHandle, InputCapture, InputCaptureError, Position,
+    self, CaptureError, CaptureEvent, CaptureHandle, CaptureOptions, InputCapture,
+    InputCaptureError, Position,
 };
 
 use crate::server::State;
-use lan_mouse_ipc::{ClientHandle, Status};
+use lan_mouse_ipc::{Presence, Status};
 
-use super::Server;
+use super::{Notification, Server};
 
 #[derive(Clone, Copy, Debug)]
 pub(crate) enum CaptureRequest {
@@ -42,6 +43,7 @@ async fn capture_task(
     loop {
         if let Err(e) = do_capture(backend, &server, &sender_tx, &mut notify_rx).await {
             log::warn!("input capture exited: {e}");
+            server.notify_desktop(Notification::CaptureFailed(e.to_string()));
         }
         server.set_capture_status(Status::Disabled);
         if server.is_cancelled() {
@@ -59,6 +61,17 @@ async fn capture_task(
     }
 }
 
+fn capture_options(server: &Server) -> CaptureOptions {
+    let mut options = CaptureOptions {
+        evdev_hotkey: server.config.evdev_hotkey.clone(),
+        ..Default::default()
+    };
+    if let Some(size) = server.config.evdev_screen_size {
+        options.evdev_screen_size = size;
+    }
+    options
+}
+
 async fn do_capture(
     backend: Option<input_capture::Backend>,
     server: &Server,
@@ -67,7 +80,7 @@ async fn do_capture(
 ) -> Result<(), InputCaptureError> {
     /* allow cancelling capture request */
     let mut capture = tokio::select! {
-        r = InputCapture::new(backend) => r?,
+        r = InputCapture::with_options(backend, capture_options(server)) => r?,
         _ = server.cancelled() => return Ok(()),
     };
 
@@ -101,7 +114,7 @@ async fn do_capture(
                     Some(e) => match e {
                         CaptureRequest::Release => {
                             capture.release().await?;
-                            server.state.replace(State::Receiving);
+                            server.set_state(State::Receiving);
                         }
                         CaptureRequest::Create(h, p) => capture.create(h, p).await?,
                         CaptureRequest::Destroy(h) => capture.destroy(h).await?,
@@ -125,15 +138,34 @@ async fn handle_capture_event(
     let (handle, event) = event;
     log::trace!("({handle}) {event:?}");
 
+    // do not enter clients that stopped responding
+    if event == CaptureEvent::Begin
+        && server.config.refuse_offline_clients
+        && server.get_presence(handle) == Presence::Offline
+    {
+        log::info!("client {handle} is offline, releasing capture");
+        // the client may be suspended, try to wake it up
+        let mac = server
+            .client_manager
+            .borrow()
+            .get(handle)
+            .filter(|(_, s)| !s.waking)
+            .and_then(|(c, _)| c.mac);
+        if let Some(mac) = mac {
+            server.wake_client(handle, mac);
+        }
+        capture.release().await?;
+        return Ok(());
+    }
+
     // capture started
     if event == CaptureEvent::Begin {
         // wait for remote to acknowlegde enter
-        server.set_state(State::AwaitAck);
+        // (the active client must be set first for the enter hook)
         server.set_active(Some(handle));
+        server.set_state(State::AwaitAck);
         // restart ping timer to release capture if unreachable
         server.restart_ping_timer();
-        // spawn enter hook cmd
-        spawn_hook_command(server, handle);
     }
 
     // release capture if emulation set state to Receiveing
@@ -148,6 +180,13 @@ async fn handle_capture_event(
         server.set_state(State::Receiving);
     }
 
+    // drop events the client does not want
+    if let CaptureEvent::Input(e) = &event {
+        if server.get_state() == State::Sending && !server.may_send(handle, e) {
+            return Ok(());
+        }
+    }
+
     if let Some(addr) = server.active_addr(handle) {
         let event = match server.get_state() {
             State::Sending => match event {
@@ -165,37 +204,6 @@ async fn handle_capture_event(
     Ok(())
 }
 
-fn spawn_hook_command(server: &Server, handle: ClientHandle) {
-    let Some(cmd) = server
-        .client_manager
-        .borrow()
-        .get(handle)
-        .and_then(|(c, _)| c.cmd.clone())
-    else {
-        return;
-    };
-    tokio::task::spawn_local(async move {
-        log::info!("spawning command!");
-        let mut child = match Command::new("sh").arg("-c").arg(cmd.as_str()).spawn() {
-            Ok(c) => c,
-            Err(e) => {
-                log::warn!("could not execute cmd: {e}");
-                return;
-            }
-        };
-        match child.wait().await {
-            Ok(s) => {
-                if s.success() {
-                    log::info!("{cmd} exited successfully");
-                } else {
-                    log::warn!("{cmd} exited with {s}");
-                }
-            }
-            Err(e) => log::warn!("{cmd}: {e}"),
-        }
-    });
-}
-
 fn to_capture_pos(pos: lan_mouse_ipc::Position) -> input_capture::Position {
     match pos {
         lan_mouse_ipc::Position::Left => input_capture::Position::Left,
@@ -0,0 +1,505 @@
+use lan_mouse_ipc::{FrontendEvent, FrontendReply, FrontendRequest};
+use local_channel::mpsc::Receiver;
+use tokio::{
+    sync::{mpsc::UnboundedSender, oneshot},
+    task::JoinHandle,
+};
+
+use super::Server;
+
+/// well-known name of the service on the session bus
+pub const DBUS_NAME: &str = "de.feschber.LanMouse";
+/// object path of the service
+pub const DBUS_PATH: &str = "/de/feschber/LanMouse";
+
+/// a request of a d-bus client and the channel for its reply
+pub(crate) type DbusRequest = (FrontendRequest, oneshot::Sender<FrontendReply>);
+
+pub(crate) fn new(
+    server: Server,
+    event_rx: Receiver<FrontendEvent>,
+    request_tx: UnboundedSender<DbusRequest>,
+) -> JoinHandle<()> {
+    tokio::task::spawn_local(async move {
+        if !server.config.dbus {
+            return;
+        }
+        tokio::select! {
+            _ = server.cancelled() => {}
+            r = dbus_task(&server, event_rx, request_tx) => if let Err(e) = r {
+                log::warn!("d-bus interface not available: {e}");
+            },
+        }
+    })
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+async fn dbus_task(
+    server: &Server,
+    mut event_rx: Receiver<FrontendEvent>,
+    request_tx: UnboundedSender<DbusRequest>,
+) -> zbus::Result<()> {
+    let conn = zbus::connection::Builder::session()?
+        .serve_at(DBUS_PATH, LanMouse::new(request_tx))?
+        .build()
+        .await?;
+    // e.g. another instance owns the name
+    match conn.request_name(DBUS_NAME).await {
+        Ok(()) => log::info!("d-bus interface available as {DBUS_NAME}"),
+        Err(e) => log::warn!(
+            "could not acquire {DBUS_NAME}: {e}, d-bus interface only available as {}",
+            conn.unique_name().map(|n| n.as_str()).unwrap_or_default()
+        ),
+    }
+    let iface = conn
+        .object_server()
+        .interface::<_, LanMouse>(DBUS_PATH)
+        .await?;
+    update_properties(server, &iface).await?;
+    while let Some(event) = event_rx.recv().await {
+        emit_signal(&iface, event).await?;
+        update_properties(server, &iface).await?;
+    }
+    Ok(())
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+async fn dbus_task(
+    _server: &Server,
+    _event_rx: Receiver<FrontendEvent>,
+    _request_tx: UnboundedSender<DbusRequest>,
+) -> Result<(), std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "not supported on this platform",
+    ))
+}
+
+/// handle, hostname, position, port, active, ips, presence
+#[cfg(all(unix, not(target_os = "macos")))]
+type ClientInfo = (u64, String, String, u16, bool, Vec<String>, String);
+
+/// hostname, ips, port
+#[cfg(all(unix, not(target_os = "macos")))]
+type PeerInfo = (String, Vec<String>, u16);
+
+/// state of the service exposed as properties
+#[cfg(all(unix, not(target_os = "macos")))]
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Properties {
+    clients: Vec<ClientInfo>,
+    peers: Vec<PeerInfo>,
+    port: u16,
+    capture_enabled: bool,
+    emulation_enabled: bool,
+    focus: String,
+    active_client: i64,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Properties {
+    fn new(server: &Server) -> Self {
+        let clients = server
+            .client_manager
+            .borrow()
+            .get_client_states()
+            .map(|(h, (c, s))| client_info(h, c, s))
+            .collect();
+        let mut peers: Vec<PeerInfo> = server
+            .discovered_peers
+            .borrow()
+            .values()
+            .map(|p| (p.hostname.clone(), to_strings(p.ips.iter()), p.port))
+            .collect();
+        peers.sort();
+        let focus = match server.state.get().into() {
+            lan_mouse_ipc::FocusState::Sending => "sending",
+            lan_mouse_ipc::FocusState::Receiving => "receiving",
+            lan_mouse_ipc::FocusState::AwaitAck => "await-ack",
+        };
+        Self {
+            clients,
+            peers,
+            port: server.port.get(),
+            capture_enabled: server.capture_status.get().into(),
+            emulation_enabled: server.emulation_status.get().into(),
+            focus: focus.into(),
+            active_client: server.active_client.get().map(|h| h as i64).unwrap_or(-1),
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn to_strings<'a>(ips: impl Iterator<Item = &'a std::net::IpAddr>) -> Vec<String> {
+    let mut ips: Vec<String> = ips.map(|ip| ip.to_string()).collect();
+    ips.sort();
+    ips
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn client_info(
+    handle: lan_mouse_ipc::ClientHandle,
+    config: &lan_mouse_ipc::ClientConfig,
+    state: &lan_mouse_ipc::ClientState,
+) -> ClientInfo {
+    (
+        handle,
+        config.hostname.clone().unwrap_or_default(),
+        config.pos.to_string(),
+        config.port,
+        state.active,
+        to_strings(state.ips.iter()),
+        state.presence.to_string(),
+    )
+}
+
+/// the `de.feschber.LanMouse` interface: frontend requests as methods,
+/// the state of the service as properties and frontend events as signals
+#[cfg(all(unix, not(target_os = "macos")))]
+struct LanMouse {
+    requests: UnboundedSender<DbusRequest>,
+    properties: Properties,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl LanMouse {
+    fn new(requests: UnboundedSender<DbusRequest>) -> Self {
+        Self {
+            requests,
+            properties: Default::default(),
+        }
+    }
+
+    /// send a request to the service and wait for its reply
+    async fn request(&self, request: FrontendRequest) -> zbus::fdo::Result<FrontendReply> {
+        let terminated = || zbus::fdo::Error::Failed("service terminated".into());
+        let (tx, rx) = oneshot::channel();
+        self.requests
+            .send((request, tx))
+            .map_err(|_| terminated())?;
+        match rx.await.map_err(|_| terminated())? {
+            FrontendReply::NoSuchClient(handle) => Err(zbus::fdo::Error::InvalidArgs(format!(
+                "no such client: {handle}"
+            ))),
+            FrontendReply::Error(e) => Err(zbus::fdo::Error::Failed(e)),
+            reply => Ok(reply),
+        }
+    }
+
+    async fn request_ok(&self, request: FrontendRequest) -> zbus::fdo::Result<()> {
+        self.request(request).await.map(|_| ())
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn unexpected(reply: FrontendReply) -> zbus::fdo::Error {
+    zbus::fdo::Error::Failed(format!("unexpected reply: {reply:?}"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn parse<T>(value: &str) -> zbus::fdo::Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e: T::Err| zbus::fdo::Error::InvalidArgs(e.to_string()))
+}
+
+/// empty strings are passed for unset values
+#[cfg(all(unix, not(target_os = "macos")))]
+fn parse_optional<T>(value: &str) -> zbus::fdo::Result<Option<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match value {
+        "" => Ok(None),
+        v => parse(v).map(Some),
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[zbus::interface(name = "de.feschber.LanMouse")]
+impl LanMouse {
+    async fn activate(&self, handle: u64, active: bool) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::Activate(handle, active))
+            .await
+    }
+
+    /// returns the handle of the new client
+    async fn create(&self) -> zbus::fdo::Result<u64> {
+        match self.request(FrontendRequest::Create).await? {
+            FrontendReply::Created(handle) => Ok(handle),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    async fn change_port(&self, port: u16) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::ChangePort(port)).await
+    }
+
+    async fn delete(&self, handle: u64) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::Delete(handle)).await
+    }
+
+    async fn enumerate(&self) -> zbus::fdo::Result<Vec<ClientInfo>> {
+        match self.request(FrontendRequest::Enumerate()).await? {
+            FrontendReply::Enumerate(clients) => Ok(clients
+                .iter()
+                .map(|(h, c, s)| client_info(*h, c, s))
+                .collect()),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    async fn resolve_dns(&self, handle: u64) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::ResolveDns(handle)).await
+    }
+
+    async fn update_hostname(&self, handle: u64, hostname: &str) -> zbus::fdo::Result<()> {
+        let hostname = Some(hostname.to_string()).filter(|h| !h.is_empty());
+        self.request_ok(FrontendRequest::UpdateHostname(handle, hostname))
+            .await
+    }
+
+    async fn update_port(&self, handle: u64, port: u16) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::UpdatePort(handle, port))
+            .await
+    }
+
+    async fn update_position(&self, handle: u64, position: &str) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::UpdatePosition(handle, parse(position)?))
+            .await
+    }
+
+    async fn update_fix_ips(&self, handle: u64, ips: Vec<String>) -> zbus::fdo::Result<()> {
+        let ips = ips.iter().map(|ip| parse(ip)).collect::<Result<_, _>>()?;
+        self.request_ok(FrontendRequest::UpdateFixIps(handle, ips))
+            .await
+    }
+
+    async fn update_transport(&self, handle: u64, transport: &str) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::UpdateTransport(handle, parse(transport)?))
+            .await
+    }
+
+    async fn update_liveness(
+        &self,
+        handle: u64,
+        ping_interval_ms: u64,
+        max_missed_pings: u32,
+        action: &str,
+    ) -> zbus::fdo::Result<()> {
+        let liveness = lan_mouse_ipc::Liveness {
+            ping_interval_ms,
+            max_missed_pings,
+            action: parse(action)?,
+        };
+        self.request_ok(FrontendRequest::UpdateLiveness(handle, liveness))
+            .await
+    }
+
+    async fn update_mac(&self, handle: u64, mac: &str) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::UpdateMac(handle, parse_optional(mac)?))
+            .await
+    }
+
+    async fn wake(&self, handle: u64) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::Wake(handle)).await
+    }
+
+    async fn update_send_filter(&self, handle: u64, classes: Vec<String>) -> zbus::fdo::Result<()> {
+        let filter = parse_filter(&classes)?;
+        self.request_ok(FrontendRequest::UpdateSendFilter(handle, filter))
+            .await
+    }
+
+    async fn update_receive_filter(
+        &self,
+        handle: u64,
+        classes: Vec<String>,
+    ) -> zbus::fdo::Result<()> {
+        let filter = parse_filter(&classes)?;
+        self.request_ok(FrontendRequest::UpdateReceiveFilter(handle, filter))
+            .await
+    }
+
+    async fn get_state(&self, handle: u64) -> zbus::fdo::Result<ClientInfo> {
+        match self.request(FrontendRequest::GetState(handle)).await? {
+            FrontendReply::State(h, c, s) => Ok(client_info(h, &c, &s)),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    async fn enable_capture(&self) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::EnableCapture).await
+    }
+
+    async fn enable_emulation(&self) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::EnableEmulation).await
+    }
+
+    async fn sync(&self) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::Sync).await
+    }
+
+    async fn add_discovered(&self, hostname: &str, position: &str) -> zbus::fdo::Result<()> {
+        self.request_ok(FrontendRequest::AddDiscovered(
+            hostname.into(),
+            parse(position)?,
+        ))
+        .await
+    }
+
+    /// handle, hostname, position, port, active, ips and presence of all clients
+    #[zbus(property)]
+    fn clients(&self) -> Vec<ClientInfo> {
+        self.properties.clients.clone()
+    }
+
+    /// hostname, ips and port of the peers discovered via mDNS
+    #[zbus(property)]
+    fn discovered_peers(&self) -> Vec<PeerInfo> {
+        self.properties.peers.clone()
+    }
+
+    #[zbus(property)]
+    fn port(&self) -> u16 {
+        self.properties.port
+    }
+
+    #[zbus(property)]
+    fn capture_enabled(&self) -> bool {
+        self.properties.capture_enabled
+    }
+
+    #[zbus(property)]
+    fn emulation_enabled(&self) -> bool {
+        self.properties.emulation_enabled
+    }
+
+    /// "sending", "receiving" or "await-ack"
+    #[zbus(property)]
+    fn focus(&self) -> String {
+        self.properties.focus.clone()
+    }
+
+    /// handle of the client most recently entered, -1 if none
+    #[zbus(property)]
+    fn active_client(&self) -> i64 {
+        self.properties.active_client
+    }
+
+    #[zbus(signal)]
+    async fn client_created(ctxt: &zbus::SignalContext<'_>, handle: u64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn client_changed(ctxt: &zbus::SignalContext<'_>, handle: u64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn client_deleted(ctxt: &zbus::SignalContext<'_>, handle: u64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn presence_changed(
+        ctxt: &zbus::SignalContext<'_>,
+        handle: u64,
+        presence: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn peer_discovered(
+        ctxt: &zbus::SignalContext<'_>,
+        hostname: &str,
+        ips: Vec<String>,
+        port: u16,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn peer_lost(ctxt: &zbus::SignalContext<'_>, hostname: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn error(ctxt: &zbus::SignalContext<'_>, message: &str) -> zbus::Result<()>;
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn parse_filter(classes: &[String]) -> zbus::fdo::Result<lan_mouse_ipc::EventFilter> {
+    classes
+        .iter()
+        .map(|c| parse::<lan_mouse_ipc::EventClass>(c))
+        .collect()
+}
+
+/// emit the signal corresponding to the event, state changes
+/// are reported through the properties
+#[cfg(all(unix, not(target_os = "macos")))]
+async fn emit_signal(
+    iface: &zbus::InterfaceRef<LanMouse>,
+    event: FrontendEvent,
+) -> zbus::Result<()> {
+    let ctxt = iface.signal_context();
+    match event {
+        FrontendEvent::Created(handle, _, _) => LanMouse::client_created(ctxt, handle).await,
+        FrontendEvent::Changed(handle) | FrontendEvent::State(handle, _, _) => {
+            LanMouse::client_changed(ctxt, handle).await
+        }
+        FrontendEvent::Deleted(handle) => LanMouse::client_deleted(ctxt, handle).await,
+        FrontendEvent::PresenceChanged(handle, presence) => {
+            LanMouse::presence_changed(ctxt, handle, &presence.to_string()).await
+        }
+        FrontendEvent::PortChanged(port, Some(msg)) => {
+            LanMouse::error(ctxt, &format!("could not bind port {port}: {msg}")).await
+        }
+        FrontendEvent::PeerDiscovered(peer) => {
+            let ips = peer.ips.iter().map(|ip| ip.to_string()).collect();
+            LanMouse::peer_discovered(ctxt, &peer.hostname, ips, peer.port).await
+        }
+        FrontendEvent::PeerLost(hostname) => LanMouse::peer_lost(ctxt, &hostname).await,
+        FrontendEvent::NoSuchClient(handle) => {
+            LanMouse::error(ctxt, &format!("no such client: {handle}")).await
+        }
+        FrontendEvent::Error(msg) => LanMouse::error(ctxt, &msg).await,
+        FrontendEvent::PortChanged(_, None)
+        | FrontendEvent::Enumerate(_)
+        | FrontendEvent::CaptureStatus(_)
+        | FrontendEvent::EmulationStatus(_)
+        | FrontendEvent::FocusChanged(_)
+        | FrontendEvent::ActiveClientChanged(_) => Ok(()),
+    }
+}
+
+/// update the properties from the state of the server,
+/// emitting `PropertiesChanged` for those that changed
+#[cfg(all(unix, not(target_os = "macos")))]
+async fn update_properties(
+    server: &Server,
+    iface: &zbus::InterfaceRef<LanMouse>,
+) -> zbus::Result<()> {
+    let new = Properties::new(server);
+    let ctxt = iface.signal_context();
+    let mut lan_mouse = iface.get_mut().await;
+    let old = std::mem::replace(&mut lan_mouse.properties, new.clone());
+    if old.clients != new.clients {
+        lan_mouse.clients_changed(ctxt).await?;
+    }
+    if old.peers != new.peers {
+        lan_mouse.discovered_peers_changed(ctxt).await?;
+    }
+    if old.port != new.port {
+        lan_mouse.port_changed(ctxt).await?;
+    }
+    if old.capture_enabled != new.capture_enabled {
+        lan_mouse.capture_enabled_changed(ctxt).await?;
+    }
+    if old.emulation_enabled != new.emulation_enabled {
+        lan_mouse.emulation_enabled_changed(ctxt).await?;
+    }
+    if old.focus != new.focus {
+        lan_mouse.focus_changed(ctxt).await?;
+    }
+    if old.active_client != new.active_client {
+        lan_mouse.active_client_changed(ctxt).await?;
+    }
+    Ok(())
+}
@@ -133,22 +133,31 @@ async fn handle_incoming_event(
     else {
         return Ok(());
     };
+    server.client_seen(handle);
 
     match (event, addr) {
         (ProtoEvent::Pong, _) => { /* ignore pong events */ }
         (ProtoEvent::Ping, addr) => {
             let _ = sender_tx.send((ProtoEvent::Pong, addr));
         }
-        (ProtoEvent::Leave(_), _) => emulate.release_keys(handle).await?,
+        (ProtoEvent::Leave(_), _) => {
+            server.stopped_receiving_from(handle);
+            emulate.release_keys(handle).await?
+        }
         (ProtoEvent::Ack(_), _) => server.set_state(State::Sending),
+        (ProtoEvent::Lock, _) => server.request_lock(handle, true),
+        (ProtoEvent::Unlock, _) => server.request_lock(handle, false),
         (ProtoEvent::Enter(_), _) => {
             server.set_state(State::Receiving);
+            server.set_receiving_from(Some(handle));
             sender_tx
                 .send((ProtoEvent::Ack(0), addr))
                 .expect("no channel")
         }
         (ProtoEvent::Input(e), _) => {
-            if let State::Receiving = server.get_state() {
+            if !server.may_receive(handle, &e) {
+                log::trace!("{event} => filtered");
+            } else if let State::Receiving = server.get_state() {
                 log::trace!("{event} => emulate");
                 emulate.consume(e, handle).await?;
                 let has_pressed_keys = emulate.has_pressed_keys(handle);
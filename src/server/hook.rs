@@ -0,0 +1,148 @@
+use std::{fmt::Display, io, process::ExitStatus, time::Duration};
+
+use tokio::process::{Child, Command};
+
+use lan_mouse_ipc::ClientHandle;
+
+use crate::config::HookKillPolicy;
+
+use super::Server;
+
+/// time a hook command has to exit after SIGTERM before it is killed
+const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// events a hook command can be run on
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum HookEvent {
+    /// the pointer entered the client
+    Enter,
+    /// the pointer left the client
+    Leave,
+    /// the client started responding to heartbeats
+    Online,
+    /// the client stopped responding to heartbeats
+    Offline,
+    /// the client started sending events
+    ReceiveStart,
+    /// the client stopped sending events
+    ReceiveStop,
+}
+
+impl Display for HookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookEvent::Enter => write!(f, "enter"),
+            HookEvent::Leave => write!(f, "leave"),
+            HookEvent::Online => write!(f, "online"),
+            HookEvent::Offline => write!(f, "offline"),
+            HookEvent::ReceiveStart => write!(f, "receive-start"),
+            HookEvent::ReceiveStop => write!(f, "receive-stop"),
+        }
+    }
+}
+
+/// run the hook command configured for `event` of the given client, if any
+pub(crate) fn spawn(server: &Server, handle: ClientHandle, event: HookEvent) {
+    let Some((config, state)) = server.client_manager.borrow().get(handle).cloned() else {
+        return;
+    };
+    let hooks = config.hooks;
+    let Some(cmd) = (match event {
+        HookEvent::Enter => hooks.enter,
+        HookEvent::Leave => hooks.leave,
+        HookEvent::Online => hooks.online,
+        HookEvent::Offline => hooks.offline,
+        HookEvent::ReceiveStart => hooks.receive_start,
+        HookEvent::ReceiveStop => hooks.receive_stop,
+    }) else {
+        return;
+    };
+
+    let (shell, args) = server
+        .config
+        .hook_shell
+        .split_first()
+        .expect("no hook shell");
+    let mut command = Command::new(shell);
+    command
+        .args(args)
+        .arg(&cmd)
+        .env("LAN_MOUSE_EVENT", event.to_string())
+        .env("LAN_MOUSE_CLIENT_HANDLE", handle.to_string())
+        .env(
+            "LAN_MOUSE_CLIENT_HOSTNAME",
+            config.hostname.unwrap_or_default(),
+        )
+        .env("LAN_MOUSE_CLIENT_POSITION", config.pos.to_string())
+        .env(
+            "LAN_MOUSE_CLIENT_ADDR",
+            state
+                .active_addr
+                .map(|addr| addr.to_string())
+                .unwrap_or_default(),
+        );
+
+    let timeout = server.config.hook_timeout;
+    let kill_policy = server.config.hook_kill_policy;
+    tokio::task::spawn_local(async move {
+        log::info!("({handle}) running {event} hook: {cmd}");
+        let mut child = match command.spawn() {
+            Ok(c) => c,
+            Err(e) => {
+                log::warn!("could not execute cmd: {e}");
+                return;
+            }
+        };
+        let status = match timeout {
+            None => child.wait().await,
+            Some(timeout) => match tokio::time::timeout(timeout, child.wait()).await {
+                Ok(status) => status,
+                Err(_) => {
+                    log::warn!("{cmd} did not exit after {timeout:?} ({kill_policy:?})");
+                    stop(&mut child, kill_policy).await
+                }
+            },
+        };
+        match status {
+            Ok(s) => {
+                if s.success() {
+                    log::info!("{cmd} exited successfully");
+                } else {
+                    log::warn!("{cmd} exited with {s}");
+                }
+            }
+            Err(e) => log::warn!("{cmd}: {e}"),
+        }
+    });
+}
+
+/// stop a hook command that exceeded its timeout according to `policy`
+async fn stop(child: &mut Child, policy: HookKillPolicy) -> io::Result<ExitStatus> {
+    match policy {
+        HookKillPolicy::Detach => {}
+        HookKillPolicy::Kill => child.kill().await?,
+        HookKillPolicy::Terminate => {
+            terminate(child);
+            if let Ok(status) = tokio::time::timeout(TERMINATE_GRACE_PERIOD, child.wait()).await {
+                return status;
+            }
+            child.kill().await?;
+        }
+    }
+    child.wait().await
+}
+
+#[cfg(unix)]
+fn terminate(child: &mut Child) {
+    if let Some(pid) = child.id() {
+        // SAFETY: pid belongs to a child that has not been reaped yet
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGTERM);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate(child: &mut Child) {
+    let _ = child.start_kill();
+}
@@ -1,37 +1,69 @@
-use local_channel::mpsc::{Receiver, Sender};
-use std::{io, net::SocketAddr};
+use local_channel::mpsc::{channel, Receiver, Sender};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use thiserror::Error;
-use tokio::{net::UdpSocket, task::JoinHandle};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpSocket, TcpStream, UdpSocket},
+    task::JoinHandle,
+};
 
 use super::Server;
-use lan_mouse_proto::{ProtoEvent, ProtocolError};
+use lan_mouse_ipc::Transport;
+use lan_mouse_proto::{ProtoEvent, ProtocolError, MAX_EVENT_SIZE};
 
-pub(crate) async fn new(
+/// minimum time between two connection attempts to the same address
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// time after which a connection attempt is given up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub(crate) fn new(
     server: Server,
+    udp: Option<std::net::UdpSocket>,
     udp_recv_tx: Sender<Result<(ProtoEvent, SocketAddr), NetworkError>>,
     udp_send_rx: Receiver<(ProtoEvent, SocketAddr)>,
 ) -> io::Result<JoinHandle<()>> {
-    // bind the udp socket
-    let listen_addr = SocketAddr::new("0.0.0.0".parse().unwrap(), server.port.get());
-    let mut socket = UdpSocket::bind(listen_addr).await?;
+    // bind the udp socket and tcp listener
+    let (mut socket, mut listener) = match udp {
+        Some(udp) => from_std(&server, udp)?,
+        None => bind(&server, server.port.get())?,
+    };
+    let scope_id = scope_id(&server);
+    let tcp = Rc::new(TcpTransport::new(
+        server.clone(),
+        udp_recv_tx.clone(),
+        scope_id,
+    ));
 
     Ok(tokio::task::spawn_local(async move {
         let mut sender_rx = udp_send_rx;
         loop {
             let udp_receiver = udp_receiver(&socket, &udp_recv_tx);
-            let udp_sender = udp_sender(&socket, &mut sender_rx);
+            let sender = sender(&socket, &tcp, &mut sender_rx, scope_id);
+            let tcp_acceptor = tcp_acceptor(listener.as_ref(), &tcp);
             tokio::select! {
                 _ = udp_receiver => break, /* channel closed */
-                _ = udp_sender => break, /* channel closed */
-                _ = server.notifies.port_changed.notified() => update_port(&server, &mut socket).await,
+                _ = sender => break, /* channel closed */
+                _ = tcp_acceptor => {},
+                _ = server.notifies.port_changed.notified() => {
+                    update_port(&server, &mut socket, &mut listener)
+                }
                 _ = server.cancelled() => break, /* cancellation requested */
             }
         }
     }))
 }
 
-async fn update_port(server: &Server, socket: &mut UdpSocket) {
+fn update_port(server: &Server, socket: &mut UdpSocket, listener: &mut Option<TcpListener>) {
     let new_port = server.port.get();
     let current_port = socket.local_addr().expect("socket not bound").port();
 
@@ -41,11 +73,11 @@ async fn update_port(server: &Server, socket: &mut UdpSocket) {
     }
 
     // bind new socket
-    let listen_addr = SocketAddr::new("0.0.0.0".parse().unwrap(), new_port);
-    let new_socket = UdpSocket::bind(listen_addr).await;
+    let new_socket = bind(server, new_port);
     let err = match new_socket {
-        Ok(new_socket) => {
+        Ok((new_socket, new_listener)) => {
             *socket = new_socket;
+            *listener = new_listener;
             None
         }
         Err(e) => Some(e.to_string()),
@@ -56,6 +88,130 @@ async fn update_port(server: &Server, socket: &mut UdpSocket) {
     server.notify_port_changed(port, err);
 }
 
+/// bind a udp socket and a tcp listener to the configured address and interface,
+/// by default dual-stack sockets listening on all interfaces
+fn bind(server: &Server, port: u16) -> io::Result<(UdpSocket, Option<TcpListener>)> {
+    let config = &server.config;
+    let Some(ip) = config.bind_addr else {
+        return match bind_to(server, SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port)) {
+            Ok(sockets) => Ok(sockets),
+            Err(e) => {
+                log::warn!("could not create dual-stack socket ({e}), falling back to ipv4");
+                bind_to(server, SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port))
+            }
+        };
+    };
+    let addr = with_scope_id(SocketAddr::new(ip, port), scope_id(server));
+    bind_to(server, addr)
+}
+
+fn bind_to(server: &Server, addr: SocketAddr) -> io::Result<(UdpSocket, Option<TcpListener>)> {
+    let udp = new_socket(server, addr, Type::DGRAM, Protocol::UDP)?;
+    udp.bind(&addr.into())?;
+    let tcp = bind_tcp(server, addr);
+    log::info!("listening on {addr}");
+    Ok((UdpSocket::from_std(udp.into())?, tcp))
+}
+
+/// use the udp socket passed by systemd, the tcp listener is bound to the same address
+fn from_std(
+    server: &Server,
+    udp: std::net::UdpSocket,
+) -> io::Result<(UdpSocket, Option<TcpListener>)> {
+    let addr = udp.local_addr()?;
+    udp.set_nonblocking(true)?;
+    server.port.set(addr.port());
+    let tcp = bind_tcp(server, addr);
+    log::info!("listening on {addr}");
+    Ok((UdpSocket::from_std(udp)?, tcp))
+}
+
+/// peers can not connect via tcp if this fails, events are still received over udp
+fn bind_tcp(server: &Server, addr: SocketAddr) -> Option<TcpListener> {
+    let bind = || {
+        let tcp = new_socket(server, addr, Type::STREAM, Protocol::TCP)?;
+        tcp.set_reuse_address(true)?;
+        tcp.bind(&addr.into())?;
+        tcp.listen(128)?;
+        TcpListener::from_std(tcp.into())
+    };
+    match bind() {
+        Ok(listener) => Some(listener),
+        Err(e) => {
+            log::warn!("could not listen for tcp connections on {addr}: {e}");
+            None
+        }
+    }
+}
+
+fn new_socket(server: &Server, addr: SocketAddr, ty: Type, proto: Protocol) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(addr), ty, Some(proto))?;
+    if addr.is_ipv6() && addr.ip().is_unspecified() {
+        socket.set_only_v6(false)?;
+    }
+    if let Some(interface) = server.config.interface.as_deref() {
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        socket.bind_device(Some(interface.as_bytes()))?;
+        #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+        log::warn!("binding to interface {interface} is not supported on this platform");
+    }
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// scope id used for link-local ipv6 addresses that do not specify one
+fn scope_id(server: &Server) -> u32 {
+    #[cfg(unix)]
+    if let Some(interface) = server.config.interface.as_deref() {
+        let Ok(name) = std::ffi::CString::new(interface) else {
+            return 0;
+        };
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            log::warn!("{interface}: {}", io::Error::last_os_error());
+        }
+        return index;
+    }
+    #[cfg(not(unix))]
+    let _ = server;
+    0
+}
+
+/// adapt the destination address to the address family of the socket,
+/// returns `None` if the socket can not reach the address
+fn to_socket_family(socket: &UdpSocket, addr: SocketAddr, scope_id: u32) -> Option<SocketAddr> {
+    let local_addr = socket.local_addr().ok()?;
+    match (local_addr, addr) {
+        (SocketAddr::V6(_), SocketAddr::V4(a)) => Some(SocketAddr::new(
+            IpAddr::V6(a.ip().to_ipv6_mapped()),
+            a.port(),
+        )),
+        (SocketAddr::V4(_), SocketAddr::V6(_)) => None,
+        (_, addr) => Some(with_scope_id(addr, scope_id)),
+    }
+}
+
+/// link-local ipv6 addresses need a scope id to be usable
+fn with_scope_id(mut addr: SocketAddr, scope_id: u32) -> SocketAddr {
+    if let SocketAddr::V6(a) = &mut addr {
+        if a.scope_id() == 0 && a.ip().is_unicast_link_local() {
+            a.set_scope_id(scope_id);
+        }
+    }
+    addr
+}
+
+/// ipv4 peers show up as ipv4-mapped addresses on a dual-stack socket
+fn to_canonical(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V6(a) => match a.ip().to_ipv4_mapped() {
+            Some(ip) => SocketAddr::new(ip.into(), a.port()),
+            None => addr,
+        },
+        addr => addr,
+    }
+}
+
 async fn udp_receiver(
     socket: &UdpSocket,
     receiver_tx: &Sender<Result<(ProtoEvent, SocketAddr), NetworkError>>,
@@ -66,15 +222,276 @@ async fn udp_receiver(
     }
 }
 
-async fn udp_sender(socket: &UdpSocket, rx: &mut Receiver<(ProtoEvent, SocketAddr)>) {
+/// send events over tcp if the destination uses tcp, over udp otherwise
+async fn sender(
+    socket: &UdpSocket,
+    tcp: &Rc<TcpTransport>,
+    rx: &mut Receiver<(ProtoEvent, SocketAddr)>,
+    scope_id: u32,
+) {
     loop {
         let (event, addr) = rx.recv().await.expect("channel closed");
+        if tcp.send(event, addr) {
+            continue;
+        }
+        let Some(addr) = to_socket_family(socket, addr, scope_id) else {
+            log::trace!("{addr} is not reachable from an ipv4 socket");
+            continue;
+        };
         if let Err(e) = send_event(socket, event, addr) {
             log::warn!("udp send failed: {e}");
         };
     }
 }
 
+async fn tcp_acceptor(listener: Option<&TcpListener>, tcp: &Rc<TcpTransport>) {
+    let Some(listener) = listener else {
+        return std::future::pending().await;
+    };
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => tcp.accept(stream, to_canonical(addr)),
+            Err(e) => log::warn!("tcp accept failed: {e}"),
+        }
+    }
+}
+
+/// persistent tcp connections, for clients using [`Transport::Tcp`]
+/// and peers that connected via tcp
+struct TcpTransport {
+    server: Server,
+    /// open connections by remote address
+    connections: RefCell<HashMap<SocketAddr, Connection>>,
+    next_id: Cell<u64>,
+    /// time of the last connection attempt by remote address
+    last_attempt: RefCell<HashMap<SocketAddr, Instant>>,
+    recv_tx: Sender<Result<(ProtoEvent, SocketAddr), NetworkError>>,
+    scope_id: u32,
+}
+
+/// a tcp connection to a peer
+struct Connection {
+    /// tells replaced connections apart
+    id: u64,
+    /// initiated by this device
+    outbound: bool,
+    /// event queue of the connection
+    tx: Sender<ProtoEvent>,
+}
+
+impl TcpTransport {
+    fn new(
+        server: Server,
+        recv_tx: Sender<Result<(ProtoEvent, SocketAddr), NetworkError>>,
+        scope_id: u32,
+    ) -> Self {
+        Self {
+            server,
+            connections: Default::default(),
+            next_id: Default::default(),
+            last_attempt: Default::default(),
+            recv_tx,
+            scope_id,
+        }
+    }
+
+    /// queue the event on a tcp connection to `addr`, connecting if necessary.
+    /// returns false if `addr` does not use tcp
+    fn send(self: &Rc<Self>, event: ProtoEvent, addr: SocketAddr) -> bool {
+        if let Some(connection) = self.connections.borrow().get(&addr) {
+            let _ = connection.tx.send(event);
+            return true;
+        }
+        if !self.uses_tcp(addr) {
+            return false;
+        }
+        let now = Instant::now();
+        let last_attempt = self.last_attempt.borrow().get(&addr).copied();
+        if last_attempt.is_some_and(|t| now.duration_since(t) < RECONNECT_INTERVAL) {
+            log::trace!("{addr}: waiting before reconnecting, dropping {event}");
+            return true;
+        }
+        self.last_attempt.borrow_mut().insert(addr, now);
+
+        let (id, rx) = self.register(addr, true);
+        if let Some(connection) = self.connections.borrow().get(&addr) {
+            let _ = connection.tx.send(event);
+        }
+        let this = self.clone();
+        tokio::task::spawn_local(async move {
+            tokio::select! {
+                _ = this.server.cancelled() => {}
+                _ = this.connect(addr, rx) => {}
+            }
+            this.unregister(addr, id);
+        });
+        true
+    }
+
+    /// handle a connection initiated by a peer
+    fn accept(self: &Rc<Self>, stream: TcpStream, peer_addr: SocketAddr) {
+        log::info!("accepted tcp connection from {peer_addr}");
+        // the stream comes from an ephemeral port, events are sent to
+        // and received from the port configured for the client instead
+        let addr = self.client_addr(peer_addr).unwrap_or(peer_addr);
+        // both peers may connect at the same time:
+        // the one with the lower address keeps its outbound connection
+        let outbound = self
+            .connections
+            .borrow()
+            .get(&addr)
+            .is_some_and(|c| c.outbound);
+        let local_addr = stream.local_addr().map(to_canonical);
+        if outbound && local_addr.is_ok_and(|local| local < addr) {
+            log::info!("{addr}: keeping outbound tcp connection, closing {peer_addr}");
+            return;
+        }
+        let (id, rx) = self.register(addr, false);
+        let this = self.clone();
+        tokio::task::spawn_local(async move {
+            tokio::select! {
+                _ = this.server.cancelled() => {}
+                r = this.handle_stream(stream, addr, rx) => {
+                    log::info!("tcp connection from {peer_addr} closed: {r:?}");
+                }
+            }
+            this.unregister(addr, id);
+        });
+    }
+
+    /// route events for `addr` to a new connection, replacing a previous one
+    fn register(&self, addr: SocketAddr, outbound: bool) -> (u64, Receiver<ProtoEvent>) {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let (tx, rx) = channel();
+        let connection = Connection { id, outbound, tx };
+        self.connections.borrow_mut().insert(addr, connection);
+        (id, rx)
+    }
+
+    fn unregister(&self, addr: SocketAddr, id: u64) {
+        let mut connections = self.connections.borrow_mut();
+        if connections.get(&addr).is_some_and(|c| c.id == id) {
+            connections.remove(&addr);
+        }
+    }
+
+    /// address of the client connecting from `peer_addr`
+    fn client_addr(&self, peer_addr: SocketAddr) -> Option<SocketAddr> {
+        self.server
+            .client_manager
+            .borrow()
+            .get_client_states()
+            .find(|(_, (_, s))| s.ips.contains(&peer_addr.ip()))
+            .map(|(_, (c, _))| SocketAddr::new(peer_addr.ip(), c.port))
+    }
+
+    fn uses_tcp(&self, addr: SocketAddr) -> bool {
+        self.server
+            .client_manager
+            .borrow()
+            .get_client_states()
+            .any(|(_, (c, s))| {
+                c.transport == Transport::Tcp && c.port == addr.port() && s.ips.contains(&addr.ip())
+            })
+    }
+
+    async fn connect(&self, addr: SocketAddr, rx: Receiver<ProtoEvent>) {
+        log::info!("connecting to {addr} via tcp ...");
+        let stream = match tokio::time::timeout(CONNECT_TIMEOUT, self.open(addr)).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => {
+                log::warn!("could not connect to {addr}: {e}");
+                return;
+            }
+            Err(_) => {
+                log::warn!("could not connect to {addr}: timed out");
+                return;
+            }
+        };
+        log::info!("connected to {addr} via tcp");
+        let r = self.handle_stream(stream, addr, rx).await;
+        log::info!("tcp connection to {addr} closed: {r:?}");
+    }
+
+    async fn open(&self, addr: SocketAddr) -> io::Result<TcpStream> {
+        let addr = with_scope_id(addr, self.scope_id);
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        let config = &self.server.config;
+        if let Some(ip) = config.bind_addr {
+            if ip.is_ipv4() == addr.is_ipv4() {
+                socket.bind(with_scope_id(SocketAddr::new(ip, 0), self.scope_id))?;
+            }
+        }
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        if let Some(interface) = config.interface.as_deref() {
+            socket.bind_device(Some(interface.as_bytes()))?;
+        }
+        socket.connect(addr).await
+    }
+
+    /// forward events between the stream and the network task
+    /// until the connection is closed
+    async fn handle_stream(
+        &self,
+        stream: TcpStream,
+        addr: SocketAddr,
+        mut rx: Receiver<ProtoEvent>,
+    ) -> Result<(), NetworkError> {
+        stream.set_nodelay(true)?;
+        let (mut reader, mut writer) = stream.into_split();
+        let receiver = async {
+            loop {
+                let event = read_frame(&mut reader).await?;
+                self.recv_tx
+                    .send(Ok((event, addr)))
+                    .expect("channel closed");
+            }
+        };
+        let sender = async {
+            while let Some(event) = rx.recv().await {
+                log::trace!("{:20} ------>->->-> {addr} (tcp)", event.to_string());
+                write_frame(&mut writer, event).await?;
+            }
+            Ok(())
+        };
+        tokio::select! {
+            r = receiver => r,
+            r = sender => r,
+        }
+    }
+}
+
+/// events on a stream are prefixed with their length
+async fn read_frame(reader: &mut (impl AsyncReadExt + Unpin)) -> Result<ProtoEvent, NetworkError> {
+    let len = reader.read_u8().await? as usize;
+    if len == 0 || len > MAX_EVENT_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid frame length: {len}"),
+        )
+        .into());
+    }
+    let mut buf = [0u8; MAX_EVENT_SIZE];
+    reader.read_exact(&mut buf[..len]).await?;
+    Ok(ProtoEvent::try_from(buf)?)
+}
+
+async fn write_frame(
+    writer: &mut (impl AsyncWriteExt + Unpin),
+    event: ProtoEvent,
+) -> Result<(), NetworkError> {
+    let (data, len): ([u8; MAX_EVENT_SIZE], usize) = event.into();
+    let mut frame = [0u8; MAX_EVENT_SIZE + 1];
+    frame[0] = len as u8;
+    frame[1..=len].copy_from_slice(&data[..len]);
+    writer.write_all(&frame[..=len]).await?;
+    Ok(())
+}
+
 #[derive(Debug, Error)]
 pub(crate) enum NetworkError {
     #[error(transparent)]
@@ -84,16 +501,99 @@ pub(crate) enum NetworkError {
 }
 
 async fn receive_event(socket: &UdpSocket) -> Result<(ProtoEvent, SocketAddr), NetworkError> {
-    let mut buf = [0u8; lan_mouse_proto::MAX_EVENT_SIZE];
+    let mut buf = [0u8; MAX_EVENT_SIZE];
     let (_len, src) = socket.recv_from(&mut buf).await?;
     let event = ProtoEvent::try_from(buf)?;
-    Ok((event, src))
+    Ok((event, to_canonical(src)))
 }
 
 fn send_event(sock: &UdpSocket, e: ProtoEvent, addr: SocketAddr) -> Result<usize, NetworkError> {
     log::trace!("{:20} ------>->->-> {addr}", e.to_string());
-    let (data, len): ([u8; lan_mouse_proto::MAX_EVENT_SIZE], usize) = e.into();
+    let (data, len): ([u8; MAX_EVENT_SIZE], usize) = e.into();
     // When udp blocks, we dont want to block the event loop.
     // Dropping events is better than potentially crashing the input capture.
     Ok(sock.try_send_to(&data[..len], addr)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::collections::HashSet;
+
+    type Events = Receiver<Result<(ProtoEvent, SocketAddr), NetworkError>>;
+
+    /// a transport with a single tcp client at `peer_port` on localhost
+    fn transport(port: u16, peer_port: u16) -> (Rc<TcpTransport>, Events) {
+        let config = Config::from_args([
+            "lan-mouse",
+            "--config",
+            "/nonexistent/config.toml",
+            "--port",
+            &port.to_string(),
+        ])
+        .unwrap();
+        let server = Server::new(config, Default::default());
+        {
+            let mut client_manager = server.client_manager.borrow_mut();
+            let handle = client_manager.add_client();
+            let (c, s) = client_manager.get_mut(handle).unwrap();
+            c.port = peer_port;
+            c.transport = Transport::Tcp;
+            s.ips = HashSet::from([IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        }
+        let (tx, rx) = channel();
+        (Rc::new(TcpTransport::new(server, tx, 0)), rx)
+    }
+
+    async fn drain(rx: &mut Events, from: SocketAddr) -> usize {
+        let mut count = 0;
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_millis(200), rx.recv()).await
+        {
+            let (event, addr) = event.unwrap();
+            assert!(matches!(event, ProtoEvent::Ping));
+            assert_eq!(addr, from);
+            count += 1;
+        }
+        count
+    }
+
+    #[tokio::test]
+    async fn simultaneous_connects() {
+        let local = tokio::task::LocalSet::new();
+        local
+            .run_until(async {
+                let listeners = [
+                    TcpListener::bind("127.0.0.1:0").await.unwrap(),
+                    TcpListener::bind("127.0.0.1:0").await.unwrap(),
+                ];
+                let [addr_a, addr_b] = listeners.each_ref().map(|l| l.local_addr().unwrap());
+                let (a, mut a_rx) = transport(addr_a.port(), addr_b.port());
+                let (b, mut b_rx) = transport(addr_b.port(), addr_a.port());
+                for (listener, transport) in listeners.into_iter().zip([a.clone(), b.clone()]) {
+                    tokio::task::spawn_local(async move {
+                        tcp_acceptor(Some(&listener), &transport).await
+                    });
+                }
+
+                // both peers connect at the same time
+                assert!(a.send(ProtoEvent::Ping, addr_b));
+                assert!(b.send(ProtoEvent::Ping, addr_a));
+                drain(&mut a_rx, addr_b).await;
+                drain(&mut b_rx, addr_a).await;
+
+                // a single connection remains and carries events in both directions
+                let outbound = |t: &TcpTransport, addr| t.connections.borrow()[&addr].outbound;
+                assert_ne!(outbound(&a, addr_b), outbound(&b, addr_a));
+                for _ in 0..10 {
+                    assert!(a.send(ProtoEvent::Ping, addr_b));
+                    assert!(b.send(ProtoEvent::Ping, addr_a));
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                assert_eq!(drain(&mut b_rx, addr_a).await, 10);
+                assert_eq!(drain(&mut a_rx, addr_b).await, 10);
+            })
+            .await;
+    }
+}
@@ -0,0 +1,119 @@
+use tokio::task::JoinHandle;
+
+use lan_mouse_ipc::ClientHandle;
+
+use super::Server;
+
+/// events that are worth a desktop notification
+#[derive(Clone, Debug)]
+pub(crate) enum Notification {
+    /// the active client did not respond and the pointer was released
+    ClientUnreachable(ClientHandle),
+    /// a client with pressed keys did not respond and its keys were released
+    KeysReleased(ClientHandle),
+    /// a client did not respond but was configured to be kept
+    ClientNotResponding(ClientHandle),
+    /// input capture exited with an error
+    CaptureFailed(String),
+    /// the requested port could not be bound
+    PortBindFailed(u16, String),
+}
+
+pub(crate) fn new(server: Server) -> JoinHandle<()> {
+    tokio::task::spawn_local(async move {
+        tokio::select! {
+            _ = server.cancelled() => {}
+            _ = notification_task(&server) => {}
+        }
+    })
+}
+
+async fn notification_task(server: &Server) {
+    let mut notifier = Notifier::default();
+    loop {
+        server.notification_pending().await;
+        while let Some(notification) = {
+            /* need to drop borrow before next iteration! */
+            let notification = server.pending_notifications.borrow_mut().pop_front();
+            notification
+        } {
+            let (summary, body) = describe(server, notification);
+            if let Err(e) = notifier.show(&summary, &body).await {
+                log::warn!("could not show notification: {e}");
+            }
+        }
+    }
+}
+
+fn describe(server: &Server, notification: Notification) -> (String, String) {
+    let hostname = |handle| {
+        server
+            .get_hostname(handle)
+            .unwrap_or_else(|| format!("client {handle}"))
+    };
+    match notification {
+        Notification::ClientUnreachable(handle) => (
+            format!("{} is unreachable", hostname(handle)),
+            "the device stopped responding, the pointer was released".into(),
+        ),
+        Notification::KeysReleased(handle) => (
+            format!("{} is unreachable", hostname(handle)),
+            "the device stopped responding, pressed keys were released".into(),
+        ),
+        Notification::ClientNotResponding(handle) => (
+            format!("{} is not responding", hostname(handle)),
+            "the device stopped responding".into(),
+        ),
+        Notification::CaptureFailed(e) => ("input capture failed".into(), e),
+        Notification::PortBindFailed(port, e) => (format!("could not bind port {port}"), e),
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[derive(Default)]
+struct Notifier {
+    conn: Option<zbus::Connection>,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Notifier {
+    /// send the notification to org.freedesktop.Notifications
+    async fn show(&mut self, summary: &str, body: &str) -> zbus::Result<()> {
+        let conn = match self.conn.as_ref() {
+            Some(conn) => conn,
+            None => self.conn.insert(zbus::Connection::session().await?),
+        };
+        let actions: Vec<&str> = vec![];
+        let hints: std::collections::HashMap<&str, zbus::zvariant::Value> = Default::default();
+        conn.call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "Notify",
+            &(
+                "Lan Mouse",
+                0u32,
+                "de.feschber.LanMouse",
+                summary,
+                body,
+                actions,
+                hints,
+                -1i32,
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+#[derive(Default)]
+struct Notifier;
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+impl Notifier {
+    async fn show(&mut self, summary: &str, body: &str) -> Result<(), std::convert::Infallible> {
+        log::info!("{summary}: {body}");
+        Ok(())
+    }
+}
@@ -1,81 +1,78 @@
-use std::{net::SocketAddr, time::Duration};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 
 use lan_mouse_proto::ProtoEvent;
 use local_channel::mpsc::Sender;
 use tokio::task::JoinHandle;
 
-use lan_mouse_ipc::ClientHandle;
+use lan_mouse_ipc::{ClientHandle, Liveness, LivenessAction, MacAddress};
 
-use super::{capture_task::CaptureRequest, emulation_task::EmulationRequest, Server, State};
+use super::{
+    capture_task::CaptureRequest, emulation_task::EmulationRequest, Notification, Server, State,
+};
 
-const MAX_RESPONSE_TIME: Duration = Duration::from_millis(500);
+/// lower bound for the ping interval of a client
+const MIN_PING_INTERVAL: Duration = Duration::from_millis(50);
 
 pub(crate) fn new(
     server: Server,
     sender_ch: Sender<(ProtoEvent, SocketAddr)>,
     emulate_notify: Sender<EmulationRequest>,
     capture_notify: Sender<CaptureRequest>,
+    dns_notify: Sender<ClientHandle>,
 ) -> JoinHandle<()> {
     // timer task
     tokio::task::spawn_local(async move {
         tokio::select! {
             _ = server.notifies.cancel.cancelled() => {}
-            _ = ping_task(&server, sender_ch, emulate_notify, capture_notify) => {}
+            _ = ping_task(&server, sender_ch, emulate_notify, capture_notify, dns_notify) => {}
         }
     })
 }
 
+/// ping state of a single client
+struct Pinged {
+    /// the answer to the last ping is due at this point
+    due: Instant,
+    /// consecutive pings the client did not answer
+    missed: u32,
+}
+
 async fn ping_task(
     server: &Server,
     sender_ch: Sender<(ProtoEvent, SocketAddr)>,
     emulate_notify: Sender<EmulationRequest>,
     capture_notify: Sender<CaptureRequest>,
+    dns_notify: Sender<ClientHandle>,
 ) {
     loop {
         // wait for wake up signal
         server.ping_timer_notified().await;
+        let mut pinged: HashMap<ClientHandle, Pinged> = HashMap::new();
         loop {
             let receiving = server.state.get() == State::Receiving;
-            let (ping_clients, ping_addrs) = {
-                let mut client_manager = server.client_manager.borrow_mut();
-
-                let ping_clients: Vec<ClientHandle> = if receiving {
+            let awaiting_ack = server.state.get() == State::AwaitAck;
+            let ping_clients: Vec<(ClientHandle, Liveness, Option<MacAddress>)> = {
+                let client_manager = server.client_manager.borrow();
+                if receiving {
                     // if receiving we care about clients with pressed keys
                     client_manager
                         .get_client_states()
                         .filter(|(_, (_, s))| s.has_pressed_keys)
-                        .map(|(h, _)| h)
+                        .map(|(h, (c, _))| (h, c.liveness, c.mac))
                         .collect()
                 } else {
                     // if sending we care about the active client
-                    server.active_client.get().iter().cloned().collect()
-                };
-
-                // get relevant socket addrs for clients
-                let ping_addrs: Vec<SocketAddr> = {
-                    ping_clients
-                        .iter()
-                        .flat_map(|&h| client_manager.get(h))
-                        .flat_map(|(c, s)| {
-                            if s.alive && s.active_addr.is_some() {
-                                vec![s.active_addr.unwrap()]
-                            } else {
-                                s.ips
-                                    .iter()
-                                    .cloned()
-                                    .map(|ip| SocketAddr::new(ip, c.port))
-                                    .collect()
-                            }
-                        })
+                    server
+                        .active_client
+                        .get()
+                        .and_then(|h| client_manager.get(h).map(|(c, _)| (h, c.liveness, c.mac)))
+                        .into_iter()
                         .collect()
-                };
-
-                // reset alive
-                for (_, (_, s)) in client_manager.get_client_states_mut() {
-                    s.alive = false;
                 }
-
-                (ping_clients, ping_addrs)
             };
 
             if receiving && ping_clients.is_empty() {
@@ -84,55 +81,122 @@ async fn ping_task(
                 break;
             }
 
-            // ping clients
-            for addr in ping_addrs {
-                if sender_ch.send((ProtoEvent::Ping, addr)).is_err() {
-                    break;
+            pinged.retain(|h, _| ping_clients.iter().any(|(c, _, _)| c == h));
+
+            let now = Instant::now();
+            for &(handle, liveness, mac) in ping_clients.iter() {
+                let interval =
+                    Duration::from_millis(liveness.ping_interval_ms).max(MIN_PING_INTERVAL);
+                let max_missed = liveness.max_missed_pings.max(1);
+
+                // the answer to the previous ping is not due yet
+                if pinged.get(&handle).is_some_and(|p| p.due > now) {
+                    continue;
                 }
-            }
 
-            // give clients time to resond
-            if receiving {
-                log::trace!(
-                    "waiting {MAX_RESPONSE_TIME:?} for response from client with pressed keys ..."
-                );
-            } else {
+                // when anything is received from a client,
+                // the alive flag gets set
+                let (alive, ping_addrs) = {
+                    let mut client_manager = server.client_manager.borrow_mut();
+                    let Some((c, s)) = client_manager.get_mut(handle) else {
+                        continue;
+                    };
+                    let alive = s.alive;
+                    let ping_addrs: Vec<SocketAddr> = match s.active_addr {
+                        Some(addr) if s.alive => vec![addr],
+                        _ => s
+                            .ips
+                            .iter()
+                            .map(|&ip| SocketAddr::new(ip, c.port))
+                            .collect(),
+                    };
+                    s.alive = false;
+                    (alive, ping_addrs)
+                };
+
+                if let Some(p) = pinged.get_mut(&handle) {
+                    if alive {
+                        p.missed = 0;
+                    } else {
+                        p.missed += 1;
+                        log::debug!("({handle}) missed {} ping(s)", p.missed);
+                        // the ip of a client that stopped responding may have changed
+                        if p.missed == 1 {
+                            let _ = dns_notify.send(handle);
+                            // the client may be suspended, try to wake it up
+                            if let (true, Some(mac)) = (awaiting_ack, mac) {
+                                server.wake_client(handle, mac);
+                            }
+                        }
+                        if p.missed == max_missed {
+                            handle_unresponsive(
+                                server,
+                                handle,
+                                liveness.action,
+                                receiving,
+                                &emulate_notify,
+                                &capture_notify,
+                            );
+                        }
+                    }
+                }
+
+                // ping client
+                for addr in ping_addrs {
+                    if sender_ch.send((ProtoEvent::Ping, addr)).is_err() {
+                        return;
+                    }
+                }
                 log::trace!(
-                    "state: {:?} => waiting {MAX_RESPONSE_TIME:?} for client to respond ...",
+                    "state: {:?} => waiting {interval:?} for client {handle} to respond ...",
                     server.state.get()
                 );
-            }
-
-            tokio::time::sleep(MAX_RESPONSE_TIME).await;
 
-            // when anything is received from a client,
-            // the alive flag gets set
-            let unresponsive_clients: Vec<_> = {
-                let client_manager = server.client_manager.borrow();
-                ping_clients
-                    .iter()
-                    .filter_map(|&h| match client_manager.get(h) {
-                        Some((_, s)) if !s.alive => Some(h),
-                        _ => None,
-                    })
-                    .collect()
-            };
+                let missed = pinged.get(&handle).map(|p| p.missed).unwrap_or(0);
+                pinged.insert(
+                    handle,
+                    Pinged {
+                        due: now + interval,
+                        missed,
+                    },
+                );
+            }
 
-            // we may not be receiving anymore but we should respond
-            // to the original state and not the "new" one
-            if receiving {
-                for h in unresponsive_clients {
-                    log::warn!("device not responding, releasing keys!");
-                    let _ = emulate_notify.send(EmulationRequest::ReleaseKeys(h));
-                }
-            } else {
-                // release pointer if the active client has not responded
-                if !unresponsive_clients.is_empty() {
-                    log::warn!("client not responding, releasing pointer!");
-                    server.state.replace(State::Receiving);
-                    let _ = capture_notify.send(CaptureRequest::Release);
-                }
+            // give clients time to respond
+            match pinged.values().map(|p| p.due).min() {
+                Some(due) => tokio::time::sleep_until(due.into()).await,
+                None => break,
             }
         }
     }
 }
+
+fn handle_unresponsive(
+    server: &Server,
+    handle: ClientHandle,
+    action: LivenessAction,
+    receiving: bool,
+    emulate_notify: &Sender<EmulationRequest>,
+    capture_notify: &Sender<CaptureRequest>,
+) {
+    // we may not be receiving anymore but we should respond
+    // to the original state and not the "new" one
+    match action {
+        LivenessAction::Release | LivenessAction::ReleaseKeys if receiving => {
+            log::warn!("device not responding, releasing keys!");
+            let _ = emulate_notify.send(EmulationRequest::ReleaseKeys(handle));
+            server.notify_desktop(Notification::KeysReleased(handle));
+        }
+        LivenessAction::Release => {
+            // release pointer if the active client has not responded
+            log::warn!("client not responding, releasing pointer!");
+            server.set_state(State::Receiving);
+            let _ = capture_notify.send(CaptureRequest::Release);
+            server.notify_desktop(Notification::ClientUnreachable(handle));
+        }
+        LivenessAction::ReleaseKeys | LivenessAction::Warn => {
+            log::warn!("client {handle} not responding!");
+            server.notify_desktop(Notification::ClientNotResponding(handle));
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use lan_mouse_proto::ProtoEvent;
+use local_channel::mpsc::Sender;
+use tokio::task::JoinHandle;
+
+use lan_mouse_ipc::{ClientHandle, Presence};
+
+use super::Server;
+
+/// interval between heartbeats sent to active clients
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// time a client has to answer a heartbeat
+const MAX_RESPONSE_TIME: Duration = Duration::from_millis(500);
+
+/// number of consecutive unanswered heartbeats
+/// after which a client is considered offline
+const MAX_MISSED_HEARTBEATS: u32 = 3;
+
+pub(crate) fn new(server: Server, sender_ch: Sender<(ProtoEvent, SocketAddr)>) -> JoinHandle<()> {
+    tokio::task::spawn_local(async move {
+        tokio::select! {
+            _ = server.cancelled() => {}
+            _ = presence_task(&server, sender_ch) => {}
+        }
+    })
+}
+
+async fn presence_task(server: &Server, sender_ch: Sender<(ProtoEvent, SocketAddr)>) {
+    // consecutive heartbeats a client did not answer
+    let mut missed: HashMap<ClientHandle, u32> = HashMap::new();
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        interval.tick().await;
+        let sent_at = Instant::now();
+
+        let (clients, addrs) = {
+            let client_manager = server.client_manager.borrow();
+            let clients: Vec<ClientHandle> = client_manager
+                .get_client_states()
+                .filter(|(_, (_, s))| s.active)
+                .map(|(h, _)| h)
+                .collect();
+            let addrs: Vec<SocketAddr> = clients
+                .iter()
+                .flat_map(|&h| client_manager.get(h))
+                .flat_map(|(c, s)| match s.active_addr {
+                    Some(addr) if s.presence == Presence::Online => vec![addr],
+                    _ => s
+                        .ips
+                        .iter()
+                        .map(|&ip| SocketAddr::new(ip, c.port))
+                        .collect(),
+                })
+                .collect();
+            (clients, addrs)
+        };
+
+        missed.retain(|h, _| clients.contains(h));
+        if clients.is_empty() {
+            continue;
+        }
+
+        for addr in addrs {
+            if sender_ch.send((ProtoEvent::Ping, addr)).is_err() {
+                return;
+            }
+        }
+
+        tokio::time::sleep(MAX_RESPONSE_TIME).await;
+
+        // anything received from a client counts as an answer
+        for handle in clients {
+            let missed = missed.entry(handle).or_default();
+            if server.last_seen(handle).is_some_and(|t| t >= sent_at) {
+                *missed = 0;
+                server.set_presence(handle, Presence::Online);
+            } else {
+                *missed += 1;
+                log::debug!("({handle}) missed {missed} heartbeat(s)");
+                if *missed >= MAX_MISSED_HEARTBEATS {
+                    server.set_presence(handle, Presence::Offline);
+                }
+            }
+        }
+    }
+}
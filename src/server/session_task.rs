@@ -0,0 +1,263 @@
+use std::net::SocketAddr;
+
+use lan_mouse_proto::ProtoEvent;
+use local_channel::mpsc::Sender;
+use tokio::task::JoinHandle;
+
+use super::Server;
+
+pub(crate) fn new(server: Server, sender_tx: Sender<(ProtoEvent, SocketAddr)>) -> JoinHandle<()> {
+    tokio::task::spawn_local(async move {
+        if !server.config.sync_lock {
+            return;
+        }
+        tokio::select! {
+            _ = server.cancelled() => {}
+            r = session_task(&server, &sender_tx) => if let Err(e) = r {
+                log::warn!("session lock synchronization failed: {e}");
+            },
+        }
+    })
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+async fn session_task(
+    server: &Server,
+    sender_tx: &Sender<(ProtoEvent, SocketAddr)>,
+) -> zbus::Result<()> {
+    use futures::StreamExt;
+
+    let session = Session::new().await?;
+    let mut lock_changes = session.lock_changes().await;
+    let mut locked = session.locked().await;
+    // lock state requested by a peer, which must not be sent back
+    let mut requested = None;
+    // idle inhibitor held while receiving input
+    let mut inhibitor = None;
+    loop {
+        tokio::select! {
+            Some(now_locked) = lock_changes.next() => {
+                if now_locked == locked {
+                    continue;
+                }
+                locked = now_locked;
+                if requested.take() == Some(locked) {
+                    continue;
+                }
+                let event = if locked { ProtoEvent::Lock } else { ProtoEvent::Unlock };
+                log::info!("session {event}ed, notifying peers");
+                for addr in server.active_client_addrs() {
+                    let _ = sender_tx.send((event, addr));
+                }
+            }
+            lock = server.lock_requested() => {
+                if lock == locked {
+                    continue;
+                }
+                log::info!("peer requested session {}", if lock { "lock" } else { "unlock" });
+                requested = Some(lock);
+                if let Err(e) = session.set_locked(lock).await {
+                    log::warn!("could not change session lock: {e}");
+                    requested = None;
+                }
+            }
+            receiving_from = server.receiving_changed() => {
+                match (receiving_from, inhibitor.take()) {
+                    (Some(_), None) => inhibitor = session.inhibit_idle().await,
+                    (Some(_), Some(cookie)) => inhibitor = Some(cookie),
+                    (None, Some(cookie)) => session.uninhibit_idle(cookie).await,
+                    (None, None) => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+async fn session_task(
+    _server: &Server,
+    _sender_tx: &Sender<(ProtoEvent, SocketAddr)>,
+) -> Result<(), std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "not supported on this platform",
+    ))
+}
+
+/// the logind session of this process.
+/// a systemd user service is not part of a session, in this case
+/// the graphical session of the user is used
+#[cfg(all(unix, not(target_os = "macos")))]
+async fn logind_session(conn: &zbus::Connection) -> zbus::Result<zbus::zvariant::OwnedObjectPath> {
+    use zbus::zvariant::OwnedObjectPath;
+
+    let manager = zbus::Proxy::new(
+        conn,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )
+    .await?;
+    if let Ok(id) = std::env::var("XDG_SESSION_ID") {
+        return manager.call("GetSession", &(id,)).await;
+    }
+    if let Ok(path) = manager
+        .call::<_, _, OwnedObjectPath>("GetSessionByPID", &(std::process::id(),))
+        .await
+    {
+        return Ok(path);
+    }
+    let uid = unsafe { libc::getuid() };
+    let user: OwnedObjectPath = manager.call("GetUser", &(uid,)).await?;
+    let user = zbus::Proxy::new(
+        conn,
+        "org.freedesktop.login1",
+        user,
+        "org.freedesktop.login1.User",
+    )
+    .await?;
+    let (id, path): (String, OwnedObjectPath) = user.get_property("Display").await?;
+    if id.is_empty() {
+        return Err(zbus::Error::Failure(format!(
+            "user {uid} has no graphical session"
+        )));
+    }
+    Ok(path)
+}
+
+/// the local session as seen by logind and the screensaver service
+#[cfg(all(unix, not(target_os = "macos")))]
+struct Session {
+    /// org.freedesktop.login1.Session on the system bus
+    logind: Option<zbus::Proxy<'static>>,
+    /// org.freedesktop.ScreenSaver on the session bus
+    screensaver: Option<zbus::Proxy<'static>>,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Session {
+    async fn new() -> zbus::Result<Self> {
+        let logind = async {
+            let conn = zbus::Connection::system().await?;
+            let path = logind_session(&conn).await?;
+            log::info!("logind session: {}", path.as_str());
+            zbus::Proxy::new(
+                &conn,
+                "org.freedesktop.login1",
+                path,
+                "org.freedesktop.login1.Session",
+            )
+            .await
+        }
+        .await;
+        let screensaver = async {
+            let conn = zbus::Connection::session().await?;
+            zbus::Proxy::new(
+                &conn,
+                "org.freedesktop.ScreenSaver",
+                "/org/freedesktop/ScreenSaver",
+                "org.freedesktop.ScreenSaver",
+            )
+            .await
+        }
+        .await;
+        match (logind, screensaver) {
+            (Err(e), Err(_)) => Err(e),
+            (logind, screensaver) => {
+                if let Err(e) = &logind {
+                    log::warn!("logind session not available: {e}");
+                }
+                if let Err(e) = &screensaver {
+                    log::warn!("screensaver service not available: {e}");
+                }
+                Ok(Self {
+                    logind: logind.ok(),
+                    screensaver: screensaver.ok(),
+                })
+            }
+        }
+    }
+
+    async fn locked(&self) -> bool {
+        if let Some(logind) = &self.logind {
+            if let Ok(locked) = logind.get_property::<bool>("LockedHint").await {
+                return locked;
+            }
+        }
+        if let Some(screensaver) = &self.screensaver {
+            if let Ok(active) = screensaver.call::<_, _, bool>("GetActive", &()).await {
+                return active;
+            }
+        }
+        false
+    }
+
+    /// changes of the lock state, as reported by logind and the screensaver
+    async fn lock_changes(&self) -> futures::stream::BoxStream<'static, bool> {
+        use futures::StreamExt;
+
+        let mut streams = vec![];
+        if let Some(logind) = &self.logind {
+            let changes = logind.receive_property_changed::<bool>("LockedHint").await;
+            streams.push(
+                changes
+                    .filter_map(|c| async move { c.get().await.ok() })
+                    .boxed(),
+            );
+        }
+        if let Some(screensaver) = &self.screensaver {
+            match screensaver.receive_signal("ActiveChanged").await {
+                Ok(signals) => streams.push(
+                    signals
+                        .filter_map(|m| async move { m.body().deserialize::<bool>().ok() })
+                        .boxed(),
+                ),
+                Err(e) => log::warn!("could not watch screensaver: {e}"),
+            }
+        }
+        futures::stream::select_all(streams).boxed()
+    }
+
+    async fn set_locked(&self, locked: bool) -> zbus::Result<()> {
+        let method = if locked { "Lock" } else { "Unlock" };
+        match (&self.logind, &self.screensaver) {
+            (Some(logind), _) => logind.call_method(method, &()).await.map(|_| ()),
+            (None, Some(screensaver)) if locked => {
+                screensaver.call_method("Lock", &()).await.map(|_| ())
+            }
+            (None, Some(screensaver)) => screensaver
+                .call_method("SetActive", &(false,))
+                .await
+                .map(|_| ()),
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// keep the session from going idle, returns the inhibitor cookie
+    async fn inhibit_idle(&self) -> Option<u32> {
+        let screensaver = self.screensaver.as_ref()?;
+        match screensaver
+            .call::<_, _, u32>("Inhibit", &("lan-mouse", "receiving input"))
+            .await
+        {
+            Ok(cookie) => {
+                log::debug!("inhibiting idle ({cookie})");
+                Some(cookie)
+            }
+            Err(e) => {
+                log::warn!("could not inhibit idle: {e}");
+                None
+            }
+        }
+    }
+
+    async fn uninhibit_idle(&self, cookie: u32) {
+        let Some(screensaver) = &self.screensaver else {
+            return;
+        };
+        log::debug!("releasing idle inhibitor ({cookie})");
+        if let Err(e) = screensaver.call_method("UnInhibit", &(cookie,)).await {
+            log::warn!("could not release idle inhibitor: {e}");
+        }
+    }
+}
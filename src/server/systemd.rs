@@ -0,0 +1,231 @@
+//! systemd integration: sockets passed via `LISTEN_FDS`,
+//! readiness, watchdog and status notifications.
+//! without systemd (no `LISTEN_FDS` / `NOTIFY_SOCKET`) everything is a no-op.
+
+use std::{env, time::Duration};
+
+/// sockets and the notification socket passed by systemd
+#[derive(Default)]
+pub struct Systemd {
+    pub(crate) listen_fds: ListenFds,
+    pub(crate) notifier: Notifier,
+}
+
+impl Systemd {
+    /// take what systemd passed via the environment.
+    /// the variables are removed so child processes like hooks do not inherit them,
+    /// which is only sound before any other thread (e.g. of the tokio runtime) is started
+    pub fn from_env() -> Self {
+        let systemd = Self {
+            listen_fds: listen_fds(),
+            notifier: Notifier::new(),
+        };
+        for var in [
+            "LISTEN_PID",
+            "LISTEN_FDS",
+            "LISTEN_FDNAMES",
+            "NOTIFY_SOCKET",
+            "WATCHDOG_PID",
+            "WATCHDOG_USEC",
+        ] {
+            env::remove_var(var);
+        }
+        systemd
+    }
+}
+
+/// sockets passed by systemd (socket activation)
+#[derive(Default)]
+pub(crate) struct ListenFds {
+    /// socket frontends connect to
+    #[cfg(unix)]
+    pub(crate) ipc: Option<std::os::unix::net::UnixListener>,
+    /// socket for events
+    pub(crate) udp: Option<std::net::UdpSocket>,
+}
+
+#[cfg(target_os = "linux")]
+fn listen_fds() -> ListenFds {
+    use socket2::{Domain, Socket, Type};
+    use std::{
+        os::fd::{FromRawFd, OwnedFd, RawFd},
+        process,
+    };
+
+    /// first file descriptor passed by systemd
+    const SD_LISTEN_FDS_START: RawFd = 3;
+
+    let mut fds = ListenFds::default();
+    let pid = env::var("LISTEN_PID").ok().and_then(|p| p.parse().ok());
+    if pid != Some(process::id()) {
+        return fds;
+    }
+    let count: RawFd = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0);
+
+    for fd in SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count {
+        // SAFETY: systemd passes ownership of these fds to this process
+        let socket = Socket::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        if let Err(e) = socket.set_cloexec(true) {
+            log::warn!("fd {fd}: {e}");
+        }
+        match (socket.domain(), socket.r#type()) {
+            (Ok(Domain::UNIX), Ok(Type::STREAM)) if fds.ipc.is_none() => {
+                log::info!("using ipc socket passed by systemd");
+                fds.ipc = Some(socket.into());
+            }
+            (Ok(Domain::IPV4 | Domain::IPV6), Ok(Type::DGRAM)) if fds.udp.is_none() => {
+                log::info!("using udp socket passed by systemd");
+                fds.udp = Some(socket.into());
+            }
+            _ => log::warn!("ignoring unexpected socket passed by systemd (fd {fd})"),
+        }
+    }
+    fds
+}
+
+#[cfg(not(target_os = "linux"))]
+fn listen_fds() -> ListenFds {
+    ListenFds::default()
+}
+
+/// sends notifications to systemd (`sd_notify`)
+#[derive(Default)]
+pub(crate) struct Notifier {
+    #[cfg(target_os = "linux")]
+    socket: Option<(
+        std::os::unix::net::UnixDatagram,
+        std::os::unix::net::SocketAddr,
+    )>,
+    watchdog_interval: Option<Duration>,
+    ready: bool,
+    status: String,
+}
+
+impl Notifier {
+    #[cfg(target_os = "linux")]
+    fn new() -> Self {
+        use std::{
+            os::{
+                linux::net::SocketAddrExt,
+                unix::net::{SocketAddr, UnixDatagram},
+            },
+            process,
+        };
+
+        let socket = env::var("NOTIFY_SOCKET").ok().and_then(|path| {
+            let addr = match path.strip_prefix('@') {
+                Some(name) => SocketAddr::from_abstract_name(name),
+                None => SocketAddr::from_pathname(&path),
+            };
+            match addr.and_then(|addr| Ok((UnixDatagram::unbound()?, addr))) {
+                Ok(socket) => Some(socket),
+                Err(e) => {
+                    log::warn!("NOTIFY_SOCKET {path}: {e}");
+                    None
+                }
+            }
+        });
+
+        let watchdog_pid = env::var("WATCHDOG_PID").ok().and_then(|p| p.parse().ok());
+        let watchdog_interval = env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|usec| usec.parse().ok())
+            .filter(|_| watchdog_pid.is_none() || watchdog_pid == Some(process::id()))
+            // notify twice per timeout
+            .map(|usec| Duration::from_micros(usec) / 2);
+
+        Self {
+            socket,
+            watchdog_interval,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn notify(&self, state: &str) {
+        if let Some((socket, addr)) = &self.socket {
+            if let Err(e) = socket.send_to_addr(state.as_bytes(), addr) {
+                log::warn!("failed to notify systemd: {e}");
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn notify(&self, _state: &str) {}
+
+    /// the sockets of the service are bound
+    pub(crate) fn ready(&mut self) {
+        if !self.ready {
+            self.ready = true;
+            self.notify("READY=1");
+        }
+    }
+
+    /// interval in which [`Notifier::watchdog`] needs to be called
+    pub(crate) fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog_interval
+    }
+
+    pub(crate) fn watchdog(&self) {
+        self.notify("WATCHDOG=1");
+    }
+
+    /// status text shown by `systemctl status`
+    pub(crate) fn status(&mut self, status: String) {
+        if self.status != status {
+            self.notify(&format!("STATUS={status}"));
+            self.status = status;
+        }
+    }
+
+    pub(crate) fn stopping(&self) {
+        self.notify("STOPPING=1");
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+
+    #[test]
+    fn ready_once() {
+        let dir = std::env::temp_dir().join(format!("lan-mouse-notify-{}", std::process::id()));
+        let _ = std::fs::create_dir_all(&dir);
+        let path = dir.join("notify");
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        systemd.set_nonblocking(true).unwrap();
+        let mut notifier = Notifier {
+            socket: Some((
+                UnixDatagram::unbound().unwrap(),
+                std::os::unix::net::SocketAddr::from_pathname(&path).unwrap(),
+            )),
+            ..Default::default()
+        };
+
+        let mut buf = [0u8; 64];
+        let mut recv = || {
+            let len = systemd.recv(&mut buf).ok()?;
+            Some(String::from_utf8_lossy(&buf[..len]).to_string())
+        };
+        assert_eq!(recv(), None);
+        notifier.ready();
+        assert_eq!(recv().as_deref(), Some("READY=1"));
+        notifier.ready();
+        assert_eq!(recv(), None);
+        notifier.status("sending to iridium".into());
+        notifier.status("sending to iridium".into());
+        assert_eq!(recv().as_deref(), Some("STATUS=sending to iridium"));
+        assert_eq!(recv(), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
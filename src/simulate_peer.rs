@@ -0,0 +1,427 @@
+//! a simulated lan-mouse peer for end-to-end tests on a single machine.
+//!
+//! The peer answers `Enter` with `Ack` and `Ping` with `Pong`, logs every
+//! event it receives and optionally runs a script with one step per line:
+//!
+//! ```text
+//! # comment
+//! send enter | leave | ack | ping | pong | lock | unlock
+//! send motion <dx> <dy> | button <button> <state> | key <key> <state> | scroll <axis> <value>
+//! expect <event> [timeout ms]   # wait for an event, fails after the timeout (default 5000)
+//! refute <event> <ms>           # fail if the event is received within the given time
+//! sleep <ms>
+//! ```
+//!
+//! where `<event>` is one of `enter`, `leave`, `ack`, `ping`, `pong`, `lock`, `unlock`,
+//! `input`, `motion`, `button`, `scroll`, `key` or `modifiers`.
+
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use clap::Args;
+use input_event::{Event, KeyboardEvent, PointerEvent};
+use lan_mouse_proto::{ProtoEvent, ProtocolError, MAX_EVENT_SIZE};
+use thiserror::Error;
+use tokio::{net::UdpSocket, time};
+
+const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// run a simulated peer on loopback
+#[derive(Args, Clone, Debug, Eq, PartialEq)]
+pub struct SimulatePeerArgs {
+    /// port the simulated peer listens on
+    #[arg(long, default_value_t = 4243)]
+    port: u16,
+
+    /// address to listen on
+    #[arg(long, default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    bind_addr: IpAddr,
+
+    /// address of the lan-mouse instance under test
+    #[arg(long, default_value = "127.0.0.1:4242")]
+    peer: SocketAddr,
+
+    /// do not acknowledge enter events
+    #[arg(long)]
+    no_ack: bool,
+
+    /// script to run, exits with an error if an expectation fails
+    /// (runs until interrupted without a script)
+    #[arg(long)]
+    script: Option<PathBuf>,
+}
+
+#[derive(Debug, Error)]
+pub enum SimulatePeerError {
+    #[error("simulated peer: `{0}`")]
+    Io(#[from] io::Error),
+    #[error("invalid script (line {line}): {msg}")]
+    Parse { line: usize, msg: String },
+    #[error("line {line}: expected {kind} within {timeout:?}")]
+    Timeout {
+        line: usize,
+        kind: EventKind,
+        timeout: Duration,
+    },
+    #[error("line {line}: unexpected {event}")]
+    Unexpected { line: usize, event: String },
+}
+
+pub async fn run(args: SimulatePeerArgs) -> Result<(), SimulatePeerError> {
+    let script = match &args.script {
+        Some(path) => Some(parse_script(&fs::read_to_string(path)?)?),
+        None => None,
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(args.bind_addr, args.port)).await?;
+    log::info!(
+        "simulated peer listening on {}, peer: {}",
+        socket.local_addr()?,
+        args.peer
+    );
+    let peer = Peer {
+        socket,
+        peer: args.peer,
+        ack: !args.no_ack,
+    };
+
+    let Some(script) = script else {
+        tokio::select! {
+            r = peer.receive_all() => r?,
+            _ = tokio::signal::ctrl_c() => {},
+        }
+        return Ok(());
+    };
+    for (line, step) in script {
+        peer.run_step(line, step).await?;
+    }
+    log::info!("script finished");
+    Ok(())
+}
+
+struct Peer {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    ack: bool,
+}
+
+impl Peer {
+    async fn send(&self, event: ProtoEvent, addr: SocketAddr) -> io::Result<()> {
+        log::info!("-> {event}");
+        let (data, len): ([u8; MAX_EVENT_SIZE], usize) = event.into();
+        self.socket.send_to(&data[..len], addr).await?;
+        Ok(())
+    }
+
+    /// receive the next event, answering enter and ping events
+    async fn recv(&self) -> io::Result<ProtoEvent> {
+        loop {
+            let mut buf = [0u8; MAX_EVENT_SIZE];
+            let (_, addr) = self.socket.recv_from(&mut buf).await?;
+            let event = match ProtoEvent::try_from(buf) {
+                Ok(e) => e,
+                Err(ProtocolError::InvalidEventId(e)) => {
+                    log::warn!("invalid event from {addr}: {e}");
+                    continue;
+                }
+            };
+            log::info!("<- {event}");
+            match event {
+                ProtoEvent::Enter(serial) if self.ack => {
+                    self.send(ProtoEvent::Ack(serial), addr).await?
+                }
+                ProtoEvent::Ping => self.send(ProtoEvent::Pong, addr).await?,
+                _ => {}
+            }
+            return Ok(event);
+        }
+    }
+
+    async fn receive_all(&self) -> io::Result<()> {
+        loop {
+            self.recv().await?;
+        }
+    }
+
+    async fn run_step(&self, line: usize, step: Step) -> Result<(), SimulatePeerError> {
+        match step {
+            Step::Send(event) => self.send(event, self.peer).await?,
+            Step::Expect(kind, timeout) => {
+                let expect = async {
+                    loop {
+                        if kind.matches(&self.recv().await?) {
+                            return Ok::<_, io::Error>(());
+                        }
+                    }
+                };
+                match time::timeout(timeout, expect).await {
+                    Ok(r) => r?,
+                    Err(_) => {
+                        return Err(SimulatePeerError::Timeout {
+                            line,
+                            kind,
+                            timeout,
+                        })
+                    }
+                }
+            }
+            Step::Refute(kind, duration) => {
+                let refute = async {
+                    loop {
+                        let event = self.recv().await?;
+                        if kind.matches(&event) {
+                            return Ok::<_, io::Error>(event);
+                        }
+                    }
+                };
+                if let Ok(event) = time::timeout(duration, refute).await {
+                    let event = event?.to_string();
+                    return Err(SimulatePeerError::Unexpected { line, event });
+                }
+            }
+            Step::Sleep(duration) => {
+                // keep answering pings
+                if let Ok(Err(e)) = time::timeout(duration, self.receive_all()).await {
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Send(ProtoEvent),
+    Expect(EventKind, Duration),
+    Refute(EventKind, Duration),
+    Sleep(Duration),
+}
+
+/// kind of event an expectation waits for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EventKind {
+    Enter,
+    Leave,
+    Ack,
+    Ping,
+    Pong,
+    Lock,
+    Unlock,
+    Input,
+    Motion,
+    Button,
+    Scroll,
+    Key,
+    Modifiers,
+}
+
+impl EventKind {
+    fn matches(&self, event: &ProtoEvent) -> bool {
+        matches!(
+            (self, event),
+            (EventKind::Enter, ProtoEvent::Enter(_))
+                | (EventKind::Leave, ProtoEvent::Leave(_))
+                | (EventKind::Ack, ProtoEvent::Ack(_))
+                | (EventKind::Ping, ProtoEvent::Ping)
+                | (EventKind::Pong, ProtoEvent::Pong)
+                | (EventKind::Lock, ProtoEvent::Lock)
+                | (EventKind::Unlock, ProtoEvent::Unlock)
+                | (EventKind::Input, ProtoEvent::Input(_))
+                | (
+                    EventKind::Motion,
+                    ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { .. }))
+                )
+                | (
+                    EventKind::Button,
+                    ProtoEvent::Input(Event::Pointer(PointerEvent::Button { .. }))
+                )
+                | (
+                    EventKind::Scroll,
+                    ProtoEvent::Input(Event::Pointer(
+                        PointerEvent::Axis { .. } | PointerEvent::AxisDiscrete120 { .. }
+                    ))
+                )
+                | (
+                    EventKind::Key,
+                    ProtoEvent::Input(Event::Keyboard(KeyboardEvent::Key { .. }))
+                )
+                | (
+                    EventKind::Modifiers,
+                    ProtoEvent::Input(Event::Keyboard(KeyboardEvent::Modifiers { .. }))
+                )
+        )
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "enter" => Self::Enter,
+            "leave" => Self::Leave,
+            "ack" => Self::Ack,
+            "ping" => Self::Ping,
+            "pong" => Self::Pong,
+            "lock" => Self::Lock,
+            "unlock" => Self::Unlock,
+            "input" => Self::Input,
+            "motion" => Self::Motion,
+            "button" => Self::Button,
+            "scroll" => Self::Scroll,
+            "key" => Self::Key,
+            "modifiers" => Self::Modifiers,
+            _ => return Err(format!("unknown event `{s}`")),
+        })
+    }
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::Enter => "enter",
+            Self::Leave => "leave",
+            Self::Ack => "ack",
+            Self::Ping => "ping",
+            Self::Pong => "pong",
+            Self::Lock => "lock",
+            Self::Unlock => "unlock",
+            Self::Input => "input",
+            Self::Motion => "motion",
+            Self::Button => "button",
+            Self::Scroll => "scroll",
+            Self::Key => "key",
+            Self::Modifiers => "modifiers",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+fn parse_script(script: &str) -> Result<Vec<(usize, Step)>, SimulatePeerError> {
+    script
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.split('#').next().unwrap_or_default().trim()))
+        .filter(|(_, l)| !l.is_empty())
+        .map(|(line, l)| {
+            parse_step(l)
+                .map(|step| (line, step))
+                .map_err(|msg| SimulatePeerError::Parse { line, msg })
+        })
+        .collect()
+}
+
+fn parse_step(step: &str) -> Result<Step, String> {
+    let words = step.split_whitespace().collect::<Vec<_>>();
+    let arg = |i: usize| -> Result<&str, String> {
+        words
+            .get(i)
+            .copied()
+            .ok_or_else(|| format!("missing argument to `{}`", words[..i].join(" ")))
+    };
+    fn num<T: FromStr>(s: &str) -> Result<T, String> {
+        s.parse().map_err(|_| format!("invalid number `{s}`"))
+    }
+    let millis = |s: &str| num(s).map(Duration::from_millis);
+
+    let step = match arg(0)? {
+        "send" => Step::Send(match arg(1)? {
+            "enter" => ProtoEvent::Enter(0),
+            "leave" => ProtoEvent::Leave(0),
+            "ack" => ProtoEvent::Ack(0),
+            "ping" => ProtoEvent::Ping,
+            "pong" => ProtoEvent::Pong,
+            "lock" => ProtoEvent::Lock,
+            "unlock" => ProtoEvent::Unlock,
+            "motion" => ProtoEvent::Input(Event::Pointer(PointerEvent::Motion {
+                time: 0,
+                dx: num(arg(2)?)?,
+                dy: num(arg(3)?)?,
+            })),
+            "button" => ProtoEvent::Input(Event::Pointer(PointerEvent::Button {
+                time: 0,
+                button: num(arg(2)?)?,
+                state: num(arg(3)?)?,
+            })),
+            "scroll" => ProtoEvent::Input(Event::Pointer(PointerEvent::AxisDiscrete120 {
+                axis: num(arg(2)?)?,
+                value: num(arg(3)?)?,
+            })),
+            "key" => ProtoEvent::Input(Event::Keyboard(KeyboardEvent::Key {
+                time: 0,
+                key: num(arg(2)?)?,
+                state: num(arg(3)?)?,
+            })),
+            e => return Err(format!("cannot send `{e}`")),
+        }),
+        "expect" => Step::Expect(
+            arg(1)?.parse()?,
+            match words.get(2) {
+                Some(ms) => millis(ms)?,
+                None => DEFAULT_EXPECT_TIMEOUT,
+            },
+        ),
+        "refute" => Step::Refute(arg(1)?.parse()?, millis(arg(2)?)?),
+        "sleep" => Step::Sleep(millis(arg(1)?)?),
+        s => return Err(format!("unknown step `{s}`")),
+    };
+    Ok(step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_script() {
+        let script = "
+            # enter the instance under test
+            send enter
+            expect ack 100
+            send key 30 1 # KeyA
+            refute leave 50
+            expect motion
+            sleep 10
+        ";
+        let steps = parse_script(script).unwrap();
+        let lines = steps.iter().map(|(l, _)| *l).collect::<Vec<_>>();
+        assert_eq!(lines, vec![3, 4, 5, 6, 7, 8]);
+        let steps = steps.into_iter().map(|(_, s)| s).collect::<Vec<_>>();
+        assert!(matches!(steps[0], Step::Send(ProtoEvent::Enter(0))));
+        assert!(matches!(steps[1], Step::Expect(EventKind::Ack, d) if d.as_millis() == 100));
+        assert!(matches!(
+            steps[2],
+            Step::Send(ProtoEvent::Input(Event::Keyboard(KeyboardEvent::Key {
+                key: 30,
+                state: 1,
+                ..
+            })))
+        ));
+        assert!(matches!(steps[3], Step::Refute(EventKind::Leave, d) if d.as_millis() == 50));
+        assert!(matches!(
+            steps[4],
+            Step::Expect(EventKind::Motion, d) if d == DEFAULT_EXPECT_TIMEOUT
+        ));
+        assert!(matches!(steps[5], Step::Sleep(d) if d.as_millis() == 10));
+    }
+
+    #[test]
+    fn reports_invalid_steps() {
+        for (script, line) in [
+            ("send\n", 1),
+            ("\nsend motion 1\n", 2),
+            ("expect nothing", 1),
+        ] {
+            assert!(matches!(
+                parse_script(script),
+                Err(SimulatePeerError::Parse { line: l, .. }) if l == line
+            ));
+        }
+    }
+}
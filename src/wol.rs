@@ -0,0 +1,111 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+};
+
+use if_addrs::Ifv4Addr;
+use lan_mouse_ipc::MacAddress;
+
+/// port magic packets are conventionally sent to
+const WOL_PORT: u16 = 9;
+
+/// magic packet: 6 bytes 0xff followed by the mac address repeated 16 times
+fn magic_packet(mac: MacAddress) -> [u8; 102] {
+    let mut packet = [0xff; 102];
+    for chunk in packet[6..].chunks_exact_mut(6) {
+        chunk.copy_from_slice(&mac.0);
+    }
+    packet
+}
+
+/// broadcast a wake-on-lan magic packet for `mac` on the local network,
+/// restricted to the network of `interface` or `bind_addr` if configured
+pub(crate) fn wake(
+    mac: MacAddress,
+    interface: Option<&str>,
+    bind_addr: Option<IpAddr>,
+) -> io::Result<()> {
+    let bind_addr = match bind_addr {
+        Some(IpAddr::V4(ip)) => Some(ip),
+        _ => None,
+    };
+    let (ip, broadcast) = match (interface, bind_addr) {
+        (None, None) => (Ipv4Addr::UNSPECIFIED, Ipv4Addr::BROADCAST),
+        _ => {
+            let interfaces = if_addrs::get_if_addrs()?;
+            let addrs = interfaces.iter().filter_map(|i| match &i.addr {
+                if_addrs::IfAddr::V4(addr) => Some((i.name.as_str(), addr)),
+                if_addrs::IfAddr::V6(_) => None,
+            });
+            broadcast_addr(addrs, interface, bind_addr).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no ipv4 network on the configured interface / bind address",
+                )
+            })?
+        }
+    };
+    let socket = UdpSocket::bind((ip, 0))?;
+    socket.set_broadcast(true)?;
+    let addr = SocketAddr::from((broadcast, WOL_PORT));
+    socket.send_to(&magic_packet(mac), addr)?;
+    log::info!("sent wake-on-lan packet to {mac} via {addr}");
+    Ok(())
+}
+
+/// address and broadcast address of the first ipv4 network
+/// on `interface` and with the address `bind_addr`
+fn broadcast_addr<'a>(
+    addrs: impl IntoIterator<Item = (&'a str, &'a Ifv4Addr)>,
+    interface: Option<&str>,
+    bind_addr: Option<Ipv4Addr>,
+) -> Option<(Ipv4Addr, Ipv4Addr)> {
+    addrs
+        .into_iter()
+        .filter(|(name, _)| interface.is_none_or(|i| i == *name))
+        .filter(|(_, addr)| bind_addr.is_none_or(|ip| ip == addr.ip))
+        .find(|(_, addr)| !addr.ip.is_loopback())
+        .map(|(_, addr)| {
+            let broadcast = addr.broadcast.unwrap_or(addr.ip | !addr.netmask);
+            (addr.ip, broadcast)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_packet_layout() {
+        let mac = MacAddress([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        let packet = magic_packet(mac);
+        assert_eq!(packet[..6], [0xff; 6]);
+        for chunk in packet[6..].chunks(6) {
+            assert_eq!(chunk, mac.0);
+        }
+        assert_eq!(packet.len(), 6 + 16 * 6);
+    }
+
+    #[test]
+    fn broadcast_of_interface() {
+        let v4 = |ip: [u8; 4], prefixlen: u8, broadcast: Option<[u8; 4]>| Ifv4Addr {
+            ip: ip.into(),
+            netmask: Ipv4Addr::from(u32::MAX << (32 - prefixlen)),
+            prefixlen,
+            broadcast: broadcast.map(Ipv4Addr::from),
+        };
+        let lo = v4([127, 0, 0, 1], 8, None);
+        let eth = v4([192, 168, 1, 20], 24, Some([192, 168, 1, 255]));
+        let wlan = v4([10, 0, 4, 7], 22, None);
+        let addrs = || [("lo", &lo), ("eth0", &eth), ("wlan0", &wlan)];
+        let eth_broadcast = Some((eth.ip, Ipv4Addr::new(192, 168, 1, 255)));
+        let wlan_broadcast = Some((wlan.ip, Ipv4Addr::new(10, 0, 7, 255)));
+
+        assert_eq!(broadcast_addr(addrs(), Some("eth0"), None), eth_broadcast);
+        assert_eq!(broadcast_addr(addrs(), Some("wlan0"), None), wlan_broadcast);
+        assert_eq!(broadcast_addr(addrs(), None, Some(wlan.ip)), wlan_broadcast);
+        assert_eq!(broadcast_addr(addrs(), Some("eth0"), Some(wlan.ip)), None);
+        assert_eq!(broadcast_addr(addrs(), Some("lo"), None), None);
+        assert_eq!(broadcast_addr(addrs(), Some("eth1"), None), None);
+    }
+}
@@ -0,0 +1,160 @@
+#![cfg(all(unix, not(target_os = "macos")))]
+
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    net::UdpSocket,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    process::{self, Child, Command, Stdio},
+    time::Duration,
+};
+
+use futures::StreamExt;
+
+const LAN_MOUSE: &str = env!("CARGO_BIN_EXE_lan-mouse");
+
+/// kills the process when the test ends
+struct Process(Child);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.local_addr().unwrap().port()
+}
+
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lan-mouse-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir.join("run"))
+        .unwrap();
+    dir
+}
+
+/// starts a private session bus, returns `None` if dbus-daemon is not installed
+fn session_bus(dir: &Path) -> Option<(Process, String)> {
+    let config = dir.join("session.conf");
+    fs::write(
+        &config,
+        format!(
+            "<busconfig><type>session</type><listen>unix:dir={}</listen>\
+            <policy context=\"default\"><allow send_destination=\"*\"/><allow receive_sender=\"*\"/>\
+            <allow own=\"*\"/></policy></busconfig>",
+            dir.display()
+        ),
+    )
+    .unwrap();
+    let mut daemon = match Command::new("dbus-daemon")
+        .arg(format!("--config-file={}", config.display()))
+        .args(["--nofork", "--print-address"])
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(daemon) => daemon,
+        Err(e) => {
+            eprintln!("skipping: could not start dbus-daemon: {e}");
+            return None;
+        }
+    };
+    let mut address = String::new();
+    BufReader::new(daemon.stdout.take().unwrap())
+        .read_line(&mut address)
+        .unwrap();
+    Some((Process(daemon), address.trim().to_string()))
+}
+
+#[tokio::test]
+async fn methods_properties_and_signals() {
+    let dir = test_dir("dbus");
+    let Some((_bus, address)) = session_bus(&dir) else {
+        return;
+    };
+    let config = dir.join("config.toml");
+    fs::write(&config, "").unwrap();
+    let _service = Process(
+        Command::new(LAN_MOUSE)
+            .args(["--daemon", "--config"])
+            .arg(&config)
+            .args(["--capture-backend", "dummy", "--emulation-backend", "dummy"])
+            .args(["--port", &free_port().to_string()])
+            .env("XDG_RUNTIME_DIR", dir.join("run"))
+            .env("HOME", &dir)
+            .env("DBUS_SESSION_BUS_ADDRESS", &address)
+            .spawn()
+            .unwrap(),
+    );
+
+    let conn = zbus::connection::Builder::address(address.as_str()).unwrap();
+    let conn = tokio::time::timeout(Duration::from_secs(5), conn.build())
+        .await
+        .expect("session bus not responding")
+        .unwrap();
+    let dbus = zbus::fdo::DBusProxy::new(&conn).await.unwrap();
+    let name = zbus::names::BusName::try_from("de.feschber.LanMouse").unwrap();
+    for _ in 0..100 {
+        if dbus.name_has_owner(name.clone()).await.unwrap() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let lan_mouse: zbus::Proxy = zbus::proxy::Builder::new(&conn)
+        .destination("de.feschber.LanMouse")
+        .unwrap()
+        .path("/de/feschber/LanMouse")
+        .unwrap()
+        .interface("de.feschber.LanMouse")
+        .unwrap()
+        .cache_properties(zbus::proxy::CacheProperties::No)
+        .build()
+        .await
+        .unwrap();
+
+    let mut created = lan_mouse.receive_signal("ClientCreated").await.unwrap();
+    let reply = lan_mouse.call_method("Create", &()).await.unwrap();
+    let handle: u64 = reply.body().deserialize().unwrap();
+    let signal = tokio::time::timeout(Duration::from_secs(5), created.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(signal.body().deserialize::<u64>().unwrap(), handle);
+
+    let mut changed = lan_mouse.receive_signal("ClientChanged").await.unwrap();
+    lan_mouse
+        .call_method("UpdatePosition", &(handle, "right"))
+        .await
+        .unwrap();
+    type ClientInfo = (u64, String, String, u16, bool, Vec<String>, String);
+    let reply = lan_mouse.call_method("GetState", &(handle,)).await.unwrap();
+    let state: ClientInfo = reply.body().deserialize().unwrap();
+    assert_eq!((state.0, state.2.as_str()), (handle, "right"));
+    tokio::time::timeout(Duration::from_secs(5), changed.next())
+        .await
+        .unwrap()
+        .unwrap();
+    let clients: Vec<ClientInfo> = lan_mouse.get_property("Clients").await.unwrap();
+    assert_eq!(clients.len(), 1);
+    assert_eq!((clients[0].0, clients[0].2.as_str()), (handle, "right"));
+
+    let invalid = lan_mouse
+        .call_method("UpdatePosition", &(handle, "nowhere"))
+        .await;
+    assert!(matches!(
+        invalid,
+        Err(zbus::Error::MethodError(name, _, _)) if name == "org.freedesktop.DBus.Error.InvalidArgs"
+    ));
+    let missing = lan_mouse.call_method("GetState", &(handle + 1,)).await;
+    assert!(matches!(
+        missing,
+        Err(zbus::Error::MethodError(name, _, _)) if name == "org.freedesktop.DBus.Error.InvalidArgs"
+    ));
+    let _ = fs::remove_dir_all(&dir);
+}
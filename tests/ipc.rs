@@ -0,0 +1,164 @@
+#![cfg(unix)]
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::PathBuf,
+    process::{self, Child, Command},
+    thread,
+    time::Duration,
+};
+
+const LAN_MOUSE: &str = env!("CARGO_BIN_EXE_lan-mouse");
+
+/// kills the service when the test ends
+struct Service(Child);
+
+impl Drop for Service {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+    socket.local_addr().unwrap().port()
+}
+
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lan-mouse-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir.join("run"))
+        .unwrap();
+    dir
+}
+
+#[test]
+fn socket_permissions_and_tcp_token() {
+    let dir = test_dir("ipc");
+    let config = dir.join("config.toml");
+    fs::write(&config, "").unwrap();
+    let ipc_addr = format!("127.0.0.1:{}", free_port());
+    let _service = Service(
+        Command::new(LAN_MOUSE)
+            .args(["--daemon", "--no-dbus", "--config"])
+            .arg(&config)
+            .args(["--capture-backend", "dummy", "--emulation-backend", "dummy"])
+            .args(["--port", &free_port().to_string()])
+            .args(["--ipc-tcp", &ipc_addr])
+            .env("XDG_RUNTIME_DIR", dir.join("run"))
+            .env("HOME", &dir)
+            .spawn()
+            .unwrap(),
+    );
+
+    let socket = dir.join("run").join("lan-mouse-socket.sock");
+    let token_file = dir.join("run").join("lan-mouse-socket.token");
+    for _ in 0..100 {
+        if socket.exists() && token_file.exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    for file in [&socket, &token_file] {
+        let mode = fs::metadata(file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600, "{file:?}");
+    }
+
+    // a wrong token closes the connection
+    let mut stream = TcpStream::connect(&ipc_addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream.write_all(b"{\"token\":\"wrong\"}\n").unwrap();
+    let mut line = String::new();
+    let read = BufReader::new(stream).read_line(&mut line);
+    assert!(matches!(read, Ok(0) | Err(_)), "{line}");
+
+    // the token of the service is accepted
+    let token = fs::read_to_string(&token_file).unwrap();
+    let status = Command::new(LAN_MOUSE)
+        .args([
+            "ctl",
+            "--ipc-tcp",
+            &ipc_addr,
+            "connect",
+            "right",
+            "localhost",
+        ])
+        .env("LAN_MOUSE_IPC_TOKEN", token.trim())
+        .env("XDG_RUNTIME_DIR", dir.join("run"))
+        .env("HOME", &dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = Command::new(LAN_MOUSE)
+        .args(["ctl", "--json", "list"])
+        .env("XDG_RUNTIME_DIR", dir.join("run"))
+        .env("HOME", &dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("localhost"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn instances_with_separate_sockets() {
+    let dir = test_dir("ipc-instances");
+    let config = dir.join("config.toml");
+    fs::write(&config, "").unwrap();
+    let sockets = [dir.join("a.sock"), dir.join("b.sock")];
+    let _services = sockets
+        .iter()
+        .map(|socket| {
+            Service(
+                Command::new(LAN_MOUSE)
+                    .args(["--daemon", "--no-dbus", "--config"])
+                    .arg(&config)
+                    .args(["--capture-backend", "dummy", "--emulation-backend", "dummy"])
+                    .args(["--port", &free_port().to_string()])
+                    .arg("--socket")
+                    .arg(socket)
+                    .env("XDG_RUNTIME_DIR", dir.join("run"))
+                    .env("HOME", &dir)
+                    .spawn()
+                    .unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let ctl = |socket: &PathBuf, args: &[&str]| {
+        Command::new(LAN_MOUSE)
+            .arg("ctl")
+            .args(args)
+            .arg("--socket")
+            .arg(socket)
+            .env("XDG_RUNTIME_DIR", dir.join("run"))
+            .env("HOME", &dir)
+            .output()
+            .unwrap()
+    };
+    assert!(ctl(&sockets[0], &["connect", "left", "alpha"])
+        .status
+        .success());
+    assert!(ctl(&sockets[1], &["connect", "right", "beta"])
+        .status
+        .success());
+    let a = ctl(&sockets[0], &["--json", "list"]);
+    let b = ctl(&sockets[1], &["--json", "list"]);
+    let (a, b) = (
+        String::from_utf8_lossy(&a.stdout),
+        String::from_utf8_lossy(&b.stdout),
+    );
+    assert!(a.contains("alpha") && !a.contains("beta"), "{a}");
+    assert!(b.contains("beta") && !b.contains("alpha"), "{b}");
+    let _ = fs::remove_dir_all(&dir);
+}
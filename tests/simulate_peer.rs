@@ -0,0 +1,88 @@
+#![cfg(unix)]
+
+use std::{
+    fs,
+    net::UdpSocket,
+    os::unix::fs::DirBuilderExt,
+    path::PathBuf,
+    process::{self, Child, Command},
+};
+
+const LAN_MOUSE: &str = env!("CARGO_BIN_EXE_lan-mouse");
+
+/// kills the service when the test ends
+struct Service(Child);
+
+impl Drop for Service {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.local_addr().unwrap().port()
+}
+
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lan-mouse-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir.join("run"))
+        .unwrap();
+    dir
+}
+
+#[test]
+fn enter_ack_and_release() {
+    let dir = test_dir("simulate-peer");
+    let (port, peer_port) = (free_port(), free_port());
+
+    let config = dir.join("config.toml");
+    fs::write(
+        &config,
+        format!("[right]\nips = [\"127.0.0.1\"]\nport = {peer_port}\nactivate_on_startup = true\n"),
+    )
+    .unwrap();
+    let script = dir.join("script");
+    fs::write(
+        &script,
+        "
+        # the dummy capture enters the peer right away (AwaitAck)
+        expect enter 10000
+        # the ack of the simulated peer makes the service send input (Sending)
+        expect motion
+        # entering the service releases its capture (Receiving)
+        send enter
+        expect ack
+        sleep 200
+        refute motion 500
+        ",
+    )
+    .unwrap();
+
+    let _service = Service(
+        Command::new(LAN_MOUSE)
+            .args(["--daemon", "--config"])
+            .arg(&config)
+            .args(["--capture-backend", "dummy", "--emulation-backend", "dummy"])
+            .args(["--port", &port.to_string()])
+            .env("XDG_RUNTIME_DIR", dir.join("run"))
+            .env("HOME", &dir)
+            .spawn()
+            .unwrap(),
+    );
+
+    let status = Command::new(LAN_MOUSE)
+        .args(["simulate-peer", "--script"])
+        .arg(&script)
+        .args(["--port", &peer_port.to_string()])
+        .args(["--peer", &format!("127.0.0.1:{port}")])
+        .status()
+        .unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    assert!(status.success());
+}